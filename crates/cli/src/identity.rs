@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use anyhow::Context;
+
+/// The identity each lobby issued the CLI, kept between invocations.
+pub struct Identities {
+    path: PathBuf,
+    by_lobby: BTreeMap<String, String>,
+}
+
+impl Identities {
+    /// `$XDG_CONFIG_HOME/sync-player/identities.json`, falling back to `~/.config`.
+    pub fn default_path() -> Option<PathBuf> {
+        let config = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(config) => PathBuf::from(config),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config.join("sync-player").join("identities.json"))
+    }
+
+    pub fn load(path: PathBuf) -> anyhow::Result<Self> {
+        let by_lobby = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("malformed identities file: {}", path.display()))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+        };
+        Ok(Self { path, by_lobby })
+    }
+
+    pub fn get(&self, lobby: &str) -> Option<&str> {
+        self.by_lobby.get(key(lobby)).map(String::as_str)
+    }
+
+    /// Remembers the lobby's identity, writing the file when it changed.
+    pub fn save(&mut self, lobby: &str, identity: String) -> anyhow::Result<()> {
+        if self.get(lobby) == Some(identity.as_str()) {
+            return Ok(());
+        }
+        self.by_lobby.insert(key(lobby).to_string(), identity);
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        let json = serde_json::to_vec_pretty(&self.by_lobby)?;
+        // the cookies are credentials: readable by the owner only, and never left half written
+        let temp = self.path.with_extension("json.tmp");
+        write_private(&temp, &json).with_context(|| format!("failed to write {}", temp.display()))?;
+        std::fs::rename(&temp, &self.path).with_context(|| format!("failed to write {}", self.path.display()))
+    }
+}
+
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
+
+/// `http://host/chat` and `http://host/chat/` are the same lobby.
+fn key(lobby: &str) -> &str {
+    lobby.trim_end_matches('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identities_are_kept_per_lobby_across_loads() {
+        let path = std::env::temp_dir()
+            .join(format!("sync-player-cli-{}", uuid::Uuid::new_v4()))
+            .join("identities.json");
+        let mut identities = Identities::load(path.clone()).unwrap();
+        assert_eq!(identities.get("http://localhost:8080/chat"), None);
+
+        identities.save("http://localhost:8080/chat/", "participant=alice".to_string()).unwrap();
+        identities.save("http://example.com/chat", "participant=bob".to_string()).unwrap();
+
+        let identities = Identities::load(path).unwrap();
+        assert_eq!(identities.get("http://localhost:8080/chat"), Some("participant=alice"));
+        assert_eq!(identities.get("http://example.com/chat/"), Some("participant=bob"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&identities.path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert!(!identities.path.with_extension("json.tmp").exists());
+    }
+}
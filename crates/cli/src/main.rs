@@ -0,0 +1,176 @@
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::time::Duration;
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use client::{Client, Frame, RoomSocket};
+use lobby::domain::RoomId;
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use crate::identity::Identities;
+
+mod identity;
+
+#[derive(Debug, Parser)]
+#[command(about = "Scripts and debugs the rooms of a sync-player lobby")]
+struct Cli {
+    /// Base URL of the lobby, including the path its handler is mounted at.
+    #[arg(long, env = "SYNC_PLAYER_URL", default_value = "http://127.0.0.1:8080/chat")]
+    url: String,
+    /// Defaults to `$XDG_CONFIG_HOME/sync-player/identities.json`.
+    #[arg(long, env = "SYNC_PLAYER_IDENTITIES")]
+    identities: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the participant this CLI acts as.
+    Whoami,
+    /// List the open rooms.
+    Rooms,
+    /// Open a room owned by this participant.
+    Create {
+        name: String,
+        #[arg(long, default_value_t = 10)]
+        capacity: usize,
+    },
+    /// Close a room this participant owns.
+    Delete { room_id: RoomId },
+    /// Join a room, sending it JSON messages read from stdin.
+    Join {
+        room_id: RoomId,
+        /// Print every frame as a single JSON line; implied when stdin isn't a terminal.
+        #[arg(long)]
+        pipe: bool,
+        /// Once stdin is exhausted, how long to keep printing frames before leaving.
+        #[arg(long, default_value_t = 500)]
+        linger_ms: u64,
+    },
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let path = cli
+        .identities
+        .or_else(Identities::default_path)
+        .context("no place to keep the identity; pass --identities")?;
+    let mut identities = Identities::load(path)?;
+    let client = match identities.get(&cli.url) {
+        Some(identity) => Client::with_identity(&cli.url, identity)?,
+        None => Client::new(&cli.url)?,
+    };
+
+    let result = run(&client, cli.command).await;
+    // the lobby may have issued or re-signed the identity even when the command failed
+    if let Some(identity) = client.identity() {
+        identities.save(&cli.url, identity)?;
+    }
+    result
+}
+
+async fn run(client: &Client, command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Whoami => println!("{}", client.participant().await?),
+        Command::Rooms => println!("{}", serde_json::to_string_pretty(&client.list_rooms().await?)?),
+        Command::Create { name, capacity } => {
+            println!("{}", serde_json::to_string_pretty(&client.create_room(&name, capacity).await?)?)
+        }
+        Command::Delete { room_id } => client.close_room(room_id).await?,
+        Command::Join { room_id, pipe, linger_ms } => {
+            let socket = client.join(room_id).await?;
+            let pipe = pipe || !std::io::stdin().is_terminal();
+            converse(socket, pipe, Duration::from_millis(linger_ms)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Relays stdin to the room and the room's frames to stdout until either side is done.
+async fn converse(mut socket: RoomSocket<Value, Value>, pipe: bool, linger: Duration) -> anyhow::Result<()> {
+    let mut lines = stdin_lines();
+    let mut stdout = tokio::io::stdout();
+    if !pipe {
+        eprintln!("joined; type JSON messages, one per line, and end with ctrl-d");
+    }
+    let mut stdin_open = true;
+    let mut linger_until = None;
+    loop {
+        prompt(&mut stdout, !pipe && stdin_open).await?;
+        tokio::select! {
+            line = lines.recv(), if stdin_open => match line.transpose()? {
+                Some(line) if line.trim().is_empty() => {}
+                Some(line) => match serde_json::from_str::<Value>(&line) {
+                    Ok(msg) => socket.send(&msg).await?,
+                    // a script fed a broken line would otherwise go on as if it was sent
+                    Err(e) if pipe => anyhow::bail!("invalid JSON on stdin: {e}"),
+                    Err(e) => eprintln!("invalid JSON: {e}"),
+                },
+                None => {
+                    stdin_open = false;
+                    linger_until = Some(tokio::time::Instant::now() + linger);
+                }
+            },
+            frame = socket.recv() => {
+                let frame = frame?;
+                if let Frame::Closed { code, reason } = &frame {
+                    eprintln!("the lobby closed the socket: {} {reason}", code.map(|code| code.to_string()).unwrap_or_default());
+                    return Ok(());
+                }
+                print_frame(&mut stdout, &frame, pipe).await?;
+            }
+            _ = sleep_until(linger_until) => break,
+        }
+    }
+    socket.close().await?;
+    Ok(())
+}
+
+async fn prompt(stdout: &mut tokio::io::Stdout, show: bool) -> std::io::Result<()> {
+    if !show {
+        return Ok(());
+    }
+    stdout.write_all(b"> ").await?;
+    stdout.flush().await
+}
+
+async fn print_frame(stdout: &mut tokio::io::Stdout, frame: &Frame<Value>, pipe: bool) -> anyhow::Result<()> {
+    let value = match frame {
+        Frame::Message(msg) => msg.clone(),
+        Frame::Notice(notice) => serde_json::to_value(notice)?,
+        Frame::Closed { .. } => return Ok(()),
+    };
+    let text = if pipe {
+        serde_json::to_string(&value)?
+    } else {
+        // start on a fresh line rather than after the prompt
+        format!("\r{}", serde_json::to_string_pretty(&value)?)
+    };
+    stdout.write_all(text.as_bytes()).await?;
+    stdout.write_all(b"\n").await?;
+    stdout.flush().await?;
+    Ok(())
+}
+
+/// Unlike tokio's stdin, doesn't keep the process alive once the lobby hung up.
+fn stdin_lines() -> mpsc::Receiver<std::io::Result<String>> {
+    let (sender, receiver) = mpsc::channel(16);
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            if sender.blocking_send(line).is_err() {
+                return;
+            }
+        }
+    });
+    receiver
+}
+
+async fn sleep_until(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
@@ -0,0 +1,223 @@
+//! Talks to a lobby the way a browser does.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+use futures_util::{SinkExt, StreamExt};
+use lobby::{LobbyNotice, LobbyRequest, SignalKind};
+use lobby::domain::{Participant, Profile, Room, RoomId};
+use reqwest::cookie::{CookieStore, Jar};
+use reqwest::header::{COOKIE, HeaderMap, RETRY_AFTER, SET_COOKIE};
+use reqwest::{Response, Url};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::error::ProtocolError;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+/// A participant of the lobby served at a base URL, e.g. `http://localhost:8080/chat/`.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    cookies: Arc<Jar>,
+    base: Url,
+}
+
+impl Client {
+    pub fn new(base: &str) -> Result<Self, ClientError> {
+        // without the slash, relative paths would replace the lobby's mount point
+        let base = if base.ends_with('/') { base.to_string() } else { format!("{base}/") };
+        let base = Url::parse(&base).map_err(|e| ClientError::InvalidUrl(e.to_string()))?;
+        let cookies = Arc::new(Jar::default());
+        let http = reqwest::Client::builder().cookie_provider(cookies.clone()).build()?;
+        Ok(Self { http, cookies, base })
+    }
+
+    /// Continues as the participant of a previous [`Client::identity`].
+    pub fn with_identity(base: &str, identity: &str) -> Result<Self, ClientError> {
+        let client = Self::new(base)?;
+        for cookie in identity.split("; ") {
+            client.cookies.add_cookie_str(cookie, &client.base);
+        }
+        Ok(client)
+    }
+
+    /// `None` until the lobby issued an identity.
+    pub fn identity(&self) -> Option<String> {
+        let cookies = self.cookies.cookies(&self.base)?;
+        cookies.to_str().ok().map(str::to_string)
+    }
+
+    fn url(&self, path: &str) -> Url {
+        self.base.join(path).expect("paths are relative to the base URL")
+    }
+
+    pub async fn participant(&self) -> Result<Participant, ClientError> {
+        #[derive(Deserialize)]
+        struct Session {
+            participant: Participant,
+        }
+        let response = self.http.post(self.url("sessions")).send().await?;
+        Ok(ok(response).await?.json::<Session>().await?.participant)
+    }
+
+    pub async fn list_rooms(&self) -> Result<Vec<Room>, ClientError> {
+        let response = self.http.get(self.url("rooms")).send().await?;
+        Ok(ok(response).await?.json().await?)
+    }
+
+    pub async fn create_room(&self, name: &str, capacity: usize) -> Result<Room, ClientError> {
+        #[derive(Serialize)]
+        struct CreateRoomRequest<'a> {
+            name: &'a str,
+            capacity: usize,
+        }
+        let response = self
+            .http
+            .post(self.url("rooms"))
+            .json(&CreateRoomRequest { name, capacity })
+            .send()
+            .await?;
+        Ok(ok(response).await?.json().await?)
+    }
+
+    pub async fn close_room(&self, room_id: RoomId) -> Result<(), ClientError> {
+        let response = self.http.delete(self.url(&format!("rooms/{room_id}"))).send().await?;
+        ok(response).await?;
+        Ok(())
+    }
+
+    pub async fn update_profile(&self, profile: &Profile) -> Result<Profile, ClientError> {
+        let response = self.http.put(self.url("me")).json(profile).send().await?;
+        Ok(ok(response).await?.json().await?)
+    }
+
+    pub async fn join<Inbound, Outbound>(&self, room_id: RoomId) -> Result<RoomSocket<Inbound, Outbound>, ClientError> {
+        let mut url = self.url(&format!("rooms/{room_id}"));
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        url.set_scheme(scheme).expect("ws and wss are valid schemes");
+        let mut request = url.as_str().into_client_request()?;
+        if let Some(cookies) = self.cookies.cookies(&self.base) {
+            request.headers_mut().insert(COOKIE, cookies);
+        }
+        let (ws, response) = match tokio_tungstenite::connect_async(request).await {
+            Ok(connected) => connected,
+            Err(tungstenite::Error::Http(response)) => {
+                let body = response.body().as_deref().map(String::from_utf8_lossy).unwrap_or_default();
+                return Err(ClientError::Status {
+                    status: response.status().as_u16(),
+                    body: body.into_owned(),
+                    retry_after_secs: retry_after_secs(response.headers()),
+                });
+            }
+            Err(e) => return Err(e.into()),
+        };
+        // the upgrade issues an identity when this is the client's first request
+        let mut set_cookies = response.headers().get_all(SET_COOKIE).iter().peekable();
+        if set_cookies.peek().is_some() {
+            self.cookies.set_cookies(&mut set_cookies, &self.base);
+        }
+        Ok(RoomSocket { ws, messages: PhantomData })
+    }
+}
+
+async fn ok(response: Response) -> Result<Response, ClientError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let retry_after_secs = retry_after_secs(response.headers());
+    let body = response.text().await.unwrap_or_default();
+    Err(ClientError::Status { status: status.as_u16(), body, retry_after_secs })
+}
+
+/// The lobby only sends `Retry-After` as a number of seconds.
+fn retry_after_secs(headers: &HeaderMap) -> Option<u64> {
+    headers.get(RETRY_AFTER)?.to_str().ok()?.parse().ok()
+}
+
+pub struct RoomSocket<Inbound, Outbound> {
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    messages: PhantomData<fn(Inbound) -> Outbound>,
+}
+
+impl<Inbound: Serialize, Outbound: DeserializeOwned> RoomSocket<Inbound, Outbound> {
+    pub async fn send(&mut self, msg: &Inbound) -> Result<(), ClientError> {
+        self.ws.send(Message::text(serde_json::to_string(msg)?)).await?;
+        Ok(())
+    }
+
+    /// An active signal has to be repeated within the lobby's TTL.
+    pub async fn signal(&mut self, kind: SignalKind, active: bool) -> Result<(), ClientError> {
+        let request = LobbyRequest::Signal { kind, active };
+        self.ws.send(Message::text(serde_json::to_string(&request)?)).await?;
+        Ok(())
+    }
+
+    /// The next frame, skipping pings.
+    pub async fn recv(&mut self) -> Result<Frame<Outbound>, ClientError> {
+        loop {
+            let Some(msg) = self.ws.next().await else {
+                return Ok(Frame::Closed { code: None, reason: String::new() });
+            };
+            match msg? {
+                Message::Text(text) => {
+                    // the handler's messages can't be told apart by shape, but notices can
+                    return Ok(match serde_json::from_str(&text) {
+                        Ok(notice) => Frame::Notice(notice),
+                        Err(_) => Frame::Message(serde_json::from_str(&text)?),
+                    });
+                }
+                Message::Close(frame) => {
+                    return Ok(Frame::Closed {
+                        code: frame.as_ref().map(|frame| frame.code.into()),
+                        reason: frame.map(|frame| frame.reason.to_string()).unwrap_or_default(),
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Leaves the room, waiting for the lobby to hang up.
+    pub async fn close(mut self) -> Result<(), ClientError> {
+        self.ws.close(None).await?;
+        while let Some(msg) = self.ws.next().await {
+            match msg {
+                Ok(_) => {}
+                // the lobby drops the connection as soon as it sees the close frame
+                Err(tungstenite::Error::Protocol(ProtocolError::ResetWithoutClosingHandshake)) => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Frame<Outbound> {
+    Message(Outbound),
+    Notice(LobbyNotice),
+    Closed { code: Option<u16>, reason: String },
+}
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("invalid lobby url: {0}")]
+    InvalidUrl(String),
+    #[error("lobby responded with {status}: {body}")]
+    Status {
+        status: u16,
+        body: String,
+        /// How long the lobby asked to wait before retrying, when it rate limited the request.
+        retry_after_secs: Option<u64>,
+    },
+    #[error("http error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("websocket error: {0}")]
+    WebSocket(#[from] tungstenite::Error),
+    #[error("malformed message: {0}")]
+    Malformed(#[from] serde_json::Error),
+}
@@ -0,0 +1,226 @@
+use std::future::pending;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Context;
+use clap::Parser;
+use client::{Client, ClientError, Frame};
+use futures_util::future::join_all;
+use lobby::LobbyNotice;
+use lobby::domain::{Room, RoomId};
+use main::chat::{ChatInbound, ChatOutbound};
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+use crate::stats::Stats;
+
+mod stats;
+
+/// Simulates participants chatting in the rooms of a running server; raise its room creation limits first.
+#[derive(Debug, Parser)]
+#[command(about = "Load tests a sync-player chat lobby")]
+struct Cli {
+    /// Base URL of the chat lobby.
+    #[arg(long, env = "LOADTEST_URL", default_value = "http://127.0.0.1:8080/chat")]
+    url: String,
+    /// Simulated participants, spread evenly across the rooms.
+    #[arg(short, long, default_value_t = 100)]
+    participants: usize,
+    /// More than the server's room creation burst are opened as its rate limit allows.
+    #[arg(short, long, default_value_t = 5)]
+    rooms: usize,
+    /// Joins are spread over this many seconds rather than all happening at once.
+    #[arg(long, default_value_t = 5)]
+    ramp_up_secs: u64,
+    /// How long to keep sending once the ramp up is over.
+    #[arg(long, default_value_t = 30)]
+    duration_secs: u64,
+    /// Public messages each participant sends per second, broadcast to their room.
+    #[arg(long, default_value_t = 0.2)]
+    chat_rate: f64,
+    /// Sent as private messages to oneself, standing in for the position reports of a player.
+    #[arg(long, default_value_t = 1.0)]
+    heartbeat_rate: f64,
+    /// Bytes of padding in every public message.
+    #[arg(long, default_value_t = 64)]
+    chat_size: usize,
+}
+
+/// When and what one participant sends.
+struct Mix {
+    chat_every: Option<Duration>,
+    heartbeat_every: Option<Duration>,
+    padding: String,
+    /// Sent timestamps are microseconds since then.
+    epoch: Instant,
+    deadline: Instant,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    anyhow::ensure!(cli.rooms > 0, "at least one room is needed");
+    anyhow::ensure!(cli.participants >= cli.rooms, "every room needs a participant to open it");
+    let clients = (0..cli.participants)
+        .map(|_| Client::new(&cli.url))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the first participant of every room opens it
+    let capacity = cli.participants.div_ceil(cli.rooms);
+    let owners = clients[..cli.rooms].to_vec();
+    let mut rooms = Vec::with_capacity(cli.rooms);
+    for (i, owner) in owners.iter().enumerate() {
+        let room = open_room(owner, &format!("loadtest {i}"), capacity)
+            .await
+            .with_context(|| format!("failed to open room {} of {}", i + 1, cli.rooms))?;
+        rooms.push(room.id);
+    }
+    println!("opened {} rooms, ramping up {} participants", cli.rooms, cli.participants);
+
+    let epoch = Instant::now();
+    let ramp_up = Duration::from_secs(cli.ramp_up_secs);
+    let mix = Arc::new(Mix {
+        chat_every: every(cli.chat_rate),
+        heartbeat_every: every(cli.heartbeat_rate),
+        padding: "x".repeat(cli.chat_size),
+        epoch,
+        deadline: epoch + ramp_up + Duration::from_secs(cli.duration_secs),
+    });
+    let participants = clients.into_iter().enumerate().map(|(i, client)| {
+        let offset = i as f64 / cli.participants as f64;
+        let room_id = rooms[i % rooms.len()];
+        tokio::spawn(participate(client, room_id, epoch + ramp_up.mul_f64(offset), offset, mix.clone()))
+    });
+    let mut stats = Stats::default();
+    for result in join_all(participants).await {
+        match result {
+            Ok(participant) => stats.merge(participant),
+            Err(_) => stats.error("participant panicked"),
+        }
+    }
+
+    print!("{}", stats.report(cli.participants, cli.rooms, epoch.elapsed()));
+    for (owner, room_id) in owners.iter().zip(rooms) {
+        if let Err(e) = owner.close_room(room_id).await {
+            eprintln!("failed to close room {room_id}: {e}");
+        }
+    }
+    Ok(())
+}
+
+/// Waits out the server's room creation rate limit rather than giving up.
+async fn open_room(owner: &Client, name: &str, capacity: usize) -> Result<Room, ClientError> {
+    loop {
+        match owner.create_room(name, capacity).await {
+            Err(ClientError::Status { status: 429, retry_after_secs, .. }) => {
+                let wait = Duration::from_secs(retry_after_secs.unwrap_or(1).max(1));
+                println!("room creation is rate limited, retrying in {}s", wait.as_secs());
+                tokio::time::sleep(wait).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn every(rate: f64) -> Option<Duration> {
+    (rate > 0.0).then(|| Duration::from_secs_f64(1.0 / rate))
+}
+
+/// `phase` spreads the sends of different participants over each period.
+async fn participate(client: Client, room_id: RoomId, join_at: Instant, phase: f64, mix: Arc<Mix>) -> Stats {
+    let mut stats = Stats::default();
+    tokio::time::sleep_until(join_at).await;
+    let me = match client.participant().await {
+        Ok(me) => me,
+        Err(e) => {
+            stats.error(error_kind(&e, "identify failed"));
+            return stats;
+        }
+    };
+    let mut socket = match client.join::<ChatInbound, ChatOutbound>(room_id).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            stats.error(error_kind(&e, "join failed"));
+            return stats;
+        }
+    };
+    stats.connected += 1;
+
+    let mut chat = mix.chat_every.map(|period| interval(period, phase));
+    let mut heartbeat = mix.heartbeat_every.map(|period| interval(period, phase));
+    loop {
+        let outbound = tokio::select! {
+            _ = tokio::time::sleep_until(mix.deadline) => break,
+            _ = tick(&mut chat) => {
+                stats.chat_sent += 1;
+                ChatInbound::SendPublicMessage { content: format!("{} {}", stamp(&mix), mix.padding) }
+            }
+            _ = tick(&mut heartbeat) => {
+                stats.heartbeats_sent += 1;
+                ChatInbound::SendPrivateMessage { to: me, content: stamp(&mix).to_string() }
+            }
+            frame = socket.recv() => {
+                match frame {
+                    Ok(Frame::Message(
+                        ChatOutbound::PublicMessage { content, .. } | ChatOutbound::PrivateMessage { content, .. },
+                    )) => {
+                        stats.received += 1;
+                        if let Some(sent_us) = content.split(' ').next().and_then(|us| us.parse::<u64>().ok()) {
+                            stats.latencies_us.push(stamp(&mix).saturating_sub(sent_us));
+                        }
+                    }
+                    // the server's flood limits turn away messages sent faster than they allow
+                    Ok(Frame::Message(ChatOutbound::MessageRejected { .. })) => stats.error("rejected by moderation"),
+                    Ok(Frame::Message(_)) => {}
+                    Ok(Frame::Notice(LobbyNotice::RateLimited { .. })) => stats.error("rate limited"),
+                    Ok(Frame::Notice(LobbyNotice::Kicked { .. })) => stats.error("kicked"),
+                    Ok(Frame::Notice(_)) => {}
+                    Ok(Frame::Closed { .. }) => {
+                        stats.error("disconnected by the server");
+                        return stats;
+                    }
+                    Err(_) => {
+                        stats.error("receive failed");
+                        return stats;
+                    }
+                }
+                continue;
+            }
+        };
+        if socket.send(&outbound).await.is_err() {
+            stats.error("send failed");
+            return stats;
+        }
+    }
+    if socket.close().await.is_err() {
+        stats.error("close failed");
+    }
+    stats
+}
+
+fn stamp(mix: &Mix) -> u64 {
+    mix.epoch.elapsed().as_micros() as u64
+}
+
+fn interval(period: Duration, phase: f64) -> Interval {
+    let mut interval = tokio::time::interval_at(Instant::now() + period.mul_f64(phase), period);
+    // a server falling behind shouldn't be answered with a burst once it catches up
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval
+}
+
+async fn tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => pending().await,
+    }
+}
+
+/// Groups failures by what went wrong, falling back to the step that `failed`.
+fn error_kind(e: &ClientError, failed: &'static str) -> &'static str {
+    match e {
+        ClientError::Status { status: 400, .. } => "join rejected: room full",
+        ClientError::Status { status: 429, .. } => "rate limited",
+        ClientError::Status { status: 503, .. } => "server shutting down",
+        _ => failed,
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// What simulated participants observed, merged into the run's totals once they finish.
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub connected: u64,
+    pub chat_sent: u64,
+    pub heartbeats_sent: u64,
+    pub received: u64,
+    /// Microseconds from a message being sent to each participant receiving it.
+    pub latencies_us: Vec<u64>,
+    pub errors: BTreeMap<&'static str, u64>,
+}
+
+impl Stats {
+    pub fn error(&mut self, kind: &'static str) {
+        *self.errors.entry(kind).or_default() += 1;
+    }
+
+    pub fn merge(&mut self, other: Stats) {
+        self.connected += other.connected;
+        self.chat_sent += other.chat_sent;
+        self.heartbeats_sent += other.heartbeats_sent;
+        self.received += other.received;
+        self.latencies_us.extend(other.latencies_us);
+        for (kind, count) in other.errors {
+            *self.errors.entry(kind).or_default() += count;
+        }
+    }
+
+    pub fn report(mut self, participants: usize, rooms: usize, elapsed: Duration) -> Report {
+        self.latencies_us.sort_unstable();
+        Report { stats: self, participants, rooms, elapsed }
+    }
+}
+
+pub struct Report {
+    stats: Stats,
+    participants: usize,
+    rooms: usize,
+    elapsed: Duration,
+}
+
+impl Report {
+    /// The latency below which `quantile` of the deliveries arrived.
+    fn latency(&self, quantile: f64) -> Option<Duration> {
+        let latencies = &self.stats.latencies_us;
+        let index = ((latencies.len() as f64 * quantile).ceil() as usize).checked_sub(1)?;
+        latencies.get(index.min(latencies.len() - 1)).map(|us| Duration::from_micros(*us))
+    }
+
+    fn per_second(&self, count: u64) -> f64 {
+        count as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stats = &self.stats;
+        writeln!(
+            f,
+            "participants: {}/{} connected across {} rooms in {:.1}s",
+            stats.connected,
+            self.participants,
+            self.rooms,
+            self.elapsed.as_secs_f64()
+        )?;
+        writeln!(
+            f,
+            "sent:         {} chat, {} heartbeats as private messages to self ({:.1}/s)",
+            stats.chat_sent,
+            stats.heartbeats_sent,
+            self.per_second(stats.chat_sent + stats.heartbeats_sent)
+        )?;
+        writeln!(f, "received:     {} ({:.1}/s)", stats.received, self.per_second(stats.received))?;
+        match [0.5, 0.9, 0.99, 1.0].map(|quantile| self.latency(quantile)) {
+            [Some(p50), Some(p90), Some(p99), Some(max)] => writeln!(
+                f,
+                "latency:      p50 {p50:.1?}, p90 {p90:.1?}, p99 {p99:.1?}, max {max:.1?}"
+            )?,
+            _ => writeln!(f, "latency:      nothing received")?,
+        }
+        if stats.errors.is_empty() {
+            return writeln!(f, "errors:       none");
+        }
+        writeln!(f, "errors:")?;
+        for (kind, count) in &stats.errors {
+            writeln!(f, "  {kind}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merged_stats_are_reported_with_latency_percentiles() {
+        let mut stats = Stats::default();
+        for i in 0..4 {
+            let mut participant = Stats { connected: 1, chat_sent: 5, received: 25, ..Stats::default() };
+            participant.latencies_us = (1..=25).map(|us| us * 1_000 + i).collect();
+            participant.error("rate limited");
+            stats.merge(participant);
+        }
+
+        let report = stats.report(5, 1, Duration::from_secs(10));
+
+        assert_eq!(report.latency(0.5), Some(Duration::from_micros(13_001)));
+        assert_eq!(report.latency(1.0), Some(Duration::from_micros(25_003)));
+        let text = report.to_string();
+        assert!(text.contains("participants: 4/5 connected across 1 rooms"), "{text}");
+        assert!(text.contains("0 heartbeats as private messages to self"), "{text}");
+        assert!(text.contains("received:     100 (10.0/s)"), "{text}");
+        assert!(text.contains("rate limited: 4"), "{text}");
+    }
+
+    #[test]
+    fn runs_without_deliveries_report_no_latency() {
+        let report = Stats::default().report(1, 1, Duration::from_secs(1));
+
+        assert_eq!(report.latency(0.5), None);
+        assert!(report.to_string().contains("nothing received"));
+    }
+}
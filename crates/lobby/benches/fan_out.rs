@@ -0,0 +1,152 @@
+//! Broadcast latency while one participant never reads from its socket.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use lobby::config::{LobbyConfig, TokenBucketConfig};
+use lobby::domain::{MessageHandler, MessageResponse, Participant, RoomContext, RoomId};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+const FAST_PARTICIPANTS: usize = 8;
+const ROUNDS: u64 = 200;
+const PADDING: usize = 64 * 1024;
+const ROUND_TIMEOUT: Duration = Duration::from_secs(2);
+
+struct EchoHandler;
+
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+struct Ping {
+    seq: u64,
+    padding: String,
+}
+
+#[derive(Clone, Debug, Error)]
+enum EchoError {}
+
+#[async_trait]
+impl MessageHandler<Ping> for EchoHandler {
+    type Outbound = Ping;
+    type Err = EchoError;
+
+    async fn handle_message(&self, _room: &RoomContext<'_>, _from: Participant, msg: Ping) -> Result<MessageResponse<Ping>, EchoError> {
+        Ok(MessageResponse::Broadcast { msg })
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    for slow_consumer in [false, true] {
+        let report = run(slow_consumer).await?;
+        println!(
+            "slow consumer: {slow_consumer:<5} rounds: {:>3}/{ROUNDS} p50: {:>8.2?} p99: {:>8.2?} max: {:>8.2?}",
+            report.latencies.len(),
+            report.percentile(0.50),
+            report.percentile(0.99),
+            report.percentile(1.0),
+        );
+    }
+    Ok(())
+}
+
+struct Report {
+    latencies: Vec<Duration>,
+}
+
+impl Report {
+    fn percentile(&self, p: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let idx = ((self.latencies.len() - 1) as f64 * p).round() as usize;
+        self.latencies[idx]
+    }
+}
+
+async fn run(slow_consumer: bool) -> anyhow::Result<Report> {
+    let mut config = LobbyConfig::default();
+    config.rate_limits.messages = TokenBucketConfig { burst: u32::MAX, per_second: f64::MAX };
+    let router = lobby::setup(Arc::new(EchoHandler), config).await?.router;
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?.to_string();
+    let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+    let room_id = create_room(&addr, FAST_PARTICIPANTS + 1).await?;
+    let url = format!("ws://{addr}/rooms/{room_id}");
+
+    // kept alive, but never read from
+    let _slow = if slow_consumer {
+        Some(tokio_tungstenite::connect_async(&url).await?.0)
+    } else {
+        None
+    };
+
+    let (received_sender, mut received) = mpsc::unbounded_channel();
+    let mut writer = None;
+    for _ in 0..FAST_PARTICIPANTS {
+        let (ws, _) = tokio_tungstenite::connect_async(&url).await?;
+        let (sink, mut stream) = ws.split();
+        writer.get_or_insert(sink);
+        let received_sender = received_sender.clone();
+        tokio::spawn(async move {
+            while let Some(Ok(Message::Text(text))) = stream.next().await {
+                let ping: Ping = serde_json::from_str(text.as_str()).expect("echoed ping");
+                let _ = received_sender.send((ping.seq, Instant::now()));
+            }
+        });
+    }
+    let mut writer = writer.expect("at least one fast participant");
+    // give every socket time to register with the sender actor
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut latencies = Vec::with_capacity(ROUNDS as usize);
+    'rounds: for seq in 0..ROUNDS {
+        let ping = Ping { seq, padding: "x".repeat(PADDING) };
+        let started = Instant::now();
+        writer.send(Message::text(serde_json::to_string(&ping)?)).await?;
+        let mut pending = FAST_PARTICIPANTS;
+        let mut latest = started;
+        while pending > 0 {
+            match tokio::time::timeout(ROUND_TIMEOUT, received.recv()).await {
+                Ok(Some((received_seq, at))) if received_seq == seq => {
+                    pending -= 1;
+                    latest = latest.max(at);
+                }
+                Ok(Some(_)) => {}
+                Ok(None) | Err(_) => break 'rounds,
+            }
+        }
+        latencies.push(latest - started);
+    }
+
+    server.abort();
+    latencies.sort();
+    Ok(Report { latencies })
+}
+
+async fn create_room(addr: &str, capacity: usize) -> anyhow::Result<RoomId> {
+    #[derive(Deserialize)]
+    struct Created {
+        id: RoomId,
+    }
+
+    let body = serde_json::json!({ "name": "fan-out", "capacity": capacity }).to_string();
+    let mut stream = TcpStream::connect(addr).await?;
+    let request = format!(
+        "POST /rooms HTTP/1.1\r\nHost: {addr}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let (_, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| anyhow::anyhow!("malformed response: {response}"))?;
+    Ok(serde_json::from_str::<Created>(body)?.id)
+}
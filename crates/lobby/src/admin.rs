@@ -0,0 +1,274 @@
+use crate::api::{ApiError, AppState};
+use crate::app;
+use crate::app::RoomAppError;
+use crate::domain::{Participant, Room, RoomContext, RoomId};
+use crate::infrastructure::Connection;
+use crate::session::bearer_token;
+use crate::LobbyNotice;
+use anyhow::bail;
+use axum::extract::{FromRef, FromRequestParts, Path, State};
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Only a MAC is kept, so tokens are compared in constant time.
+pub(crate) struct AdminToken {
+    key: [u8; 32],
+    digest: Vec<u8>,
+}
+
+impl AdminToken {
+    pub(crate) fn new(token: &str) -> anyhow::Result<Self> {
+        if token.len() < 32 {
+            bail!("the admin token must be at least 32 bytes long");
+        }
+        let mut key = [0; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        let digest = Self::mac(&key, token).finalize().into_bytes().to_vec();
+        Ok(Self { key, digest })
+    }
+
+    pub(crate) fn verify(&self, token: &str) -> bool {
+        Self::mac(&self.key, token).verify_slice(&self.digest).is_ok()
+    }
+
+    fn mac(key: &[u8], token: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(token.as_bytes());
+        mac
+    }
+}
+
+/// A request carrying the admin token as `Authorization: Bearer <token>`.
+pub(crate) struct Operator;
+
+impl<S> FromRequestParts<S> for Operator
+where
+    Arc<AdminToken>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match bearer_token(&parts.headers) {
+            Some(Ok(token)) if Arc::<AdminToken>::from_ref(state).verify(token) => Ok(Operator),
+            _ => Err(ApiError::InvalidAdminToken),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct AdminState<Inbound, Outbound, Err>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    pub(crate) app: AppState<Inbound, Outbound, Err>,
+    pub(crate) token: Arc<AdminToken>,
+}
+
+impl<Inbound, Outbound, Err> FromRef<AdminState<Inbound, Outbound, Err>> for Arc<AdminToken>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    fn from_ref(admin_state: &AdminState<Inbound, Outbound, Err>) -> Self {
+        admin_state.token.clone()
+    }
+}
+
+pub(crate) fn router<Inbound, Outbound, Err>(admin_state: AdminState<Inbound, Outbound, Err>) -> Router
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    Router::new()
+        .route("/rooms", get(list_rooms))
+        .route("/rooms/{room_id}", get(get_room).delete(close_room))
+        .route("/participants/{participant}", delete(disconnect_participant))
+        .route("/announcements", post(announce))
+        .with_state(admin_state)
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct AdminRoom {
+    #[serde(flatten)]
+    room: Room,
+    connections: HashMap<Participant, Option<Connection>>,
+}
+
+impl AdminRoom {
+    fn new(room: Room, connections: &HashMap<Participant, Connection>) -> Self {
+        let connections = room
+            .participants
+            .iter()
+            .map(|participant| (*participant, connections.get(participant).cloned()))
+            .collect();
+        Self { room, connections }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct AdminRoomState {
+    #[serde(flatten)]
+    room: AdminRoom,
+    handler_state: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AnnouncementRequest {
+    message: String,
+}
+
+async fn connections<Inbound, Outbound, Err>(
+    app_state: &AppState<Inbound, Outbound, Err>,
+) -> Result<HashMap<Participant, Connection>, RoomAppError>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    app_state
+        .message_sender
+        .connections()
+        .await
+        .map_err(|e| RoomAppError::MessageSenderError(e.into()))
+}
+
+pub(crate) async fn list_rooms<Inbound, Outbound, Err>(
+    State(admin_state): State<AdminState<Inbound, Outbound, Err>>,
+    _: Operator,
+) -> Result<impl IntoResponse, ApiError>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    let rooms = app::list_rooms(&admin_state.app.room_repo).await?;
+    let connections = connections(&admin_state.app).await?;
+    let rooms: Vec<_> = rooms.into_iter().map(|room| AdminRoom::new(room, &connections)).collect();
+    Ok(Json(rooms))
+}
+
+/// The room along with the message handler's state for it.
+pub(crate) async fn get_room<Inbound, Outbound, Err>(
+    State(admin_state): State<AdminState<Inbound, Outbound, Err>>,
+    _: Operator,
+    Path(room_id): Path<RoomId>,
+) -> Result<impl IntoResponse, ApiError>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    let app_state = &admin_state.app;
+    let room = app_state
+        .room_repo
+        .get(room_id)
+        .await
+        .map_err(|e| RoomAppError::RoomRepositoryError(Box::new(e)))?
+        .ok_or(RoomAppError::RoomNotFound { room_id })?;
+    let profiles = app::resolve_profiles(&app_state.profile_repo, &room.participants).await?;
+    let handler_state = app_state
+        .message_handler
+        .debug_state(&RoomContext { room: &room, profiles: &profiles })
+        .await;
+    let connections = connections(app_state).await?;
+    Ok(Json(AdminRoomState { room: AdminRoom::new(room, &connections), handler_state }))
+}
+
+/// Closes the room whoever owns it, disconnecting its participants.
+pub(crate) async fn close_room<Inbound, Outbound, Err>(
+    State(admin_state): State<AdminState<Inbound, Outbound, Err>>,
+    _: Operator,
+    Path(room_id): Path<RoomId>,
+) -> Result<impl IntoResponse, ApiError>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    let room = app::force_close_room(&admin_state.app.room_repo, room_id).await?;
+    admin_state.app.message_handler.room_closed(room_id).await;
+    tracing::info!("room {room_id} closed by an operator");
+    for participant in room.participants {
+        kick(&admin_state.app, participant, "room closed by an operator").await;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Removes the participant from their rooms and closes their socket.
+pub(crate) async fn disconnect_participant<Inbound, Outbound, Err>(
+    State(admin_state): State<AdminState<Inbound, Outbound, Err>>,
+    _: Operator,
+    Path(participant): Path<Participant>,
+) -> Result<impl IntoResponse, ApiError>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    app::leave_all_rooms(&admin_state.app.room_repo, participant).await?;
+    tracing::info!("participant {participant} disconnected by an operator");
+    kick(&admin_state.app, participant, "disconnected by an operator").await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Sends a message to every participant connected to this node.
+pub(crate) async fn announce<Inbound, Outbound, Err>(
+    State(admin_state): State<AdminState<Inbound, Outbound, Err>>,
+    _: Operator,
+    Json(request): Json<AnnouncementRequest>,
+) -> Result<impl IntoResponse, ApiError>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    let notice = LobbyNotice::Announcement { message: request.message };
+    let delivered = admin_state
+        .app
+        .message_sender
+        .announce(&notice)
+        .await
+        .map_err(|e| RoomAppError::MessageSenderError(e.into()))?;
+    Ok(Json(serde_json::json!({ "delivered": delivered })))
+}
+
+async fn kick<Inbound, Outbound, Err>(app_state: &AppState<Inbound, Outbound, Err>, participant: Participant, reason: &str)
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    let notice = LobbyNotice::Kicked { reason: reason.to_string() };
+    if let Err(e) = app_state.message_sender.kick(participant, &notice, reason).await {
+        tracing::error!("failed to kick participant {participant}: {e:#}");
+    }
+}
@@ -1,27 +1,36 @@
 use crate::app;
 use crate::app::RoomAppError;
-use crate::domain::{MessageHandler, Participant, RoomError, RoomId};
-use crate::infrastructure::{InMemoryRoomRepo, MessageSenderProxy};
+use crate::domain::{MessageHandler, Participant, Profile, Room, RoomError, RoomId};
+use crate::bus::RoutingMessageSender;
+use crate::config::RoomLimits;
+use crate::docs::ApiDocs;
+use crate::health::{Health, HealthReport};
+use crate::identity::{Identity, ParticipantCookies};
+use crate::infrastructure::{DynProfileRepo, DynRoomRepo};
+use crate::metrics::Metrics;
+use crate::rate_limit::{ClientIp, RateLimits};
+use crate::session::{Sessions, bearer_token};
+use crate::signal::{SignalKind, Signals};
+use crate::{LobbyNotice, LobbyRequest};
 use axum::{Json, Router};
 use axum::extract::ws::{Message, WebSocket};
-use axum::extract::{Path, State, WebSocketUpgrade};
-use axum::http::StatusCode;
+use axum::extract::{FromRef, Path, State, WebSocketUpgrade};
+use axum::http::{HeaderMap, StatusCode};
+use axum::http::header::{CONTENT_TYPE, RETRY_AFTER, WWW_AUTHENTICATE};
 use axum::response::{IntoResponse, Response};
-use axum_extra::extract::CookieJar;
-use axum_extra::extract::cookie::Cookie;
 use futures_util::StreamExt;
 use futures_util::stream::{SplitSink, SplitStream};
+use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt::Debug;
-use std::str::FromStr;
-use std::sync::Arc;
-use axum::routing::{delete, get};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use axum::routing::{delete, get, post, put};
 use thiserror::Error;
-use uuid::Uuid;
-
-const PARTICIPANT: &str = "participant";
 
 #[derive(Clone)]
 pub(crate) struct AppState<Inbound, Outbound, Err>
@@ -31,10 +40,57 @@ where
     Err: Error + Send + Sync + 'static,
     Err: Clone,
 {
-    pub(crate) room_repo: InMemoryRoomRepo,
-    pub(crate) message_sender: MessageSenderProxy<Outbound>,
+    pub(crate) room_repo: DynRoomRepo,
+    pub(crate) profile_repo: DynProfileRepo,
+    pub(crate) message_sender: RoutingMessageSender<Outbound>,
     pub(crate) message_handler:
         Arc<dyn MessageHandler<Inbound, Outbound=Outbound, Err=Err> + Send + Sync + 'static>,
+    /// Cleared once the lobby starts shutting down.
+    pub(crate) accepting: Arc<AtomicBool>,
+    pub(crate) room_limits: RoomLimits,
+    pub(crate) cookies: Arc<ParticipantCookies>,
+    pub(crate) sessions: Arc<Sessions>,
+    pub(crate) rate_limits: Arc<RateLimits>,
+    pub(crate) signals: Arc<Signals>,
+    pub(crate) metrics: Metrics,
+    pub(crate) health: Health,
+    pub(crate) docs: Arc<ApiDocs>,
+}
+
+impl<Inbound, Outbound, Err> FromRef<AppState<Inbound, Outbound, Err>> for Arc<RateLimits>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    fn from_ref(app_state: &AppState<Inbound, Outbound, Err>) -> Self {
+        app_state.rate_limits.clone()
+    }
+}
+
+impl<Inbound, Outbound, Err> FromRef<AppState<Inbound, Outbound, Err>> for Arc<ParticipantCookies>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    fn from_ref(app_state: &AppState<Inbound, Outbound, Err>) -> Self {
+        app_state.cookies.clone()
+    }
+}
+
+impl<Inbound, Outbound, Err> FromRef<AppState<Inbound, Outbound, Err>> for Arc<Sessions>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    fn from_ref(app_state: &AppState<Inbound, Outbound, Err>) -> Self {
+        app_state.sessions.clone()
+    }
 }
 
 pub(crate) fn router<Inbound, Outbound, Err>(app_state: AppState<Inbound, Outbound, Err>) -> Router
@@ -47,19 +103,74 @@ where
     Router::new()
         .route("/rooms", get(get_rooms).post(create_room))
         .route("/rooms/{room_id}", delete(delete_room).get(join_room))
+        .route("/metrics", get(get_metrics))
+        .route("/openapi.json", get(get_openapi))
+        .route("/asyncapi.json", get(get_asyncapi))
+        .route("/healthz", get(get_health))
+        .route("/readyz", get(get_readiness))
+        .route("/me", put(update_profile))
+        .route("/participants/{participant}", get(get_profile))
+        .route("/sessions", post(create_session).delete(revoke_session))
+        .route("/sessions/refresh", post(refresh_session))
         .with_state(app_state)
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
 pub struct CreateRoomRequest {
     name: String,
     capacity: usize,
 }
 
+/// A room along with the profiles of its owner and participants.
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct RoomResponse {
+    #[serde(flatten)]
+    room: Room,
+    profiles: HashMap<Participant, Profile>,
+}
+
+impl RoomResponse {
+    async fn resolve(profile_repo: &DynProfileRepo, rooms: Vec<Room>) -> Result<Vec<Self>, ApiError> {
+        let participants: Vec<Participant> = rooms
+            .iter()
+            .flat_map(|room| room.participants.iter().copied().chain([room.created_by]))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let profiles = app::resolve_profiles(profile_repo, &participants).await?;
+        Ok(rooms
+            .into_iter()
+            .map(|room| {
+                let profiles = room
+                    .participants
+                    .iter()
+                    .chain([&room.created_by])
+                    .filter_map(|participant| Some((*participant, profiles.get(participant)?.clone())))
+                    .collect();
+                RoomResponse { room, profiles }
+            })
+            .collect())
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum ApiError {
     #[error("invalid participant cookie")]
     InvalidParticipantCookie,
+    #[error("participant cookie is not signed")]
+    UnsignedParticipantCookie,
+    #[error("invalid bearer token")]
+    InvalidToken,
+    #[error("bearer token expired")]
+    ExpiredToken,
+    #[error("bearer token revoked")]
+    RevokedToken,
+    #[error("invalid admin token")]
+    InvalidAdminToken,
+    #[error("too many requests, retry in {}s", retry_after_secs(*.0))]
+    RateLimited(Duration),
+    #[error("server is shutting down")]
+    ShuttingDown,
     #[error(transparent)]
     RoomAppError(#[from] RoomAppError),
 }
@@ -68,19 +179,53 @@ impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         match self {
             ApiError::InvalidParticipantCookie => {
-                (StatusCode::BAD_REQUEST, "invalid participant id").into_response()
+                (StatusCode::UNAUTHORIZED, "invalid participant cookie").into_response()
+            }
+            ApiError::UnsignedParticipantCookie => (
+                StatusCode::UNAUTHORIZED,
+                "participant cookie is not signed, it was issued by an older server version; \
+                 clear it to be issued a new identity",
+            )
+                .into_response(),
+            e @ (ApiError::InvalidToken
+            | ApiError::ExpiredToken
+            | ApiError::RevokedToken
+            | ApiError::InvalidAdminToken) => {
+                (StatusCode::UNAUTHORIZED, [(WWW_AUTHENTICATE, "Bearer")], e.to_string()).into_response()
+            }
+            e @ ApiError::RateLimited(retry_after) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after_secs(retry_after).to_string())],
+                e.to_string(),
+            )
+                .into_response(),
+            ApiError::ShuttingDown => {
+                (StatusCode::SERVICE_UNAVAILABLE, "server is shutting down").into_response()
             }
             ApiError::RoomAppError(e) => e.into_response(),
         }
     }
 }
 
+fn retry_after_secs(retry_after: Duration) -> u64 {
+    retry_after.as_secs_f64().ceil() as u64
+}
+
 impl IntoResponse for RoomAppError {
     fn into_response(self) -> Response {
         match self {
             RoomAppError::RoomNotFound { room_id } => {
                 (StatusCode::NOT_FOUND, format!("room {room_id} not found")).into_response()
             }
+            RoomAppError::InvalidRoom(errors) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({ "message": "invalid room", "errors": errors })),
+            )
+                .into_response(),
+            e @ RoomAppError::ProfileNotFound { .. } => {
+                (StatusCode::NOT_FOUND, e.to_string()).into_response()
+            }
+            RoomAppError::Profile(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
             RoomAppError::RoomDomain(e) => match e {
                 RoomError::RoomFull { .. } => {
                     (StatusCode::BAD_REQUEST, "room full").into_response()
@@ -99,7 +244,9 @@ impl IntoResponse for RoomAppError {
                     (StatusCode::INTERNAL_SERVER_ERROR, "internal server error").into_response()
                 }
             },
-            RoomAppError::RoomRepositoryError(_) | RoomAppError::MessageSenderError(_) => {
+            RoomAppError::RoomRepositoryError(_)
+            | RoomAppError::ProfileRepositoryError(_)
+            | RoomAppError::MessageSenderError(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "internal server error").into_response()
             }
         }
@@ -116,12 +263,13 @@ where
     Err: Clone,
 {
     let rooms = app::list_rooms(&app_state.room_repo).await?;
-    Ok(Json(rooms))
+    Ok(Json(RoomResponse::resolve(&app_state.profile_repo, rooms).await?))
 }
 
 pub(crate) async fn create_room<Inbound, Outbound, Err>(
     State(app_state): State<AppState<Inbound, Outbound, Err>>,
-    cookie_jar: CookieJar,
+    identity: Identity,
+    ClientIp(ip): ClientIp,
     Json(request): Json<CreateRoomRequest>,
 ) -> Result<impl IntoResponse, ApiError>
 where
@@ -130,21 +278,28 @@ where
     Err: Error + Send + Sync + 'static,
     Err: Clone,
 {
-    let (participant, cookie_jar) =
-        get_participant(cookie_jar).map_err(|_| ApiError::InvalidParticipantCookie)?;
+    if !app_state.accepting.load(Ordering::SeqCst) {
+        return Err(ApiError::ShuttingDown);
+    }
+    app_state
+        .rate_limits
+        .check_room_creation(identity.participant, ip)
+        .map_err(ApiError::RateLimited)?;
     let room = app::open_room(
         &app_state.room_repo,
+        &app_state.room_limits,
         request.name,
         request.capacity,
-        participant,
+        identity.participant,
     )
         .await?;
-    Ok((StatusCode::OK, cookie_jar, Json(room)))
+    let room = RoomResponse::resolve(&app_state.profile_repo, vec![room]).await?.remove(0);
+    Ok((StatusCode::OK, identity.cookie_jar, Json(room)))
 }
 
 pub(crate) async fn delete_room<Inbound, Outbound, Err>(
     State(app_state): State<AppState<Inbound, Outbound, Err>>,
-    cookie_jar: CookieJar,
+    identity: Identity,
     Path(room_id): Path<RoomId>,
 ) -> Result<impl IntoResponse, ApiError>
 where
@@ -153,15 +308,140 @@ where
     Err: Error + Send + Sync + 'static,
     Err: Clone,
 {
-    let (participant, cookie_jar) =
-        get_participant(cookie_jar).map_err(|_| ApiError::InvalidParticipantCookie)?;
-    app::close_room(&app_state.room_repo, room_id, participant).await?;
-    Ok((StatusCode::OK, cookie_jar))
+    app::close_room(&app_state.room_repo, room_id, identity.participant).await?;
+    app_state.message_handler.room_closed(room_id).await;
+    Ok((StatusCode::OK, identity.cookie_jar))
+}
+
+pub(crate) async fn get_metrics<Inbound, Outbound, Err>(
+    State(app_state): State<AppState<Inbound, Outbound, Err>>,
+) -> impl IntoResponse
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        app_state.metrics.render().await,
+    )
+}
+
+pub(crate) async fn get_openapi<Inbound, Outbound, Err>(
+    State(app_state): State<AppState<Inbound, Outbound, Err>>,
+) -> impl IntoResponse
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    Json(app_state.docs.openapi.clone())
+}
+
+pub(crate) async fn get_asyncapi<Inbound, Outbound, Err>(
+    State(app_state): State<AppState<Inbound, Outbound, Err>>,
+) -> impl IntoResponse
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    Json(app_state.docs.asyncapi.clone())
+}
+
+pub(crate) async fn get_health() -> HealthReport {
+    HealthReport::alive()
+}
+
+pub(crate) async fn get_readiness<Inbound, Outbound, Err>(
+    State(app_state): State<AppState<Inbound, Outbound, Err>>,
+) -> HealthReport
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    app_state.health.check().await
+}
+
+pub(crate) async fn update_profile<Inbound, Outbound, Err>(
+    State(app_state): State<AppState<Inbound, Outbound, Err>>,
+    identity: Identity,
+    Json(profile): Json<Profile>,
+) -> Result<impl IntoResponse, ApiError>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    let profile = app::update_profile(&app_state.profile_repo, identity.participant, profile).await?;
+    Ok((identity.cookie_jar, Json(profile)))
+}
+
+pub(crate) async fn get_profile<Inbound, Outbound, Err>(
+    State(app_state): State<AppState<Inbound, Outbound, Err>>,
+    Path(participant): Path<Participant>,
+) -> Result<impl IntoResponse, ApiError>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    Ok(Json(app::get_profile(&app_state.profile_repo, participant).await?))
+}
+
+/// Issues a bearer token for the requesting participant, or for a new one.
+pub(crate) async fn create_session<Inbound, Outbound, Err>(
+    State(app_state): State<AppState<Inbound, Outbound, Err>>,
+    identity: Identity,
+) -> Result<impl IntoResponse, ApiError>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    Ok((identity.cookie_jar, Json(app_state.sessions.issue(identity.participant))))
+}
+
+pub(crate) async fn refresh_session<Inbound, Outbound, Err>(
+    State(app_state): State<AppState<Inbound, Outbound, Err>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    let token = bearer_token(&headers).unwrap_or(Err(ApiError::InvalidToken))?;
+    Ok(Json(app_state.sessions.refresh(token)?))
+}
+
+pub(crate) async fn revoke_session<Inbound, Outbound, Err>(
+    State(app_state): State<AppState<Inbound, Outbound, Err>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError>
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    let token = bearer_token(&headers).unwrap_or(Err(ApiError::InvalidToken))?;
+    app_state.sessions.revoke(token)?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 pub(crate) async fn join_room<Inbound, Outbound, Err>(
     State(app_state): State<AppState<Inbound, Outbound, Err>>,
-    cookie_jar: CookieJar,
+    identity: Identity,
     Path(room_id): Path<RoomId>,
     ws: WebSocketUpgrade,
 ) -> Result<impl IntoResponse, ApiError>
@@ -171,14 +451,16 @@ where
     Err: Error + Send + Sync + 'static,
     Err: Clone,
 {
-    let (participant, cookie_jar) =
-        get_participant(cookie_jar).map_err(|_| ApiError::InvalidParticipantCookie)?;
+    if !app_state.accepting.load(Ordering::SeqCst) {
+        return Err(ApiError::ShuttingDown);
+    }
+    let participant = identity.participant;
     let app_state_clone = app_state.clone();
     app::join_room(&app_state.room_repo, room_id, participant).await?;
     tracing::info!("Participant {participant} joined room");
     let response =
         ws.on_upgrade(move |ws| handle_socket(app_state_clone, room_id, participant, ws));
-    Ok((cookie_jar, response))
+    Ok((identity.cookie_jar, response))
 }
 
 async fn handle_socket<Inbound, Outbound, Err>(
@@ -194,39 +476,81 @@ async fn handle_socket<Inbound, Outbound, Err>(
 {
     let (sender, mut receiver): (SplitSink<WebSocket, Message>, SplitStream<WebSocket>) =
         socket.split();
-    app_state
-        .message_sender
-        .register(participant, sender)
+    if let Err(e) = app_state.message_sender.register(participant, sender).await {
+        tracing::error!("failed to register participant {participant}: {e:#}");
+        let _ = app::leave_room(&app_state.room_repo, room_id, participant).await;
+        return;
+    }
+    let _connected = app_state.metrics.lobby.connected();
+    if let Err(e) = app::welcome(
+        &app_state.room_repo,
+        &app_state.profile_repo,
+        &app_state.message_sender,
+        app_state.message_handler.as_ref(),
+        &app_state.metrics.lobby,
+        room_id,
+        participant,
+    )
         .await
-        .expect("should never happen");
+    {
+        tracing::error!("failed to welcome participant {participant}: {e:#}");
+    }
+    for (from, kind) in app_state.signals.active_in(room_id) {
+        if from == participant {
+            continue;
+        }
+        let notice = LobbyNotice::Signal { from, kind, active: true };
+        if let Err(e) = app_state.message_sender.signal(participant, &notice, signal_key(from, kind)).await {
+            tracing::debug!("failed to send active signal to participant {participant}: {e}");
+        }
+    }
+    // messages sent in a row while rate limited
+    let mut violations = 0;
     while let Some(msg) = receiver.next().await {
         let Ok(msg) = msg else {
-            tracing::info!("participant disconnected: {}", participant);
-            app_state
-                .message_sender
-                .unregister(participant)
-                .await
-                .expect("should never happen");
-            return;
+            break;
         };
         match msg {
             Message::Text(msg) => {
+                app_state.metrics.lobby.messages_in.inc();
+                if let Err(retry_after) = app_state.rate_limits.messages.check(participant) {
+                    violations += 1;
+                    if app_state.rate_limits.kick_after.is_some_and(|kick_after| violations >= kick_after) {
+                        tracing::warn!("kicking participant {participant} for flooding");
+                        let notice = LobbyNotice::Kicked { reason: "rate limit exceeded".to_string() };
+                        if let Err(e) = app_state.message_sender.kick(participant, &notice, "rate limit exceeded").await {
+                            tracing::error!("failed to kick participant {participant}: {e:#}");
+                        }
+                        let _ = app::leave_room(&app_state.room_repo, room_id, participant).await;
+                        forget_signals(&app_state, room_id, participant).await;
+                        return;
+                    }
+                    let notice = LobbyNotice::RateLimited { retry_after_ms: retry_after.as_millis() as u64 };
+                    if let Err(e) = app_state.message_sender.notify(participant, &notice).await {
+                        tracing::warn!("failed to notify rate limited participant {participant}: {e}");
+                    }
+                    continue;
+                }
+                violations = 0;
+                if let Ok(LobbyRequest::Signal { kind, active }) = serde_json::from_slice(msg.as_bytes()) {
+                    if app_state.signals.set(room_id, participant, kind, active) {
+                        relay_signal(&app_state.room_repo, &app_state.message_sender, room_id, participant, kind, active).await;
+                    }
+                    continue;
+                }
                 tracing::info!("{participant}: {}", msg.as_str());
                 let maybe_inbound = serde_json::from_slice(msg.as_bytes());
                 let Ok(inbound) = maybe_inbound else {
                     tracing::error!("failed to deserialize inbound message: {:?}", maybe_inbound);
-                    app_state
-                        .message_sender
-                        .unregister(participant)
-                        .await
-                        .expect("should never happen");
-                    return;
+                    break;
                 };
                 let app_state_clone = app_state.clone();
                 let handle_result = app::handle_message(
                     &app_state_clone.room_repo,
+                    &app_state_clone.profile_repo,
                     &app_state_clone.message_sender,
                     app_state_clone.message_handler.as_ref(),
+                    &app_state_clone.metrics.lobby,
                     room_id,
                     participant,
                     inbound,
@@ -236,30 +560,99 @@ async fn handle_socket<Inbound, Outbound, Err>(
                     tracing::error!("failed to handle message {:?}", e)
                 }
             }
-            Message::Close(_) => {
-                tracing::info!("participant disconnected: {}", participant);
-                let _ = app::leave_room(&app_state.room_repo, room_id, participant).await;
-                app_state
-                    .message_sender
-                    .unregister(participant)
-                    .await
-                    .expect("should never happen");
-                return;
-            }
+            Message::Close(_) => break,
             _ => tracing::warn!("received unknown message type"),
         }
     }
+    // sockets dropped without a close frame free their seat too
+    tracing::info!("participant disconnected: {}", participant);
+    let _ = app::leave_room(&app_state.room_repo, room_id, participant).await;
+    forget_signals(&app_state, room_id, participant).await;
+    unregister(&app_state, participant).await;
+}
+
+/// Tells the rest of the room a participant's signal changed.
+async fn relay_signal<Outbound>(
+    room_repo: &DynRoomRepo,
+    message_sender: &RoutingMessageSender<Outbound>,
+    room_id: RoomId,
+    from: Participant,
+    kind: SignalKind,
+    active: bool,
+) where
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+{
+    let room = match room_repo.get(room_id).await {
+        Ok(Some(room)) => room,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("failed to relay signal in room {room_id}: {e:#}");
+            return;
+        }
+    };
+    let notice = LobbyNotice::Signal { from, kind, active };
+    let key = signal_key(from, kind);
+    let relays = room.participants.into_iter().filter(|to| *to != from).map(|to| {
+        let key = key.clone();
+        let notice = &notice;
+        async move { (to, message_sender.signal(to, notice, key).await) }
+    });
+    for (to, result) in futures_util::future::join_all(relays).await {
+        // signals are superseded soon enough, so a lost one isn't worth more than a trace
+        if let Err(e) = result {
+            tracing::debug!("failed to relay signal to participant {to}: {e}");
+        }
+    }
+}
+
+fn signal_key(from: Participant, kind: SignalKind) -> String {
+    format!("lobby-signal:{from}:{kind:?}")
+}
+
+/// Ends the signals of a participant leaving the room.
+async fn forget_signals<Inbound, Outbound, Err>(app_state: &AppState<Inbound, Outbound, Err>, room_id: RoomId, participant: Participant)
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    for kind in app_state.signals.clear(room_id, participant) {
+        relay_signal(&app_state.room_repo, &app_state.message_sender, room_id, participant, kind, false).await;
+    }
 }
 
-fn get_participant(cookie_jar: CookieJar) -> Result<(Participant, CookieJar), uuid::Error> {
-    match cookie_jar.get(PARTICIPANT) {
-        Some(cookie) => Ok((Uuid::from_str(cookie.value())?, cookie_jar)),
-        None => {
-            let participant = Uuid::new_v4();
-            Ok((
-                participant,
-                cookie_jar.add(Cookie::new(PARTICIPANT, participant.to_string())),
-            ))
+/// Runs until the lobby's signals are dropped along with the rest of its state.
+pub(crate) async fn expire_signals<Outbound>(
+    signals: Weak<Signals>,
+    room_repo: DynRoomRepo,
+    message_sender: RoutingMessageSender<Outbound>,
+) where
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+{
+    let Some(sweep_interval) = signals.upgrade().map(|signals| signals.sweep_interval()) else {
+        return;
+    };
+    let mut sweep = tokio::time::interval(sweep_interval);
+    loop {
+        sweep.tick().await;
+        let Some(expired) = signals.upgrade().map(|signals| signals.expire()) else {
+            return;
+        };
+        for (room_id, participant, kind) in expired {
+            relay_signal(&room_repo, &message_sender, room_id, participant, kind, false).await;
         }
     }
 }
+
+async fn unregister<Inbound, Outbound, Err>(app_state: &AppState<Inbound, Outbound, Err>, participant: Participant)
+where
+    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+    Err: Clone,
+{
+    if let Err(e) = app_state.message_sender.unregister(participant).await {
+        tracing::error!("failed to unregister participant {participant}: {e:#}");
+    }
+}
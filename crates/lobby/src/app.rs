@@ -1,4 +1,12 @@
-use crate::domain::{MessageHandler, MessageSender, MessageSenderError, Participant, Room, RoomError, RoomId, RoomRepository};
+use crate::config::RoomLimits;
+use crate::metrics::LobbyMetrics;
+use crate::domain::{
+    MessageHandler, MessageSender, MessageSenderError, Participant, Profile, ProfileError, ProfileRepository, Room,
+    RoomContext, RoomError, RoomId, RoomQuota, RoomRepository,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::error::Error;
 use thiserror::Error;
 
@@ -11,15 +19,46 @@ pub(crate) async fn list_rooms(room_repo: &impl RoomRepository) -> Result<Vec<Ro
 
 pub(crate) async fn open_room(
     room_repo: &impl RoomRepository,
+    limits: &RoomLimits,
     name: impl Into<String>,
     capacity: usize,
     participant: Participant,
 ) -> Result<Room, RoomAppError> {
-    let room = Room::new(name, capacity, participant);
-    room_repo
-        .save(room)
+    let name = name.into().trim().to_string();
+    let mut errors = Vec::new();
+    let name_len = name.chars().count();
+    if name_len == 0 || name_len > limits.max_name_len {
+        errors.push(FieldError::new("name", format!("must be 1 to {} characters", limits.max_name_len)));
+    }
+    if name.chars().any(char::is_control) {
+        errors.push(FieldError::new("name", "must not contain control characters"));
+    }
+    if capacity == 0 || capacity > limits.max_capacity {
+        errors.push(FieldError::new("capacity", format!("must be between 1 and {}", limits.max_capacity)));
+    }
+    if !errors.is_empty() {
+        return Err(RoomAppError::InvalidRoom(errors));
+    }
+
+    let quota = RoomQuota { max_rooms: limits.max_rooms, max_rooms_per_owner: limits.max_rooms_per_owner };
+    let exceeded = match room_repo
+        .insert(Room::new(name, capacity, participant), quota)
         .await
-        .map_err(|e| RoomAppError::RoomRepositoryError(Box::new(e)))
+        .map_err(|e| RoomAppError::RoomRepositoryError(Box::new(e)))?
+    {
+        Ok(room) => return Ok(room),
+        Err(exceeded) => exceeded,
+    };
+    if exceeded.rooms {
+        errors.push(FieldError::quota(format!("no more than {} rooms may be open", limits.max_rooms)));
+    }
+    if exceeded.per_owner {
+        errors.push(FieldError::quota(format!(
+            "no participant may own more than {} rooms",
+            limits.max_rooms_per_owner
+        )));
+    }
+    Err(RoomAppError::InvalidRoom(errors))
 }
 
 pub(crate) async fn close_room(
@@ -44,14 +83,7 @@ pub(crate) async fn join_room(
     room_id: RoomId,
     participant: Participant,
 ) -> Result<(), RoomAppError> {
-    let room = room_repo
-        .get(room_id)
-        .await
-        .map_err(|e| RoomAppError::RoomRepositoryError(Box::new(e)))?;
-    let mut room = room.ok_or(RoomAppError::RoomNotFound { room_id })?;
-    room.join(participant)?;
-    room_repo.save(room).await.map_err(|e| RoomAppError::RoomRepositoryError(Box::new(e)))?;
-    Ok(())
+    update_room(room_repo, room_id, |room| room.join(participant)).await
 }
 
 pub(crate) async fn leave_room(
@@ -59,47 +91,187 @@ pub(crate) async fn leave_room(
     room_id: RoomId,
     participant_id: Participant,
 ) -> Result<(), RoomAppError> {
+    update_room(room_repo, room_id, |room| {
+        room.leave(participant_id);
+        Ok(())
+    })
+    .await
+}
+
+/// Retries `change` whenever the room changed in between, e.g. on another node.
+async fn update_room(
+    room_repo: &impl RoomRepository,
+    room_id: RoomId,
+    change: impl Fn(&mut Room) -> Result<(), RoomError>,
+) -> Result<(), RoomAppError> {
+    loop {
+        let current = room_repo
+            .get(room_id)
+            .await
+            .map_err(|e| RoomAppError::RoomRepositoryError(Box::new(e)))?
+            .ok_or(RoomAppError::RoomNotFound { room_id })?;
+        let mut room = current.clone();
+        change(&mut room)?;
+        let replaced = room_repo
+            .replace(&current, room)
+            .await
+            .map_err(|e| RoomAppError::RoomRepositoryError(Box::new(e)))?;
+        if replaced {
+            return Ok(());
+        }
+    }
+}
+
+/// Closes the room whoever owns it, returning it so its participants can be disconnected.
+pub(crate) async fn force_close_room(room_repo: &impl RoomRepository, room_id: RoomId) -> Result<Room, RoomAppError> {
     let room = room_repo
         .get(room_id)
         .await
         .map_err(|e| RoomAppError::RoomRepositoryError(Box::new(e)))?;
-    let mut room = room.ok_or(RoomAppError::RoomNotFound { room_id })?;
-    room.leave(participant_id);
-    Ok(())
+    let room = room.ok_or(RoomAppError::RoomNotFound { room_id })?;
+    room_repo
+        .delete(room_id)
+        .await
+        .map_err(|e| RoomAppError::RoomRepositoryError(Box::new(e)))?;
+    Ok(room)
+}
+
+/// Removes the participant from every room they are in, returning those rooms.
+pub(crate) async fn leave_all_rooms(
+    room_repo: &impl RoomRepository,
+    participant: Participant,
+) -> Result<Vec<RoomId>, RoomAppError> {
+    let rooms = list_rooms(room_repo).await?;
+    let mut left = Vec::new();
+    for room in rooms {
+        if room.participants.contains(&participant) {
+            leave_room(room_repo, room.id, participant).await?;
+            left.push(room.id);
+        }
+    }
+    Ok(left)
+}
+
+pub(crate) async fn update_profile(
+    profile_repo: &impl ProfileRepository,
+    participant: Participant,
+    profile: Profile,
+) -> Result<Profile, RoomAppError> {
+    profile.validate()?;
+    let profile = Profile {
+        display_name: profile.display_name.trim().to_string(),
+        ..profile
+    };
+    profile_repo
+        .save(participant, profile)
+        .await
+        .map_err(|e| RoomAppError::ProfileRepositoryError(Box::new(e)))
+}
+
+pub(crate) async fn get_profile(
+    profile_repo: &impl ProfileRepository,
+    participant: Participant,
+) -> Result<Profile, RoomAppError> {
+    resolve_profiles(profile_repo, &[participant])
+        .await?
+        .remove(&participant)
+        .ok_or(RoomAppError::ProfileNotFound { participant })
 }
 
-pub(crate) async fn handle_message<Inbound, Outbound: Clone>(
+pub(crate) async fn resolve_profiles(
+    profile_repo: &impl ProfileRepository,
+    participants: &[Participant],
+) -> Result<HashMap<Participant, Profile>, RoomAppError> {
+    profile_repo
+        .get_many(participants)
+        .await
+        .map_err(|e| RoomAppError::ProfileRepositoryError(Box::new(e)))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn handle_message<Inbound, Outbound>(
     room_repo: &impl RoomRepository,
+    profile_repo: &impl ProfileRepository,
     msg_sender: &impl MessageSender<Outbound>,
     msg_handler: &dyn MessageHandler<Inbound, Outbound=Outbound, Err=impl Error + Send + Sync + 'static>,
+    metrics: &LobbyMetrics,
     room_id: RoomId,
     participant: Participant,
     inbound_msg: Inbound,
 ) -> Result<(), RoomAppError>
 where
     Inbound: Send + Sync + 'static,
-    Outbound: Send + Sync + 'static,
+    Outbound: Clone + Send + Sync + 'static,
 {
     let room = room_repo
         .get(room_id)
         .await
         .map_err(|e| RoomAppError::RoomRepositoryError(Box::new(e)))?;
     let room = room.ok_or(RoomAppError::RoomNotFound { room_id })?;
+    let profiles = resolve_profiles(profile_repo, &room.participants).await?;
+    let handler_timer = metrics.handler_latency.start_timer();
     let responses = room
-        .handle_message(msg_handler, participant, inbound_msg)
+        .handle_message(msg_handler, &profiles, participant, inbound_msg)
         .await?;
-    for (to, outbound_msg) in responses {
-        let result = msg_sender
-            .send(to, outbound_msg)
-            .await;
-        if let Err(e) = result {
-            match e {
-                MessageSenderError::ParticipantDisconnected(participant, _) => {
-                    leave_room(room_repo, room_id, participant).await?
-                }
-                MessageSenderError::MessageSenderError(_) => {
-                    return Err(RoomAppError::MessageSenderError(Box::new(e)));
-                }
+    handler_timer.observe_duration();
+    deliver(room_repo, msg_sender, metrics, room_id, responses).await
+}
+
+/// Sends a participant whose socket just connected whatever the handler has for newcomers.
+pub(crate) async fn welcome<Inbound, Outbound>(
+    room_repo: &impl RoomRepository,
+    profile_repo: &impl ProfileRepository,
+    msg_sender: &impl MessageSender<Outbound>,
+    msg_handler: &dyn MessageHandler<Inbound, Outbound=Outbound, Err=impl Error + Send + Sync + 'static>,
+    metrics: &LobbyMetrics,
+    room_id: RoomId,
+    participant: Participant,
+) -> Result<(), RoomAppError>
+where
+    Inbound: Send + Sync + 'static,
+    Outbound: Clone + Send + Sync + 'static,
+{
+    let room = room_repo
+        .get(room_id)
+        .await
+        .map_err(|e| RoomAppError::RoomRepositoryError(Box::new(e)))?;
+    let room = room.ok_or(RoomAppError::RoomNotFound { room_id })?;
+    let profiles = resolve_profiles(profile_repo, &room.participants).await?;
+    let context = RoomContext { room: &room, profiles: &profiles };
+    let msgs = msg_handler.on_join(&context, participant).await;
+    if msgs.is_empty() {
+        return Ok(());
+    }
+    let responses = msgs.into_iter().map(|msg| (participant, msg)).collect();
+    deliver(room_repo, msg_sender, metrics, room_id, responses).await
+}
+
+async fn deliver<Outbound>(
+    room_repo: &impl RoomRepository,
+    msg_sender: &impl MessageSender<Outbound>,
+    metrics: &LobbyMetrics,
+    room_id: RoomId,
+    responses: Vec<(Participant, Outbound)>,
+) -> Result<(), RoomAppError>
+where
+    Outbound: Clone + Send + Sync + 'static,
+{
+    let sent = responses.len();
+    let send_timer = metrics.send_latency.start_timer();
+    let errors = msg_sender.send_all(responses).await;
+    send_timer.observe_duration();
+    metrics.messages_out.inc_by(sent.saturating_sub(errors.len()) as u64);
+    for e in errors {
+        metrics.send_failed(&e);
+        match e {
+            MessageSenderError::ParticipantDisconnected(participant, _) => {
+                leave_room(room_repo, room_id, participant).await?
+            }
+            MessageSenderError::QueueFull(participant) => {
+                tracing::warn!("dropped message for slow participant: {participant}");
+            }
+            MessageSenderError::MessageSenderError(_) => {
+                return Err(RoomAppError::MessageSenderError(Box::new(e)));
             }
         }
     }
@@ -110,10 +282,44 @@ where
 pub enum RoomAppError {
     #[error("room not found: {room_id}")]
     RoomNotFound { room_id: RoomId },
+    #[error("invalid room: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    InvalidRoom(Vec<FieldError>),
+    #[error("participant has no profile: {participant}")]
+    ProfileNotFound { participant: Participant },
     #[error(transparent)]
     RoomDomain(#[from] RoomError),
+    #[error(transparent)]
+    Profile(#[from] ProfileError),
     #[error("room repository error: {0}")]
     RoomRepositoryError(#[source] Box<dyn Error + Send + Sync + 'static>),
+    #[error("profile repository error: {0}")]
+    ProfileRepositoryError(#[source] Box<dyn Error + Send + Sync + 'static>),
     #[error("message sender error: {0}")]
     MessageSenderError(#[source] Box<dyn Error + Send + Sync + 'static>),
 }
+
+/// `field` is `None` for limits like quotas.
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+pub struct FieldError {
+    pub field: Option<&'static str>,
+    pub message: String,
+}
+
+impl FieldError {
+    fn new(field: &'static str, message: impl Into<String>) -> Self {
+        Self { field: Some(field), message: message.into() }
+    }
+
+    fn quota(message: impl Into<String>) -> Self {
+        Self { field: None, message: message.into() }
+    }
+}
+
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.field {
+            Some(field) => write!(f, "{field} {}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use crate::domain::{MessageSender, MessageSenderError, Participant};
+use crate::infrastructure::{CoalesceKey, Connection, InfrastructureError, MessageSenderProxy};
+use crate::LobbyNotice;
+use anyhow::anyhow;
+use async_trait::async_trait;
+use axum::extract::ws::Message;
+use futures_util::Sink;
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, PoisonError, RwLock};
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
+
+/// Identifies one server process taking part in a cluster.
+pub type NodeId = Uuid;
+
+/// An already serialized outbound message, as it travels between nodes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct Envelope {
+    pub(crate) key: Option<String>,
+    pub(crate) payload: String,
+}
+
+/// A message routed to this node for one of the participants whose socket it holds.
+pub(crate) struct Delivery {
+    pub(crate) to: Participant,
+    pub(crate) envelope: Envelope,
+    pub(crate) result_sender: oneshot::Sender<Result<(), DeliveryError>>,
+}
+
+/// Outcome of a delivery on the owning node, in a form that can cross the network.
+#[derive(Clone, Debug, Error, Serialize, Deserialize)]
+pub(crate) enum DeliveryError {
+    #[error("participant disconnected")]
+    ParticipantDisconnected,
+    #[error("outbound queue full")]
+    QueueFull,
+    #[error("no node holds a socket for the participant")]
+    NotFound,
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl DeliveryError {
+    fn into_sender_error(self, participant: Participant) -> MessageSenderError {
+        match self {
+            DeliveryError::ParticipantDisconnected => MessageSenderError::ParticipantDisconnected(
+                participant,
+                Box::new(InfrastructureError(anyhow!("remote participant disconnected: {participant}"))),
+            ),
+            DeliveryError::QueueFull => MessageSenderError::QueueFull(participant),
+            DeliveryError::NotFound => MessageSenderError::MessageSenderError(Box::new(InfrastructureError(
+                anyhow!("sink not found for participant: {participant}"),
+            ))),
+            DeliveryError::Failed(e) => MessageSenderError::MessageSenderError(Box::new(InfrastructureError(anyhow!(e)))),
+        }
+    }
+}
+
+impl From<MessageSenderError> for DeliveryError {
+    fn from(e: MessageSenderError) -> Self {
+        match e {
+            MessageSenderError::ParticipantDisconnected(..) => DeliveryError::ParticipantDisconnected,
+            MessageSenderError::QueueFull(_) => DeliveryError::QueueFull,
+            MessageSenderError::MessageSenderError(e) => DeliveryError::Failed(e.to_string()),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("message bus error: {0}")]
+pub struct BusError(#[from] pub(crate) anyhow::Error);
+
+/// Routes outbound messages to whichever node holds the addressed participant's socket.
+#[async_trait]
+pub(crate) trait MessageBus: Send + Sync + 'static {
+    /// Attaches a node to the bus, returning the deliveries addressed to its participants.
+    async fn join(&self, node: NodeId) -> Result<mpsc::Receiver<Delivery>, BusError>;
+    /// Records that `node` holds the socket of `participant`.
+    async fn claim(&self, node: NodeId, participant: Participant) -> Result<(), BusError>;
+    async fn release(&self, node: NodeId, participant: Participant) -> Result<(), BusError>;
+    async fn publish(&self, to: Participant, envelope: Envelope) -> Result<(), DeliveryError>;
+}
+
+/// Bus connecting the nodes living in a single process.
+#[derive(Default)]
+pub(crate) struct InProcessBus {
+    state: RwLock<InProcessBusState>,
+}
+
+#[derive(Default)]
+struct InProcessBusState {
+    nodes: HashMap<NodeId, mpsc::Sender<Delivery>>,
+    owners: HashMap<Participant, NodeId>,
+}
+
+#[async_trait]
+impl MessageBus for InProcessBus {
+    async fn join(&self, node: NodeId) -> Result<mpsc::Receiver<Delivery>, BusError> {
+        let (sender, receiver) = mpsc::channel(DELIVERY_BUFFER);
+        self.state.write().unwrap_or_else(PoisonError::into_inner).nodes.insert(node, sender);
+        Ok(receiver)
+    }
+
+    async fn claim(&self, node: NodeId, participant: Participant) -> Result<(), BusError> {
+        self.state.write().unwrap_or_else(PoisonError::into_inner).owners.insert(participant, node);
+        Ok(())
+    }
+
+    async fn release(&self, node: NodeId, participant: Participant) -> Result<(), BusError> {
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        if state.owners.get(&participant) == Some(&node) {
+            state.owners.remove(&participant);
+        }
+        Ok(())
+    }
+
+    async fn publish(&self, to: Participant, envelope: Envelope) -> Result<(), DeliveryError> {
+        let node = {
+            let state = self.state.read().unwrap_or_else(PoisonError::into_inner);
+            state
+                .owners
+                .get(&to)
+                .and_then(|node| state.nodes.get(node))
+                .cloned()
+                .ok_or(DeliveryError::NotFound)?
+        };
+        let (result_sender, result_receiver) = oneshot::channel();
+        node.send(Delivery { to, envelope, result_sender })
+            .await
+            .map_err(|_| DeliveryError::Failed("node left the bus".to_string()))?;
+        result_receiver
+            .await
+            .map_err(|_| DeliveryError::Failed("node dropped the delivery".to_string()))?
+    }
+}
+
+pub(crate) const DELIVERY_BUFFER: usize = 1024;
+
+/// Hands deliveries routed to this node over to its local sender actor, in order.
+pub(crate) async fn deliver<M: Send + Sync + 'static>(
+    mut deliveries: mpsc::Receiver<Delivery>,
+    local: MessageSenderProxy<M>,
+) {
+    while let Some(Delivery { to, envelope, result_sender }) = deliveries.recv().await {
+        let result = local.send_envelope(to, envelope).await.map_err(DeliveryError::from);
+        let _ = result_sender.send(result);
+    }
+}
+
+pub(crate) struct RoutingMessageSender<M: Send + Sync + 'static> {
+    node: NodeId,
+    local: MessageSenderProxy<M>,
+    local_participants: Arc<RwLock<HashSet<Participant>>>,
+    bus: Arc<dyn MessageBus>,
+    coalesce_key: CoalesceKey<M>,
+}
+
+impl<M: Send + Sync + 'static> Clone for RoutingMessageSender<M> {
+    fn clone(&self) -> Self {
+        Self {
+            node: self.node,
+            local: self.local.clone(),
+            local_participants: self.local_participants.clone(),
+            bus: self.bus.clone(),
+            coalesce_key: self.coalesce_key.clone(),
+        }
+    }
+}
+
+impl<M: Serialize + Send + Sync + 'static> RoutingMessageSender<M> {
+    pub(crate) fn new(node: NodeId, local: MessageSenderProxy<M>, bus: Arc<dyn MessageBus>, coalesce_key: CoalesceKey<M>) -> Self {
+        Self {
+            node,
+            local,
+            local_participants: Default::default(),
+            bus,
+            coalesce_key,
+        }
+    }
+
+    pub(crate) async fn register(
+        &self,
+        participant: Participant,
+        ws_sender: impl Sink<Message, Error = axum::Error> + Send + 'static,
+    ) -> Result<(), anyhow::Error> {
+        self.local.register(participant, ws_sender).await?;
+        self.local_participants
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(participant);
+        self.bus.claim(self.node, participant).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn unregister(&self, participant: Participant) -> Result<(), anyhow::Error> {
+        self.local_participants
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&participant);
+        self.bus.release(self.node, participant).await?;
+        self.local.unregister(participant).await
+    }
+
+    /// Sends a lobby frame to a participant connected to this node.
+    pub(crate) async fn notify(&self, participant: Participant, notice: &LobbyNotice) -> Result<(), MessageSenderError> {
+        let payload = serde_json::to_string(notice).map_err(|e| MessageSenderError::MessageSenderError(Box::new(e)))?;
+        self.local.send_envelope(participant, Envelope { key: None, payload }).await
+    }
+
+    pub(crate) async fn signal(&self, to: Participant, notice: &LobbyNotice, key: String) -> Result<(), MessageSenderError> {
+        let payload = serde_json::to_string(notice).map_err(|e| MessageSenderError::MessageSenderError(Box::new(e)))?;
+        let envelope = Envelope { key: Some(key), payload };
+        if self.is_local(to) {
+            self.local.send_envelope(to, envelope).await
+        } else {
+            self.bus.publish(to, envelope).await.map_err(|e| e.into_sender_error(to))
+        }
+    }
+
+    /// Disconnects a participant connected to this node, sending `notice` first.
+    pub(crate) async fn kick(&self, participant: Participant, notice: &LobbyNotice, reason: &str) -> Result<(), anyhow::Error> {
+        self.local_participants
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&participant);
+        self.bus.release(self.node, participant).await?;
+        self.local.kick(participant, serde_json::to_string(notice)?, reason).await
+    }
+
+    /// The sockets held by this node.
+    pub(crate) async fn connections(&self) -> Result<HashMap<Participant, Connection>, anyhow::Error> {
+        self.local.connections().await
+    }
+
+    /// Returns how many participants the frame was queued for.
+    pub(crate) async fn announce(&self, notice: &LobbyNotice) -> Result<usize, anyhow::Error> {
+        self.local.announce(serde_json::to_string(notice)?).await
+    }
+
+    fn is_local(&self, participant: Participant) -> bool {
+        self.local_participants
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(&participant)
+    }
+
+    async fn publish(&self, to: Participant, message: M) -> Result<(), MessageSenderError> {
+        let payload = serde_json::to_string(&message).map_err(|e| MessageSenderError::MessageSenderError(Box::new(e)))?;
+        let envelope = Envelope { key: (self.coalesce_key)(&message), payload };
+        self.bus
+            .publish(to, envelope)
+            .await
+            .map_err(|e| e.into_sender_error(to))
+    }
+}
+
+#[async_trait]
+impl<M: Serialize + Send + Sync + 'static> MessageSender<M> for RoutingMessageSender<M> {
+    async fn send(&self, to: Participant, outbound_msg: M) -> Result<(), MessageSenderError> {
+        if self.is_local(to) {
+            self.local.send(to, outbound_msg).await
+        } else {
+            self.publish(to, outbound_msg).await
+        }
+    }
+
+    async fn send_all(&self, outbound_msgs: Vec<(Participant, M)>) -> Vec<MessageSenderError> {
+        let (local, remote): (Vec<_>, Vec<_>) = outbound_msgs
+            .into_iter()
+            .partition(|(to, _)| self.is_local(*to));
+        let remote = join_all(remote.into_iter().map(|(to, msg)| self.publish(to, msg)));
+        let (mut errors, remote) = tokio::join!(self.local.send_all(local), remote);
+        errors.extend(remote.into_iter().filter_map(Result::err));
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OutboundQueueConfig;
+    use crate::infrastructure::init_actor_proxy;
+    use futures_util::sink;
+
+    #[tokio::test]
+    async fn remote_messages_are_sent_while_the_local_actor_is_stopped() {
+        let bus: Arc<dyn MessageBus> = Arc::new(InProcessBus::default());
+        let (stopped, proxy) = init_actor_proxy::<String>(8, OutboundQueueConfig::default(), Arc::new(|_| None));
+        drop(stopped);
+        let node = Uuid::new_v4();
+        let sender = RoutingMessageSender::new(node, proxy, bus.clone(), Arc::new(|_| None));
+
+        let (actor, proxy) = init_actor_proxy::<String>(8, OutboundQueueConfig::default(), Arc::new(|_| None));
+        tokio::spawn(actor.supervise());
+        let other_node = Uuid::new_v4();
+        tokio::spawn(deliver(bus.join(other_node).await.unwrap(), proxy.clone()));
+        let other = RoutingMessageSender::new(other_node, proxy, bus, Arc::new(|_| None));
+        let remote = Uuid::new_v4();
+        let (messages, mut received) = mpsc::unbounded_channel();
+        let sink = sink::unfold(messages, |messages, message| async move {
+            messages.send(message).map_err(axum::Error::new)?;
+            Ok::<_, axum::Error>(messages)
+        });
+        other.register(remote, sink).await.unwrap();
+
+        assert!(sender.send_all(Vec::new()).await.is_empty());
+        let errors = sender
+            .send_all(vec![(remote, "hello".to_string()), (Uuid::new_v4(), "lost".to_string())])
+            .await;
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(received.recv().await, Some(Message::from("\"hello\"")));
+    }
+}
@@ -0,0 +1,275 @@
+use serde::Deserialize;
+use std::path::PathBuf;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LobbyConfig {
+    /// Identifies the lobby in its metrics, as the `handler` label.
+    pub name: String,
+    /// Number of commands that can be queued for the message sender actor.
+    pub actor_buffer: usize,
+    pub outbound_queue: OutboundQueueConfig,
+    pub bus: BusConfig,
+    /// Restored from on startup and persisted to on shutdown.
+    pub rooms_snapshot: Option<PathBuf>,
+    pub shutdown: ShutdownConfig,
+    pub rooms: RoomLimits,
+    pub cookies: CookieConfig,
+    pub tokens: TokenConfig,
+    pub rate_limits: RateLimitConfig,
+    pub signals: SignalConfig,
+    pub health: HealthConfig,
+    pub admin: AdminConfig,
+}
+
+impl Default for LobbyConfig {
+    fn default() -> Self {
+        Self {
+            name: "lobby".to_string(),
+            actor_buffer: 100,
+            outbound_queue: OutboundQueueConfig::default(),
+            bus: BusConfig::default(),
+            rooms_snapshot: None,
+            shutdown: ShutdownConfig::default(),
+            rooms: RoomLimits::default(),
+            cookies: CookieConfig::default(),
+            tokens: TokenConfig::default(),
+            rate_limits: RateLimitConfig::default(),
+            signals: SignalConfig::default(),
+            health: HealthConfig::default(),
+            admin: AdminConfig::default(),
+        }
+    }
+}
+
+/// Ephemeral signals like typing indicators, see `LobbyRequest::Signal`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SignalConfig {
+    /// How long a signal stays active unless the participant repeats it.
+    pub ttl_ms: u64,
+}
+
+impl Default for SignalConfig {
+    fn default() -> Self {
+        Self { ttl_ms: 5_000 }
+    }
+}
+
+/// The operator API, see `Lobby::admin`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Bearer token operators authenticate with; the admin router is only built when set.
+    pub token: Option<String>,
+}
+
+/// How `/readyz` probes the lobby's components.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    /// How long a component gets to answer before it is reported as not ready.
+    pub probe_timeout_ms: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self { probe_timeout_ms: 1_000 }
+    }
+}
+
+/// The signed cookie participants are identified by.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CookieConfig {
+    /// The first key signs, the others are still accepted; random when empty.
+    pub keys: Vec<String>,
+    /// Set the `Secure` attribute; turn on when the server is only reachable over TLS.
+    pub secure: bool,
+    pub same_site: SameSitePolicy,
+    /// How long an identity stays valid after it was issued.
+    pub max_age_secs: u64,
+}
+
+impl Default for CookieConfig {
+    fn default() -> Self {
+        Self {
+            keys: Vec::new(),
+            secure: false,
+            same_site: SameSitePolicy::default(),
+            max_age_secs: 30 * 24 * 60 * 60,
+        }
+    }
+}
+
+/// Bearer tokens issued by `POST /sessions` to clients that don't keep cookies.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenConfig {
+    /// Secrets of at least 32 bytes tokens are signed with, rotated like `CookieConfig::keys`.
+    pub keys: Vec<String>,
+    /// How long a token is valid; clients refresh it before then.
+    pub ttl_secs: u64,
+}
+
+impl Default for TokenConfig {
+    fn default() -> Self {
+        Self {
+            keys: Vec::new(),
+            ttl_secs: 60 * 60,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Rooms a participant, and separately an IP address, may create.
+    pub room_creation: TokenBucketConfig,
+    /// Messages a participant may send over their sockets.
+    pub messages: TokenBucketConfig,
+    /// Disconnect participants that send this many messages in a row while rate limited.
+    pub kick_after: Option<u32>,
+    /// Only turn on behind a proxy that sets `X-Forwarded-For`.
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            room_creation: TokenBucketConfig { burst: 5, per_second: 0.1 },
+            messages: TokenBucketConfig { burst: 50, per_second: 10.0 },
+            kick_after: None,
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+/// Allows `burst` requests at once, refilled at `per_second`.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenBucketConfig {
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+/// Value of the cookie's `SameSite` attribute.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SameSitePolicy {
+    Strict,
+    #[default]
+    Lax,
+    None,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoomLimits {
+    /// Largest capacity a room may be created with.
+    pub max_capacity: usize,
+    /// Number of rooms that may be open at the same time.
+    pub max_rooms: usize,
+    /// Number of rooms a single participant may own at the same time.
+    pub max_rooms_per_owner: usize,
+    /// Longest room name, in characters.
+    pub max_name_len: usize,
+}
+
+impl Default for RoomLimits {
+    fn default() -> Self {
+        Self {
+            max_capacity: 100,
+            max_rooms: 1_000,
+            max_rooms_per_owner: 10,
+            max_name_len: 64,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    /// How long sockets get to flush the going away notice before they are dropped.
+    pub deadline_ms: u64,
+    /// Suggested delay before clients reconnect, sent with the going away notice.
+    pub reconnect_after_ms: u64,
+    /// Where clients should reconnect to, when not to this server.
+    pub reconnect_url: Option<String>,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            deadline_ms: 5_000,
+            reconnect_after_ms: 1_000,
+            reconnect_url: None,
+        }
+    }
+}
+
+/// How messages reach participants connected to other server instances.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BusConfig {
+    /// Single node: rooms and sockets live in this process only.
+    #[default]
+    InProcess,
+    /// Rooms are kept by the hub, see `lobby::serve_bus_hub`.
+    Tcp { hub: String, secret: String },
+}
+
+/// The hub nodes configured with `BusConfig::Tcp` connect to.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HubConfig {
+    /// Nodes introduce themselves with it; at least 32 bytes long.
+    pub secret: String,
+    /// How long a node has to reconnect before its participants are dropped from their rooms.
+    pub reconnect_grace_ms: u64,
+    /// Frames that can wait for a node before deliveries to it are refused.
+    pub queue_capacity: usize,
+}
+
+impl Default for HubConfig {
+    fn default() -> Self {
+        Self {
+            secret: String::new(),
+            reconnect_grace_ms: 10_000,
+            queue_capacity: 1024,
+        }
+    }
+}
+
+/// Limits applied to the outbound queue of every connected participant.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutboundQueueConfig {
+    /// Number of messages that can wait for a participant whose socket isn't draining.
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for OutboundQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 64,
+            overflow: OverflowPolicy::default(),
+        }
+    }
+}
+
+/// What happens to a message sent to a participant whose outbound queue is full.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Reject the new message with `MessageSenderError::QueueFull`.
+    #[default]
+    DropNewest,
+    /// Discard the oldest queued message to make room for the new one.
+    DropOldest,
+    /// Replace a queued message with the same `MessageHandler::coalesce_key`.
+    Coalesce,
+    /// Close the participant's socket.
+    Disconnect,
+}
@@ -0,0 +1,274 @@
+use schemars::generate::{SchemaGenerator, SchemaSettings};
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::api::{CreateRoomRequest, RoomResponse};
+use crate::app::FieldError;
+use crate::domain::Profile;
+use crate::session::Session;
+use crate::{LobbyNotice, LobbyRequest};
+
+pub(crate) struct ApiDocs {
+    pub(crate) openapi: Value,
+    pub(crate) asyncapi: Value,
+}
+
+impl ApiDocs {
+    pub(crate) fn new<Inbound: JsonSchema, Outbound: JsonSchema>(name: &str) -> Self {
+        Self {
+            openapi: openapi(name),
+            asyncapi: asyncapi::<Inbound, Outbound>(name),
+        }
+    }
+}
+
+/// Schemas are collected under `components`, where both documents expect them.
+fn generator(settings: SchemaSettings) -> SchemaGenerator {
+    settings
+        .with(|settings| {
+            settings.definitions_path = "/components/schemas".into();
+            settings.meta_schema = None;
+        })
+        .into_generator()
+}
+
+fn openapi(name: &str) -> Value {
+    let mut generator = generator(SchemaSettings::draft2020_12().for_serialize());
+    let room = generator.subschema_for::<RoomResponse>();
+    let create_room = generator.subschema_for::<CreateRoomRequest>();
+    let field_error = generator.subschema_for::<FieldError>();
+    let profile = generator.subschema_for::<Profile>();
+    let session = generator.subschema_for::<Session>();
+    let uuid = json!({ "type": "string", "format": "uuid" });
+    // identities are issued on the fly to requests carrying neither
+    let identity = json!([{ "participant_cookie": [] }, { "bearer": [] }, {}]);
+    let invalid_room = json!({
+        "description": "The room is invalid or a quota was reached.",
+        "content": { "application/json": { "schema": {
+            "type": "object",
+            "properties": {
+                "message": { "type": "string" },
+                "errors": { "type": "array", "items": field_error },
+            },
+            "required": ["message", "errors"],
+        } } },
+    });
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": format!("{name} lobby"),
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Rooms are joined over a WebSocket, whose protocol is described by `asyncapi.json`.",
+        },
+        // relative to where this document is served, as the lobby may be mounted under a path
+        "servers": [{ "url": "." }],
+        "paths": {
+            "/rooms": {
+                "get": {
+                    "summary": "List the open rooms",
+                    "operationId": "listRooms",
+                    "responses": {
+                        "200": json_response("The open rooms.", json!({ "type": "array", "items": room })),
+                    },
+                },
+                "post": {
+                    "summary": "Open a room owned by the requesting participant",
+                    "operationId": "createRoom",
+                    "security": identity,
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": create_room } },
+                    },
+                    "responses": {
+                        "200": json_response("The room was opened.", &room),
+                        "401": text_response("The participant cookie or bearer token is invalid."),
+                        "422": invalid_room,
+                        "429": text_response("Too many rooms were opened recently; see `Retry-After`."),
+                        "503": text_response("The server is shutting down."),
+                    },
+                },
+            },
+            "/rooms/{room_id}": {
+                "parameters": [{ "name": "room_id", "in": "path", "required": true, "schema": uuid }],
+                "get": {
+                    "summary": "Join the room over a WebSocket",
+                    "description": "Upgrades to a WebSocket speaking the protocol described by `asyncapi.json`. \
+                        Browsers may pass a bearer token as the `token` query parameter instead of a header.",
+                    "operationId": "joinRoom",
+                    "security": identity,
+                    "responses": {
+                        "101": { "description": "Joined; switching to the WebSocket protocol." },
+                        "400": text_response("The room is full."),
+                        "401": text_response("The participant cookie or bearer token is invalid."),
+                        "404": text_response("There is no such room."),
+                        "503": text_response("The server is shutting down."),
+                    },
+                },
+                "delete": {
+                    "summary": "Close a room",
+                    "operationId": "closeRoom",
+                    "security": identity,
+                    "responses": {
+                        "200": { "description": "The room was closed." },
+                        "401": text_response("The requesting participant doesn't own the room."),
+                        "404": text_response("There is no such room."),
+                    },
+                },
+            },
+            "/me": {
+                "put": {
+                    "summary": "Set the requesting participant's profile",
+                    "operationId": "updateProfile",
+                    "security": identity,
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": profile } },
+                    },
+                    "responses": {
+                        "200": json_response("The profile as stored.", &profile),
+                        "400": text_response("The profile is invalid."),
+                    },
+                },
+            },
+            "/participants/{participant}": {
+                "parameters": [{ "name": "participant", "in": "path", "required": true, "schema": uuid }],
+                "get": {
+                    "summary": "Look up a participant's profile",
+                    "operationId": "getProfile",
+                    "responses": {
+                        "200": json_response("The participant's profile.", &profile),
+                        "404": text_response("The participant has no profile."),
+                    },
+                },
+            },
+            "/sessions": {
+                "post": {
+                    "summary": "Issue a bearer token for the requesting participant",
+                    "operationId": "createSession",
+                    "security": identity,
+                    "responses": { "200": json_response("The new session.", &session) },
+                },
+                "delete": {
+                    "summary": "Revoke the bearer token the request is made with",
+                    "operationId": "revokeSession",
+                    "security": [{ "bearer": [] }],
+                    "responses": {
+                        "204": { "description": "The token was revoked." },
+                        "401": text_response("The bearer token is invalid."),
+                    },
+                },
+            },
+            "/sessions/refresh": {
+                "post": {
+                    "summary": "Exchange the bearer token the request is made with for a fresh one",
+                    "operationId": "refreshSession",
+                    "security": [{ "bearer": [] }],
+                    "responses": {
+                        "200": json_response("The new session.", &session),
+                        "401": text_response("The bearer token is invalid, expired or revoked."),
+                    },
+                },
+            },
+        },
+        "components": {
+            "schemas": generator.take_definitions(true),
+            "securitySchemes": {
+                "participant_cookie": { "type": "apiKey", "in": "cookie", "name": "participant" },
+                "bearer": { "type": "http", "scheme": "bearer" },
+            },
+        },
+    })
+}
+
+fn json_response(description: &str, schema: impl Serialize) -> Value {
+    json!({ "description": description, "content": { "application/json": { "schema": schema } } })
+}
+
+fn text_response(description: &str) -> Value {
+    json!({ "description": description, "content": { "text/plain": { "schema": { "type": "string" } } } })
+}
+
+fn asyncapi<Inbound: JsonSchema, Outbound: JsonSchema>(name: &str) -> Value {
+    let mut inbound_generator = generator(SchemaSettings::draft07().for_deserialize());
+    let inbound = inbound_generator.subschema_for::<Inbound>();
+    let request = inbound_generator.subschema_for::<LobbyRequest>();
+    let mut outbound_generator = generator(SchemaSettings::draft07().for_serialize());
+    let outbound = outbound_generator.subschema_for::<Outbound>();
+    let notice = outbound_generator.subschema_for::<LobbyNotice>();
+    let mut schemas = inbound_generator.take_definitions(true);
+    schemas.extend(outbound_generator.take_definitions(true));
+
+    json!({
+        "asyncapi": "3.0.0",
+        "info": {
+            "title": format!("{name} lobby WebSocket protocol"),
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Every frame is a single JSON text message. Rooms are opened and joined through the \
+                REST API described by `openapi.json`.",
+        },
+        "defaultContentType": "application/json",
+        "channels": {
+            "room": {
+                "address": "rooms/{room_id}",
+                "title": "A room's WebSocket",
+                "parameters": { "room_id": { "description": "The id of the joined room." } },
+                "messages": {
+                    "inbound": { "$ref": "#/components/messages/inbound" },
+                    "request": { "$ref": "#/components/messages/request" },
+                    "outbound": { "$ref": "#/components/messages/outbound" },
+                    "notice": { "$ref": "#/components/messages/notice" },
+                },
+            },
+        },
+        "operations": {
+            "receiveInbound": {
+                "action": "receive",
+                "channel": { "$ref": "#/channels/room" },
+                "summary": "Messages participants send, handled by the room's message handler.",
+                "messages": [{ "$ref": "#/channels/room/messages/inbound" }],
+            },
+            "receiveRequest": {
+                "action": "receive",
+                "channel": { "$ref": "#/channels/room" },
+                "summary": "Ephemeral signals relayed by the lobby to the rest of the room, never reaching the handler.",
+                "messages": [{ "$ref": "#/channels/room/messages/request" }],
+            },
+            "sendOutbound": {
+                "action": "send",
+                "channel": { "$ref": "#/channels/room" },
+                "summary": "Messages the lobby sends participants.",
+                "messages": [
+                    { "$ref": "#/channels/room/messages/outbound" },
+                    { "$ref": "#/channels/room/messages/notice" },
+                ],
+            },
+        },
+        "components": {
+            "messages": {
+                "inbound": {
+                    "name": "Inbound",
+                    "title": "Message to the handler",
+                    "payload": inbound,
+                },
+                "request": {
+                    "name": "LobbyRequest",
+                    "title": "Message to the lobby itself",
+                    "payload": request,
+                },
+                "outbound": {
+                    "name": "Outbound",
+                    "title": "Message from the handler",
+                    "payload": outbound,
+                },
+                "notice": {
+                    "name": "LobbyNotice",
+                    "title": "Message from the lobby itself",
+                    "summary": "Rate limiting, kicks, announcements, shutdown notices and other participants' signals.",
+                    "payload": notice,
+                },
+            },
+            "schemas": schemas,
+        },
+    })
+}
@@ -1,14 +1,17 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::error::Error;
+use std::ops::Deref;
 use thiserror::Error;
 use uuid::Uuid;
 
 pub type RoomId = Uuid;
 pub type Participant = Uuid;
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Room {
     pub id: RoomId,
     pub name: String,
@@ -23,7 +26,7 @@ impl Room {
         Self {
             id: Uuid::new_v4(),
             name: name.into(),
-            participants: Vec::with_capacity(capacity),
+            participants: Vec::new(),
             capacity,
             created_at: Utc::now(),
             created_by: participant,
@@ -55,6 +58,7 @@ impl Room {
     pub(crate) async fn handle_message<In, Out>(
         &self,
         msg_handler: &dyn MessageHandler<In, Outbound=Out, Err=impl Error + Send + Sync + 'static>,
+        profiles: &HashMap<Participant, Profile>,
         from: Participant,
         message: In,
     ) -> Result<Vec<(Participant, Out)>, RoomError>
@@ -68,25 +72,40 @@ impl Room {
                 participant: from,
             });
         }
-        match msg_handler
-            .handle_message(&self, from, message)
+        let context = RoomContext { room: self, profiles };
+        let response = msg_handler
+            .handle_message(&context, from, message)
             .await
-            .map_err(|e| RoomError::MessageHandlerError(Box::new(e)))?
-        {
+            .map_err(|e| RoomError::MessageHandlerError(Box::new(e)))?;
+        let mut outbound_msgs = Vec::new();
+        self.address(response, &mut outbound_msgs)?;
+        Ok(outbound_msgs)
+    }
+
+    fn address<Out: Clone>(
+        &self,
+        response: MessageResponse<Out>,
+        outbound_msgs: &mut Vec<(Participant, Out)>,
+    ) -> Result<(), RoomError> {
+        match response {
             MessageResponse::Unicast { to, .. } if !self.is_participant(to) => {
-                Err(RoomError::NotParticipant {
+                return Err(RoomError::NotParticipant {
                     room_id: self.id,
                     participant: to,
-                })
+                });
             }
-            MessageResponse::Unicast { to, msg } => Ok(vec![(to, msg)]),
-            MessageResponse::Broadcast { msg } => Ok(self
-                .participants
-                .iter()
-                .map(|to| (*to, msg.clone()))
-                .collect()),
-            MessageResponse::Void => Ok(vec![]),
+            MessageResponse::Unicast { to, msg } => outbound_msgs.push((to, msg)),
+            MessageResponse::Broadcast { msg } => {
+                outbound_msgs.extend(self.participants.iter().map(|to| (*to, msg.clone())))
+            }
+            MessageResponse::Multiple { responses } => {
+                for response in responses {
+                    self.address(response, outbound_msgs)?;
+                }
+            }
+            MessageResponse::Void => {}
         }
+        Ok(())
     }
 
     pub fn is_full(&self) -> bool {
@@ -98,6 +117,151 @@ impl Room {
     }
 }
 
+/// How many rooms may be open at once, overall and per owner.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub(crate) struct RoomQuota {
+    pub(crate) max_rooms: usize,
+    pub(crate) max_rooms_per_owner: usize,
+}
+
+/// Which limits of a [`RoomQuota`] opening another room would exceed.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct QuotaExceeded {
+    pub(crate) rooms: bool,
+    pub(crate) per_owner: bool,
+}
+
+impl RoomQuota {
+    pub(crate) fn check<'a>(
+        &self,
+        rooms: impl ExactSizeIterator<Item=&'a Room>,
+        owner: Participant,
+    ) -> Result<(), QuotaExceeded> {
+        let exceeded = QuotaExceeded {
+            rooms: rooms.len() >= self.max_rooms,
+            per_owner: rooms.filter(|room| room.created_by == owner).count() >= self.max_rooms_per_owner,
+        };
+        match exceeded.rooms || exceeded.per_owner {
+            true => Err(exceeded),
+            false => Ok(()),
+        }
+    }
+}
+
+/// The room a message was sent in, along with the profiles of its participants.
+pub struct RoomContext<'a> {
+    pub room: &'a Room,
+    /// Participants that haven't set up a profile are missing.
+    pub profiles: &'a HashMap<Participant, Profile>,
+}
+
+impl RoomContext<'_> {
+    /// The participant's display name, or the start of their id when they have no profile.
+    pub fn display_name(&self, participant: Participant) -> String {
+        match self.profiles.get(&participant) {
+            Some(profile) => profile.display_name.clone(),
+            None => participant.simple().to_string()[..8].to_string(),
+        }
+    }
+}
+
+impl Deref for RoomContext<'_> {
+    type Target = Room;
+
+    fn deref(&self) -> &Room {
+        self.room
+    }
+}
+
+/// How a participant presents themselves to others.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Profile {
+    pub display_name: String,
+    #[serde(default)]
+    pub avatar: Avatar,
+    /// BCP 47 language tag, e.g. `pt-BR`.
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Avatar {
+    /// `#rrggbb`
+    pub color: Option<String>,
+    pub emoji: Option<String>,
+}
+
+impl Profile {
+    pub(crate) const MAX_DISPLAY_NAME_LEN: usize = 32;
+
+    pub(crate) fn validate(&self) -> Result<(), ProfileError> {
+        let display_name_len = self.display_name.trim().chars().count();
+        if display_name_len == 0
+            || display_name_len > Self::MAX_DISPLAY_NAME_LEN
+            || self.display_name.chars().any(char::is_control)
+        {
+            return Err(ProfileError::InvalidDisplayName);
+        }
+        if let Some(color) = &self.avatar.color {
+            let hex = color.strip_prefix('#').unwrap_or_default();
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(ProfileError::InvalidAvatarColor);
+            }
+        }
+        if let Some(emoji) = &self.avatar.emoji {
+            let len = emoji.chars().count();
+            if len == 0 || len > 16 || emoji.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                return Err(ProfileError::InvalidAvatarEmoji);
+            }
+        }
+        if let Some(locale) = &self.locale {
+            let mut subtags = locale.split('-');
+            let language_ok = subtags
+                .next()
+                .is_some_and(|language| (2..=8).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic()));
+            let rest_ok = subtags.all(|subtag| (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric()));
+            if !language_ok || !rest_ok {
+                return Err(ProfileError::InvalidLocale);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ProfileError {
+    #[error("display name must be 1 to {} characters", Profile::MAX_DISPLAY_NAME_LEN)]
+    InvalidDisplayName,
+    #[error("avatar color must look like #rrggbb")]
+    InvalidAvatarColor,
+    #[error("avatar emoji must be 1 to 16 characters without whitespace")]
+    InvalidAvatarEmoji,
+    #[error("locale must be a BCP 47 language tag")]
+    InvalidLocale,
+}
+
+#[async_trait]
+pub(crate) trait ProfileRepository {
+    type Err: Error + Send + Sync + 'static;
+
+    /// Profiles of the given participants; those without one are left out.
+    async fn get_many(&self, participants: &[Participant]) -> Result<HashMap<Participant, Profile>, Self::Err>;
+    async fn save(&self, participant: Participant, profile: Profile) -> Result<Profile, Self::Err>;
+}
+
+#[async_trait]
+impl<R: ProfileRepository + Send + Sync + ?Sized> ProfileRepository for std::sync::Arc<R> {
+    type Err = R::Err;
+
+    async fn get_many(&self, participants: &[Participant]) -> Result<HashMap<Participant, Profile>, Self::Err> {
+        self.as_ref().get_many(participants).await
+    }
+
+    async fn save(&self, participant: Participant, profile: Profile) -> Result<Profile, Self::Err> {
+        self.as_ref().save(participant, profile).await
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum RoomError {
     #[error("room is full: {room_id}")]
@@ -117,14 +281,21 @@ pub enum RoomError {
 }
 
 #[async_trait]
-pub trait MessageSender<Outbound> {
+pub trait MessageSender<Outbound: Send + 'static>: Sync {
     async fn send(&self, to: Participant, outbound_msg: Outbound) -> Result<(), MessageSenderError>;
+
+    async fn send_all(&self, outbound_msgs: Vec<(Participant, Outbound)>) -> Vec<MessageSenderError> {
+        let sends = outbound_msgs.into_iter().map(|(to, outbound_msg)| self.send(to, outbound_msg));
+        futures_util::future::join_all(sends).await.into_iter().filter_map(Result::err).collect()
+    }
 }
 
 #[derive(Error, Debug)]
 pub enum MessageSenderError {
     #[error("participant disconnected: {0}")]
     ParticipantDisconnected(Participant, #[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+    #[error("outbound queue full for participant: {0}")]
+    QueueFull(Participant),
     #[error(transparent)]
     MessageSenderError(#[from] Box<dyn std::error::Error + Send + Sync + 'static>),
 }
@@ -136,7 +307,48 @@ pub(crate) trait RoomRepository {
     async fn get(&self, room_id: RoomId) -> Result<Option<Room>, Self::Err>;
     async fn get_all(&self) -> Result<Vec<Room>, Self::Err>;
     async fn save(&self, room: Room) -> Result<Room, Self::Err>;
+    async fn insert(&self, room: Room, quota: RoomQuota) -> Result<Result<Room, QuotaExceeded>, Self::Err>;
+    /// Saves `room` only if the stored one still equals `current`, returning whether it did.
+    async fn replace(&self, current: &Room, room: Room) -> Result<bool, Self::Err>;
     async fn delete(&self, room_id: RoomId) -> Result<(), Self::Err>;
+
+    /// For repositories that don't already write through.
+    async fn persist(&self) -> Result<(), Self::Err> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<R: RoomRepository + Send + Sync + ?Sized> RoomRepository for std::sync::Arc<R> {
+    type Err = R::Err;
+
+    async fn get(&self, room_id: RoomId) -> Result<Option<Room>, Self::Err> {
+        self.as_ref().get(room_id).await
+    }
+
+    async fn get_all(&self) -> Result<Vec<Room>, Self::Err> {
+        self.as_ref().get_all().await
+    }
+
+    async fn save(&self, room: Room) -> Result<Room, Self::Err> {
+        self.as_ref().save(room).await
+    }
+
+    async fn insert(&self, room: Room, quota: RoomQuota) -> Result<Result<Room, QuotaExceeded>, Self::Err> {
+        self.as_ref().insert(room, quota).await
+    }
+
+    async fn replace(&self, current: &Room, room: Room) -> Result<bool, Self::Err> {
+        self.as_ref().replace(current, room).await
+    }
+
+    async fn delete(&self, room_id: RoomId) -> Result<(), Self::Err> {
+        self.as_ref().delete(room_id).await
+    }
+
+    async fn persist(&self) -> Result<(), Self::Err> {
+        self.as_ref().persist().await
+    }
 }
 
 #[async_trait]
@@ -146,14 +358,61 @@ pub trait MessageHandler<Inbound>: Send + Sync + 'static {
 
     async fn handle_message(
         &self,
-        room: &Room,
+        room: &RoomContext<'_>,
         from: Participant,
         msg: Inbound,
     ) -> Result<MessageResponse<Self::Outbound>, Self::Err>;
+
+    async fn on_join(&self, _room: &RoomContext<'_>, _participant: Participant) -> Vec<Self::Outbound> {
+        Vec::new()
+    }
+
+    /// Forgets whatever the handler keeps about a room that was closed.
+    async fn room_closed(&self, _room_id: RoomId) {}
+
+    /// Only honoured under `OverflowPolicy::Coalesce`.
+    fn coalesce_key(&self, _msg: &Self::Outbound) -> Option<String> {
+        None
+    }
+
+    /// Whatever the handler keeps about the room, dumped by the admin API for debugging.
+    async fn debug_state(&self, _room: &RoomContext<'_>) -> serde_json::Value {
+        serde_json::Value::Null
+    }
 }
 
 pub enum MessageResponse<M> {
     Unicast { to: Participant, msg: M },
     Broadcast { msg: M },
+    /// Nothing is sent if any response is addressed outside the room.
+    Multiple { responses: Vec<MessageResponse<M>> },
     Void,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::sync::Barrier;
+
+    // only lets a send finish once every other one has started
+    struct BarrierSender(Barrier);
+
+    #[async_trait]
+    impl MessageSender<()> for BarrierSender {
+        async fn send(&self, _to: Participant, _outbound_msg: ()) -> Result<(), MessageSenderError> {
+            self.0.wait().await;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn send_all_starts_every_delivery_at_once() {
+        let sender = BarrierSender(Barrier::new(3));
+        let msgs = (0..3).map(|_| (Uuid::new_v4(), ())).collect();
+
+        let errors = tokio::time::timeout(Duration::from_secs(5), sender.send_all(msgs)).await.unwrap();
+
+        assert!(errors.is_empty());
+    }
+}
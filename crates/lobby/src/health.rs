@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use futures_util::future::join_all;
+use serde::Serialize;
+use uuid::Uuid;
+use crate::domain::RoomRepository;
+use crate::infrastructure::{DynRoomRepo, Ping};
+
+/// Probes the components a lobby needs to serve participants.
+#[derive(Clone)]
+pub struct Health {
+    pub(crate) name: String,
+    pub(crate) accepting: Arc<AtomicBool>,
+    pub(crate) sender: Arc<dyn Ping>,
+    pub(crate) room_repo: DynRoomRepo,
+    pub(crate) probe_timeout: Duration,
+}
+
+impl Health {
+    /// Looking up a missing room still makes a round trip to the hub.
+    pub async fn check(&self) -> HealthReport {
+        let (message_sender, room_repository) = tokio::join!(
+            self.probe(self.sender.ping()),
+            self.probe(async { self.room_repo.get(Uuid::nil()).await.map(drop).map_err(Into::into) }),
+        );
+        let accepting = if self.accepting.load(Ordering::SeqCst) {
+            ComponentHealth::ok()
+        } else {
+            ComponentHealth::failing("shutting down")
+        };
+        HealthReport::new([
+            ("message_sender".to_string(), message_sender),
+            ("room_repository".to_string(), room_repository),
+            ("accepting".to_string(), accepting),
+        ])
+    }
+
+    async fn probe(&self, probe: impl Future<Output = anyhow::Result<()>>) -> ComponentHealth {
+        match tokio::time::timeout(self.probe_timeout, probe).await {
+            Ok(Ok(())) => ComponentHealth::ok(),
+            Ok(Err(e)) => ComponentHealth::failing(format!("{e:#}")),
+            Err(_) => ComponentHealth::failing(format!("no answer within {:?}", self.probe_timeout)),
+        }
+    }
+}
+
+pub async fn check_all(lobbies: &[Health]) -> HealthReport {
+    let reports = join_all(lobbies.iter().map(Health::check)).await;
+    HealthReport::new(lobbies.iter().zip(reports).flat_map(|(lobby, report)| {
+        report
+            .components
+            .into_iter()
+            .map(move |(component, health)| (format!("{}.{component}", lobby.name), health))
+    }))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    Failing,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ComponentHealth {
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ComponentHealth {
+    fn ok() -> Self {
+        Self { status: Status::Ok, error: None }
+    }
+
+    fn failing(error: impl Into<String>) -> Self {
+        Self { status: Status::Failing, error: Some(error.into()) }
+    }
+}
+
+/// Served as JSON, with `503 Service Unavailable` when any component is failing.
+#[derive(Clone, Debug, Serialize)]
+pub struct HealthReport {
+    pub status: Status,
+    pub components: BTreeMap<String, ComponentHealth>,
+}
+
+impl HealthReport {
+    /// The process is up; reported without probing anything.
+    pub fn alive() -> Self {
+        Self::new([])
+    }
+
+    fn new(components: impl IntoIterator<Item = (String, ComponentHealth)>) -> Self {
+        let components: BTreeMap<_, _> = components.into_iter().collect();
+        let status = if components.values().all(|component| component.status == Status::Ok) {
+            Status::Ok
+        } else {
+            Status::Failing
+        };
+        Self { status, components }
+    }
+}
+
+impl IntoResponse for HealthReport {
+    fn into_response(self) -> Response {
+        let status = match self.status {
+            Status::Ok => StatusCode::OK,
+            Status::Failing => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        (status, Json(self)).into_response()
+    }
+}
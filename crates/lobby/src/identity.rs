@@ -0,0 +1,154 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use anyhow::bail;
+use axum::extract::{FromRef, FromRequestParts, Query};
+use axum::http::HeaderMap;
+use axum::http::header::UPGRADE;
+use axum::http::request::Parts;
+use axum_extra::extract::CookieJar;
+use axum_extra::extract::cookie::{Cookie, Key, SameSite, SignedCookieJar};
+use chrono::Utc;
+use serde::Deserialize;
+use uuid::Uuid;
+use crate::api::ApiError;
+use crate::config::{CookieConfig, SameSitePolicy};
+use crate::domain::Participant;
+use crate::session::{Sessions, bearer_token};
+
+const PARTICIPANT: &str = "participant";
+
+/// The cookie value is `<participant>.<expires at>`, so the expiry can't be extended.
+pub(crate) struct ParticipantCookies {
+    /// The first key signs, the rest only verify.
+    keys: Vec<Key>,
+    secure: bool,
+    same_site: SameSite,
+    max_age_secs: u64,
+}
+
+impl ParticipantCookies {
+    pub(crate) fn new(config: &CookieConfig) -> anyhow::Result<Self> {
+        let mut keys = Vec::with_capacity(config.keys.len());
+        for key in &config.keys {
+            if key.len() < 32 {
+                bail!("cookie keys must be at least 32 bytes long");
+            }
+            keys.push(Key::derive_from(key.as_bytes()));
+        }
+        if keys.is_empty() {
+            tracing::warn!("no cookie keys configured, participant identities won't survive a restart");
+            keys.push(Key::generate());
+        }
+        Ok(Self {
+            keys,
+            secure: config.secure,
+            same_site: match config.same_site {
+                SameSitePolicy::Strict => SameSite::Strict,
+                SameSitePolicy::Lax => SameSite::Lax,
+                SameSitePolicy::None => SameSite::None,
+            },
+            max_age_secs: config.max_age_secs,
+        })
+    }
+
+    fn identify(&self, headers: &HeaderMap) -> Result<Identity, ApiError> {
+        let now = Utc::now().timestamp();
+        for (i, key) in self.keys.iter().enumerate() {
+            let Some(cookie) = SignedCookieJar::from_headers(headers, key.clone()).get(PARTICIPANT) else {
+                continue;
+            };
+            let (participant, expires_at) = cookie
+                .value()
+                .split_once('.')
+                .and_then(|(participant, expires_at)| {
+                    Some((Uuid::from_str(participant).ok()?, expires_at.parse::<i64>().ok()?))
+                })
+                .ok_or(ApiError::InvalidParticipantCookie)?;
+            if expires_at <= now {
+                return Ok(self.mint(now));
+            }
+            let cookie_jar = if i > 0 {
+                self.issue(participant, expires_at, now)
+            } else {
+                self.unchanged()
+            };
+            return Ok(Identity { participant, cookie_jar });
+        }
+
+        match CookieJar::from_headers(headers).get(PARTICIPANT) {
+            // identities issued before cookies were signed are a bare uuid
+            Some(cookie) if Uuid::from_str(cookie.value()).is_ok() => Err(ApiError::UnsignedParticipantCookie),
+            _ => Ok(self.mint(now)),
+        }
+    }
+
+    fn mint(&self, now: i64) -> Identity {
+        let participant = Uuid::new_v4();
+        let expires_at = now.saturating_add(self.max_age_secs as i64);
+        Identity {
+            participant,
+            cookie_jar: self.issue(participant, expires_at, now),
+        }
+    }
+
+    fn issue(&self, participant: Participant, expires_at: i64, now: i64) -> SignedCookieJar {
+        let cookie = Cookie::build((PARTICIPANT, format!("{participant}.{expires_at}")))
+            .http_only(true)
+            .secure(self.secure)
+            .same_site(self.same_site)
+            .max_age(time::Duration::seconds(expires_at - now))
+            .build();
+        self.unchanged().add(cookie)
+    }
+
+    fn unchanged(&self) -> SignedCookieJar {
+        SignedCookieJar::new(self.keys[0].clone())
+    }
+}
+
+/// A bearer token or `token` query parameter takes precedence over the cookie.
+pub(crate) struct Identity {
+    pub(crate) participant: Participant,
+    pub(crate) cookie_jar: SignedCookieJar,
+}
+
+impl<S> FromRequestParts<S> for Identity
+where
+    Arc<ParticipantCookies>: FromRef<S>,
+    Arc<Sessions>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let cookies = Arc::<ParticipantCookies>::from_ref(state);
+        let token = match bearer_token(&parts.headers) {
+            Some(token) => Some(token?.to_string()),
+            None if is_websocket_upgrade(&parts.headers) => {
+                Query::<TokenQuery>::try_from_uri(&parts.uri)
+                    .ok()
+                    .and_then(|query| query.0.token)
+            }
+            None => None,
+        };
+        match token {
+            Some(token) => Ok(Identity {
+                participant: Arc::<Sessions>::from_ref(state).verify(&token)?,
+                cookie_jar: cookies.unchanged(),
+            }),
+            None => cookies.identify(&parts.headers),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+fn is_websocket_upgrade(headers: &HeaderMap) -> bool {
+    headers
+        .get(UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+}
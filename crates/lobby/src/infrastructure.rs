@@ -1,30 +1,58 @@
-use crate::domain::{MessageSender, MessageSenderError, Participant, Room, RoomId, RoomRepository};
-use anyhow::anyhow;
+use crate::bus::Envelope;
+use crate::config::{OutboundQueueConfig, OverflowPolicy};
+use crate::domain::{
+    MessageSender, MessageSenderError, Participant, Profile, ProfileRepository, QuotaExceeded, Room, RoomId, RoomQuota,
+    RoomRepository,
+};
+use anyhow::{anyhow, Context};
 use async_trait::async_trait;
-use axum::extract::ws::{Message, WebSocket};
-use futures_util::SinkExt;
-use futures_util::stream::SplitSink;
+use axum::extract::ws::{close_code, CloseFrame, Message};
+use futures_util::future::join_all;
+use futures_util::{Sink, SinkExt};
 use serde::Serialize;
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, VecDeque};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, PoisonError};
+use std::time::Duration;
 use thiserror::Error;
 use tokio::sync::mpsc::{Receiver, Sender};
-use tokio::sync::{Mutex, mpsc, oneshot};
+use tokio::sync::{Mutex, Notify, mpsc, oneshot};
+use tokio::task::{AbortHandle, JoinHandle};
 
 #[derive(Clone, Default)]
 pub(crate) struct InMemoryRoomRepo {
     map: Arc<Mutex<HashMap<RoomId, Room>>>,
+    snapshot: Option<PathBuf>,
 }
 
 impl InMemoryRoomRepo {
     pub(crate) fn new() -> Self {
         Self::default()
     }
+
+    /// Restores the rooms persisted to `snapshot` by a previous run, if any.
+    pub(crate) async fn with_snapshot(snapshot: PathBuf) -> Result<Self, InfrastructureError> {
+        let rooms: Vec<Room> = match tokio::fs::read(&snapshot).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("malformed room snapshot: {}", snapshot.display()))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(InfrastructureError(anyhow!(e))),
+        };
+        tracing::info!("restored {} rooms from {}", rooms.len(), snapshot.display());
+        Ok(Self {
+            map: Arc::new(Mutex::new(rooms.into_iter().map(|room| (room.id, room)).collect())),
+            snapshot: Some(snapshot),
+        })
+    }
 }
 
 #[derive(Error, Debug)]
 #[error(transparent)]
-pub struct InfrastructureError(#[from] anyhow::Error);
+pub struct InfrastructureError(#[from] pub(crate) anyhow::Error);
+
+pub(crate) type DynRoomRepo = Arc<dyn RoomRepository<Err = InfrastructureError> + Send + Sync>;
 
 #[async_trait]
 impl RoomRepository for InMemoryRoomRepo {
@@ -46,17 +74,81 @@ impl RoomRepository for InMemoryRoomRepo {
         Ok(room)
     }
 
+    async fn insert(&self, room: Room, quota: RoomQuota) -> Result<Result<Room, QuotaExceeded>, Self::Err> {
+        let mut guard = self.map.lock().await;
+        if let Err(exceeded) = quota.check(guard.values(), room.created_by) {
+            return Ok(Err(exceeded));
+        }
+        guard.insert(room.id, room.clone());
+        Ok(Ok(room))
+    }
+
+    async fn replace(&self, current: &Room, room: Room) -> Result<bool, Self::Err> {
+        let mut guard = self.map.lock().await;
+        if guard.get(&room.id) != Some(current) {
+            return Ok(false);
+        }
+        guard.insert(room.id, room);
+        Ok(true)
+    }
+
     async fn delete(&self, room_id: RoomId) -> Result<(), Self::Err> {
         let mut guard = self.map.lock().await;
         guard.remove(&room_id);
         Ok(())
     }
+
+    async fn persist(&self) -> Result<(), Self::Err> {
+        let Some(snapshot) = &self.snapshot else {
+            return Ok(());
+        };
+        // sockets don't survive a restart, so neither do the participants
+        let rooms: Vec<Room> = self
+            .map
+            .lock()
+            .await
+            .values()
+            .cloned()
+            .map(|room| Room { participants: Vec::new(), ..room })
+            .collect();
+        let json = serde_json::to_vec(&rooms).context("failed to serialize rooms")?;
+        tokio::fs::write(snapshot, json)
+            .await
+            .with_context(|| format!("failed to write room snapshot: {}", snapshot.display()))?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct InMemoryProfileRepo {
+    map: Arc<Mutex<HashMap<Participant, Profile>>>,
+}
+
+pub(crate) type DynProfileRepo = Arc<dyn ProfileRepository<Err = InfrastructureError> + Send + Sync>;
+
+#[async_trait]
+impl ProfileRepository for InMemoryProfileRepo {
+    type Err = InfrastructureError;
+
+    async fn get_many(&self, participants: &[Participant]) -> Result<HashMap<Participant, Profile>, Self::Err> {
+        let guard = self.map.lock().await;
+        Ok(participants
+            .iter()
+            .filter_map(|participant| Some((*participant, guard.get(participant)?.clone())))
+            .collect())
+    }
+
+    async fn save(&self, participant: Participant, profile: Profile) -> Result<Profile, Self::Err> {
+        let mut guard = self.map.lock().await;
+        guard.insert(participant, profile.clone());
+        Ok(profile)
+    }
 }
 
 pub(crate) enum Command<M: Send + Sync + 'static> {
     RegisterParticipant {
         participant: Participant,
-        ws_sender: SplitSink<WebSocket, Message>,
+        ws_sender: ParticipantSink,
         result_sender: oneshot::Sender<()>,
     },
     UnregisterParticipant {
@@ -68,30 +160,245 @@ pub(crate) enum Command<M: Send + Sync + 'static> {
         message: M,
         result_sender: oneshot::Sender<Result<(), MessageSenderError>>,
     },
+    SendMessages {
+        messages: Vec<(Participant, M)>,
+        result_sender: oneshot::Sender<Vec<MessageSenderError>>,
+    },
+    SendEnvelope {
+        participant: Participant,
+        envelope: Envelope,
+        result_sender: oneshot::Sender<Result<(), MessageSenderError>>,
+    },
+    /// Sends `notice` to the participant, then closes their socket for violating a policy.
+    Kick {
+        participant: Participant,
+        notice: String,
+        reason: String,
+        result_sender: oneshot::Sender<()>,
+    },
+    /// Reports the participants with a socket on this node.
+    Connections {
+        result_sender: oneshot::Sender<HashMap<Participant, Connection>>,
+    },
+    /// Queues `notice` for every participant, returning how many it was queued for.
+    Announce {
+        notice: String,
+        result_sender: oneshot::Sender<usize>,
+    },
+    /// Answers right away, showing the actor is still processing commands.
+    Ping {
+        result_sender: oneshot::Sender<()>,
+    },
+    /// Sends `notice` to every participant, then closes their sockets as going away.
+    Drain {
+        notice: String,
+        result_sender: oneshot::Sender<Vec<JoinHandle<()>>>,
+    },
 }
 
+/// A participant's socket as seen by the sender actor.
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Connection {
+    /// Messages waiting to be written to the socket.
+    pub(crate) queued: usize,
+    /// The socket is being closed, e.g. after a kick.
+    pub(crate) closing: bool,
+}
+
+struct Outgoing {
+    key: Option<String>,
+    message: Message,
+}
+
+#[derive(Default)]
+struct QueueState {
+    items: VecDeque<Outgoing>,
+    closed: bool,
+    // sent once the queue is drained, before the writer stops
+    close_frame: Option<CloseFrame>,
+}
+
+/// Drained by its own writer task, so a slow socket only stalls itself.
+#[derive(Default)]
+struct OutboundQueue {
+    state: std::sync::Mutex<QueueState>,
+    notify: Notify,
+}
+
+impl OutboundQueue {
+    fn state(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn push(&self, participant: Participant, config: &OutboundQueueConfig, outgoing: Outgoing) -> Result<(), MessageSenderError> {
+        let mut state = self.state();
+        if state.closed {
+            return Err(MessageSenderError::ParticipantDisconnected(
+                participant,
+                Box::new(InfrastructureError(anyhow!("writer closed for participant: {participant}"))),
+            ));
+        }
+        if config.overflow == OverflowPolicy::Coalesce && outgoing.key.is_some() {
+            if let Some(queued) = state.items.iter_mut().find(|queued| queued.key == outgoing.key) {
+                *queued = outgoing;
+                return Ok(());
+            }
+        }
+        if state.items.len() >= config.capacity {
+            match config.overflow {
+                OverflowPolicy::DropOldest => {
+                    tracing::debug!("dropped oldest queued message for participant: {participant}");
+                    state.items.pop_front();
+                }
+                OverflowPolicy::DropNewest | OverflowPolicy::Coalesce => {
+                    return Err(MessageSenderError::QueueFull(participant));
+                }
+                OverflowPolicy::Disconnect => {
+                    state.items.clear();
+                    state.closed = true;
+                    state.close_frame = Some(CloseFrame {
+                        code: close_code::POLICY,
+                        reason: "outbound queue overflow".into(),
+                    });
+                    self.notify.notify_one();
+                    return Err(MessageSenderError::ParticipantDisconnected(
+                        participant,
+                        Box::new(InfrastructureError(anyhow!("outbound queue overflow for participant: {participant}"))),
+                    ));
+                }
+            }
+        }
+        state.items.push_back(outgoing);
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    async fn pop(&self) -> Result<Message, Option<CloseFrame>> {
+        loop {
+            {
+                let mut state = self.state();
+                if let Some(outgoing) = state.items.pop_front() {
+                    return Ok(outgoing.message);
+                }
+                if state.closed {
+                    return Err(state.close_frame.take());
+                }
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    fn close(&self) {
+        self.state().closed = true;
+        self.notify.notify_one();
+    }
+
+    /// Queues `message` regardless of the queue limits, then closes the socket with `close_frame`.
+    fn close_with(&self, message: Message, close_frame: CloseFrame) {
+        let mut state = self.state();
+        if !state.closed {
+            state.items.push_back(Outgoing { key: None, message });
+            state.closed = true;
+            state.close_frame = Some(close_frame);
+        }
+        self.notify.notify_one();
+    }
+}
+
+struct ParticipantWriter {
+    queue: Arc<OutboundQueue>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl ParticipantWriter {
+    fn spawn(participant: Participant, mut sink: ParticipantSink) -> Self {
+        let queue = Arc::new(OutboundQueue::default());
+        let writer_queue = queue.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let message = match writer_queue.pop().await {
+                    Ok(message) => message,
+                    Err(Some(close_frame)) => Message::Close(Some(close_frame)),
+                    Err(None) => return,
+                };
+                let is_close = matches!(message, Message::Close(_));
+                if let Err(e) = sink.send(message).await {
+                    tracing::info!("participant disconnected: {participant}: {e}");
+                    writer_queue.close();
+                    return;
+                }
+                if is_close {
+                    return;
+                }
+            }
+        });
+        Self { queue, task: Some(task) }
+    }
+}
+
+impl Drop for ParticipantWriter {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
+
+/// Write half of a participant's socket.
+pub(crate) type ParticipantSink = Pin<Box<dyn Sink<Message, Error = axum::Error> + Send + 'static>>;
+
 pub(crate) struct MessageSenderActor<M: Send + Sync + 'static> {
-    receiver: Receiver<Command<M>>,
-    map: HashMap<Participant, SplitSink<WebSocket, Message>>,
+    receiver: Arc<Mutex<Receiver<Command<M>>>>,
+    // shared with restarted incarnations of the actor, so registrations survive a panic
+    map: Arc<std::sync::Mutex<HashMap<Participant, ParticipantWriter>>>,
+    queue_config: OutboundQueueConfig,
+    coalesce_key: CoalesceKey<M>,
+}
+
+impl<M: Send + Sync + 'static> Clone for MessageSenderActor<M> {
+    fn clone(&self) -> Self {
+        Self {
+            receiver: self.receiver.clone(),
+            map: self.map.clone(),
+            queue_config: self.queue_config.clone(),
+            coalesce_key: self.coalesce_key.clone(),
+        }
+    }
 }
 
 impl<M: Serialize + Send + Sync + 'static> MessageSenderActor<M> {
-    pub(crate) async fn process(mut self) {
-        while let Some(command) = self.receiver.recv().await {
+    /// Restarts the actor whenever processing a command panics.
+    pub(crate) async fn supervise(self) {
+        loop {
+            match tokio::spawn(self.clone().process()).await {
+                Ok(()) => return,
+                Err(e) if e.is_panic() => {
+                    tracing::error!("message sender actor panicked, restarting: {e}");
+                }
+                Err(e) => {
+                    tracing::error!("message sender actor stopped: {e}");
+                    return;
+                }
+            }
+        }
+    }
+
+    pub(crate) async fn process(self) {
+        let mut receiver = self.receiver.lock().await;
+        while let Some(command) = receiver.recv().await {
             match command {
                 Command::RegisterParticipant {
                     participant,
                     ws_sender,
                     result_sender,
                 } => {
-                    self.map.insert(participant, ws_sender);
+                    let writer = ParticipantWriter::spawn(participant, ws_sender);
+                    self.writers().insert(participant, writer);
                     let _ = result_sender.send(());
                 }
                 Command::UnregisterParticipant {
                     participant,
                     result_sender,
                 } => {
-                    self.map.remove(&participant);
+                    self.writers().remove(&participant);
                     let _ = result_sender.send(());
                 }
                 Command::SendMessage {
@@ -99,70 +406,283 @@ impl<M: Serialize + Send + Sync + 'static> MessageSenderActor<M> {
                     message,
                     result_sender,
                 } => {
-                    let Some(sink) = self.map.get_mut(&participant) else {
-                        let _ = result_sender.send(Err(MessageSenderError::MessageSenderError(Box::new(InfrastructureError(anyhow!(
-                            "sink not found for participant: {participant}"
-                        ))))));
-                        return;
-                    };
-                    let msg_json = serde_json::to_string(&message);
-                    let Ok(msg_json) = msg_json else {
-                        let _ = result_sender.send(msg_json.map(|_| ()).map_err(|e| MessageSenderError::MessageSenderError(Box::new(e))));
-                        return;
-                    };
-                    let send = sink.send(Message::from(msg_json)).await;
-
-                    // remove participant when disconnected
-                    let response = match send {
-                        Ok(_) => Ok(()),
-                        Err(e) => {
-                            tracing::info!("participant disconnected: {}", participant);
-                            self.map.remove(&participant);
-                            Err(MessageSenderError::ParticipantDisconnected(participant, Box::new(e)))
-                        }
-                    };
+                    let response = self.send(participant, message);
                     let _ = result_sender.send(response);
                 }
+                Command::SendMessages {
+                    messages,
+                    result_sender,
+                } => {
+                    let errors = messages
+                        .into_iter()
+                        .filter_map(|(participant, message)| self.send(participant, message).err())
+                        .collect();
+                    let _ = result_sender.send(errors);
+                }
+                Command::SendEnvelope {
+                    participant,
+                    envelope,
+                    result_sender,
+                } => {
+                    let outgoing = Outgoing { key: envelope.key, message: Message::from(envelope.payload) };
+                    let _ = result_sender.send(self.push(participant, outgoing));
+                }
+                Command::Kick {
+                    participant,
+                    notice,
+                    reason,
+                    result_sender,
+                } => {
+                    if let Some(writer) = self.writers().remove(&participant) {
+                        writer.queue.close_with(
+                            Message::from(notice),
+                            CloseFrame {
+                                code: close_code::POLICY,
+                                reason: reason.into(),
+                            },
+                        );
+                    }
+                    let _ = result_sender.send(());
+                }
+                Command::Connections { result_sender } => {
+                    let connections = self
+                        .writers()
+                        .iter()
+                        .map(|(participant, writer)| {
+                            let state = writer.queue.state();
+                            (*participant, Connection { queued: state.items.len(), closing: state.closed })
+                        })
+                        .collect();
+                    let _ = result_sender.send(connections);
+                }
+                Command::Announce {
+                    notice,
+                    result_sender,
+                } => {
+                    let participants: Vec<_> = self.writers().keys().copied().collect();
+                    let delivered = participants
+                        .into_iter()
+                        .filter(|participant| {
+                            let outgoing = Outgoing { key: None, message: Message::from(notice.clone()) };
+                            self.push(*participant, outgoing).is_ok()
+                        })
+                        .count();
+                    let _ = result_sender.send(delivered);
+                }
+                Command::Ping { result_sender } => {
+                    let _ = result_sender.send(());
+                }
+                Command::Drain {
+                    notice,
+                    result_sender,
+                } => {
+                    let writers = std::mem::take(&mut *self.writers());
+                    let tasks = writers
+                        .into_values()
+                        .filter_map(|mut writer| {
+                            writer.queue.close_with(
+                                Message::from(notice.clone()),
+                                CloseFrame {
+                                    code: close_code::AWAY,
+                                    reason: "server going away".into(),
+                                },
+                            );
+                            writer.task.take()
+                        })
+                        .collect();
+                    let _ = result_sender.send(tasks);
+                }
             }
         }
     }
+
+    fn writers(&self) -> std::sync::MutexGuard<'_, HashMap<Participant, ParticipantWriter>> {
+        self.map.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn send(&self, participant: Participant, message: M) -> Result<(), MessageSenderError> {
+        let msg_json = serde_json::to_string(&message)
+            .map_err(|e| MessageSenderError::MessageSenderError(Box::new(e)))?;
+        let key = (self.coalesce_key)(&message);
+        self.push(participant, Outgoing { key, message: Message::from(msg_json) })
+    }
+
+    fn push(&self, participant: Participant, outgoing: Outgoing) -> Result<(), MessageSenderError> {
+        let mut writers = self.writers();
+        let Some(writer) = writers.get(&participant) else {
+            return Err(MessageSenderError::MessageSenderError(Box::new(InfrastructureError(anyhow!(
+                "sink not found for participant: {participant}"
+            )))));
+        };
+        let result = writer.queue.push(participant, &self.queue_config, outgoing);
+        // remove participant when disconnected
+        if let Err(MessageSenderError::ParticipantDisconnected(..)) = result {
+            writers.remove(&participant);
+        }
+        result
+    }
 }
 
-#[derive(Clone)]
 pub struct MessageSenderProxy<M: Send + Sync + 'static> {
     sender: Sender<Command<M>>,
 }
 
+impl<M: Send + Sync + 'static> Clone for MessageSenderProxy<M> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
 impl<M: Send + Sync + 'static> MessageSenderProxy<M> {
-    pub async fn register(
+    pub(crate) async fn register(
         &self,
         participant: Participant,
-        ws_sender: SplitSink<WebSocket, Message>,
+        ws_sender: impl Sink<Message, Error = axum::Error> + Send + 'static,
     ) -> Result<(), anyhow::Error> {
         let (result_sender, result_receiver) = oneshot::channel();
         self.sender
             .send(Command::RegisterParticipant {
                 participant,
-                ws_sender,
+                ws_sender: Box::pin(ws_sender),
                 result_sender,
             })
-            .await?;
-        Ok(result_receiver
             .await
-            .unwrap_or_else(|_| panic!("Failed to receive result from actor")))
+            .map_err(|_| actor_stopped())?;
+        result_receiver
+            .await
+            .context("message sender actor dropped the register command")
     }
 
-    pub async fn unregister(&self, participant: Participant) -> Result<(), anyhow::Error> {
+    pub(crate) async fn unregister(&self, participant: Participant) -> Result<(), anyhow::Error> {
         let (result_sender, result_receiver) = oneshot::channel();
         self.sender
             .send(Command::UnregisterParticipant {
                 participant,
                 result_sender,
             })
-            .await?;
-        Ok(result_receiver
             .await
-            .unwrap_or_else(|_| panic!("Failed to receive result from actor")))
+            .map_err(|_| actor_stopped())?;
+        result_receiver
+            .await
+            .context("message sender actor dropped the unregister command")
+    }
+}
+
+/// Type-erased access to the sender actor for shutting down.
+#[async_trait]
+pub(crate) trait Drain: Send + Sync {
+    async fn drain(&self, notice: String, deadline: Duration) -> Result<(), anyhow::Error>;
+}
+
+#[async_trait]
+impl<M: Send + Sync + 'static> Drain for MessageSenderProxy<M> {
+    async fn drain(&self, notice: String, deadline: Duration) -> Result<(), anyhow::Error> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.sender
+            .send(Command::Drain {
+                notice,
+                result_sender,
+            })
+            .await
+            .map_err(|_| actor_stopped())?;
+        let tasks = result_receiver
+            .await
+            .context("message sender actor dropped the drain command")?;
+        let abort_handles: Vec<_> = tasks.iter().map(JoinHandle::abort_handle).collect();
+        if tokio::time::timeout(deadline, join_all(tasks)).await.is_err() {
+            tracing::warn!("sockets did not close within {deadline:?}, dropping them");
+            abort_handles.iter().for_each(AbortHandle::abort);
+        }
+        Ok(())
+    }
+}
+
+/// Type-erased access to the sender actor for health checks.
+#[async_trait]
+pub(crate) trait Ping: Send + Sync {
+    /// Round-trips a command through the actor.
+    async fn ping(&self) -> Result<(), anyhow::Error>;
+}
+
+#[async_trait]
+impl<M: Send + Sync + 'static> Ping for MessageSenderProxy<M> {
+    async fn ping(&self) -> Result<(), anyhow::Error> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.sender
+            .send(Command::Ping { result_sender })
+            .await
+            .map_err(|_| actor_stopped())?;
+        result_receiver
+            .await
+            .context("message sender actor dropped the ping")
+    }
+}
+
+impl<M: Send + Sync + 'static> MessageSenderProxy<M> {
+    /// Commands waiting for the actor.
+    pub(crate) fn queue_depth(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+
+    /// Unregisters the participant, closing their socket once `notice` is sent.
+    pub(crate) async fn kick(&self, participant: Participant, notice: String, reason: &str) -> Result<(), anyhow::Error> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.sender
+            .send(Command::Kick {
+                participant,
+                notice,
+                reason: reason.to_string(),
+                result_sender,
+            })
+            .await
+            .map_err(|_| actor_stopped())?;
+        result_receiver
+            .await
+            .context("message sender actor dropped the kick")
+    }
+
+    pub(crate) async fn connections(&self) -> Result<HashMap<Participant, Connection>, anyhow::Error> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.sender
+            .send(Command::Connections { result_sender })
+            .await
+            .map_err(|_| actor_stopped())?;
+        result_receiver
+            .await
+            .context("message sender actor dropped the connections query")
+    }
+
+    /// Queues `notice` for every connected participant.
+    pub(crate) async fn announce(&self, notice: String) -> Result<usize, anyhow::Error> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.sender
+            .send(Command::Announce {
+                notice,
+                result_sender,
+            })
+            .await
+            .map_err(|_| actor_stopped())?;
+        result_receiver
+            .await
+            .context("message sender actor dropped the announcement")
+    }
+
+    /// Sends a message that was already serialized, e.g. by another node.
+    pub(crate) async fn send_envelope(&self, participant: Participant, envelope: Envelope) -> Result<(), MessageSenderError> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.sender
+            .send(Command::SendEnvelope {
+                participant,
+                envelope,
+                result_sender,
+            })
+            .await
+            .map_err(|_| MessageSenderError::MessageSenderError(Box::new(actor_stopped())))?;
+        result_receiver
+            .await
+            .map_err(|e| MessageSenderError::MessageSenderError(Box::new(e)))?
     }
 }
 
@@ -177,22 +697,264 @@ impl<M: Send + Sync + 'static> MessageSender<M> for MessageSenderProxy<M> {
                 result_sender,
             })
             .await
-            .map_err(|e| MessageSenderError::MessageSenderError(Box::new(e)))?;
+            .map_err(|_| MessageSenderError::MessageSenderError(Box::new(actor_stopped())))?;
         result_receiver
             .await
-            .unwrap_or_else(|_| panic!("Failed to receive result from actor"))?;
-        Ok(())
+            .map_err(|e| MessageSenderError::MessageSenderError(Box::new(e)))?
+    }
+
+    async fn send_all(&self, messages: Vec<(Participant, M)>) -> Vec<MessageSenderError> {
+        let count = messages.len();
+        let (result_sender, result_receiver) = oneshot::channel();
+        let send = self
+            .sender
+            .send(Command::SendMessages {
+                messages,
+                result_sender,
+            })
+            .await;
+        if send.is_err() {
+            return (0..count)
+                .map(|_| MessageSenderError::MessageSenderError(Box::new(actor_stopped())))
+                .collect();
+        }
+        result_receiver.await.unwrap_or_else(|e| {
+            (0..count)
+                .map(|_| MessageSenderError::MessageSenderError(Box::new(e.clone())))
+                .collect()
+        })
     }
 }
 
+fn actor_stopped() -> InfrastructureError {
+    InfrastructureError(anyhow!("message sender actor stopped"))
+}
+
+/// Extracts the key under which a queued message may be superseded by a newer one.
+pub(crate) type CoalesceKey<M> = Arc<dyn Fn(&M) -> Option<String> + Send + Sync + 'static>;
+
 pub(crate) fn init_actor_proxy<M: Send + Sync + 'static>(
     size: usize,
+    queue_config: OutboundQueueConfig,
+    coalesce_key: CoalesceKey<M>,
 ) -> (MessageSenderActor<M>, MessageSenderProxy<M>) {
     let (sender, receiver) = mpsc::channel(size);
     let actor = MessageSenderActor {
-        receiver,
+        receiver: Arc::new(Mutex::new(receiver)),
         map: Default::default(),
+        queue_config,
+        coalesce_key,
     };
     let proxy = MessageSenderProxy { sender };
     (actor, proxy)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::sink;
+    use serde::ser::Error;
+    use tokio::sync::mpsc::UnboundedReceiver;
+    use uuid::Uuid;
+
+    #[derive(Debug)]
+    enum Outbound {
+        Text(&'static str),
+        Unserializable,
+        Panic,
+    }
+
+    impl Serialize for Outbound {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                Outbound::Text(text) => serializer.serialize_str(text),
+                Outbound::Unserializable => Err(S::Error::custom("unserializable")),
+                Outbound::Panic => panic!("serializer panicked"),
+            }
+        }
+    }
+
+    fn spawn_actor() -> MessageSenderProxy<Outbound> {
+        let (actor, proxy) = init_actor_proxy(8, OutboundQueueConfig::default(), Arc::new(|_| None));
+        tokio::spawn(actor.supervise());
+        proxy
+    }
+
+    async fn register(proxy: &MessageSenderProxy<Outbound>) -> (Participant, UnboundedReceiver<Message>) {
+        let participant = Uuid::new_v4();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let sink = sink::unfold(sender, |sender, message| async move {
+            sender.send(message).map_err(axum::Error::new)?;
+            Ok::<_, axum::Error>(sender)
+        });
+        proxy.register(participant, sink).await.unwrap();
+        (participant, receiver)
+    }
+
+    async fn assert_delivered(proxy: &MessageSenderProxy<Outbound>) {
+        let (participant, mut receiver) = register(proxy).await;
+        proxy.send(participant, Outbound::Text("hello")).await.unwrap();
+        assert_eq!(receiver.recv().await, Some(Message::from("\"hello\"")));
+    }
+
+    #[tokio::test]
+    async fn unknown_participant_is_reported_to_the_caller_only() {
+        let proxy = spawn_actor();
+
+        let result = proxy.send(Uuid::new_v4(), Outbound::Text("hello")).await;
+
+        assert!(matches!(result, Err(MessageSenderError::MessageSenderError(_))));
+        assert_delivered(&proxy).await;
+    }
+
+    #[tokio::test]
+    async fn serialization_failure_is_reported_to_the_caller_only() {
+        let proxy = spawn_actor();
+        let (participant, _receiver) = register(&proxy).await;
+
+        let result = proxy.send(participant, Outbound::Unserializable).await;
+
+        assert!(matches!(result, Err(MessageSenderError::MessageSenderError(_))));
+        assert_delivered(&proxy).await;
+    }
+
+    #[tokio::test]
+    async fn send_all_reports_each_failure() {
+        let proxy = spawn_actor();
+        let (participant, mut receiver) = register(&proxy).await;
+        let unknown = Uuid::new_v4();
+
+        let errors = proxy
+            .send_all(vec![
+                (unknown, Outbound::Text("lost")),
+                (participant, Outbound::Unserializable),
+                (participant, Outbound::Text("hello")),
+            ])
+            .await;
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(receiver.recv().await, Some(Message::from("\"hello\"")));
+    }
+
+    #[tokio::test]
+    async fn actor_is_restarted_after_a_panic() {
+        let proxy = spawn_actor();
+        let (participant, mut receiver) = register(&proxy).await;
+
+        let result = proxy.send(participant, Outbound::Panic).await;
+
+        assert!(matches!(result, Err(MessageSenderError::MessageSenderError(_))));
+        // registrations made before the panic survive the restart
+        proxy.send(participant, Outbound::Text("hello")).await.unwrap();
+        assert_eq!(receiver.recv().await, Some(Message::from("\"hello\"")));
+        assert_delivered(&proxy).await;
+    }
+
+    #[tokio::test]
+    async fn proxy_returns_errors_when_actor_is_gone() {
+        let (actor, proxy) = init_actor_proxy::<Outbound>(8, OutboundQueueConfig::default(), Arc::new(|_| None));
+        drop(actor);
+        let participant = Uuid::new_v4();
+
+        assert!(proxy.register(participant, sink::drain().sink_map_err(|e| match e {})).await.is_err());
+        assert!(proxy.unregister(participant).await.is_err());
+        assert!(proxy.send(participant, Outbound::Text("hello")).await.is_err());
+        let batch = vec![(participant, Outbound::Text("hello")), (participant, Outbound::Text("again"))];
+        assert_eq!(proxy.send_all(batch).await.len(), 2);
+        assert!(proxy.send_all(Vec::new()).await.is_empty());
+    }
+
+    fn outgoing(key: Option<&str>, text: &str) -> Outgoing {
+        Outgoing { key: key.map(str::to_string), message: Message::from(text) }
+    }
+
+    fn queued(queue: &OutboundQueue) -> Vec<Message> {
+        queue.state().items.iter().map(|outgoing| outgoing.message.clone()).collect()
+    }
+
+    fn overflowing(overflow: OverflowPolicy) -> OutboundQueueConfig {
+        OutboundQueueConfig { capacity: 2, overflow }
+    }
+
+    #[test]
+    fn drop_newest_rejects_messages_past_capacity() {
+        let (queue, participant) = (OutboundQueue::default(), Uuid::new_v4());
+        let config = overflowing(OverflowPolicy::DropNewest);
+
+        queue.push(participant, &config, outgoing(None, "1")).unwrap();
+        queue.push(participant, &config, outgoing(None, "2")).unwrap();
+        let result = queue.push(participant, &config, outgoing(None, "3"));
+
+        assert!(matches!(result, Err(MessageSenderError::QueueFull(p)) if p == participant));
+        assert_eq!(queued(&queue), [Message::from("1"), Message::from("2")]);
+    }
+
+    #[test]
+    fn drop_oldest_makes_room_for_new_messages() {
+        let (queue, participant) = (OutboundQueue::default(), Uuid::new_v4());
+        let config = overflowing(OverflowPolicy::DropOldest);
+
+        for text in ["1", "2", "3", "4"] {
+            queue.push(participant, &config, outgoing(None, text)).unwrap();
+        }
+
+        assert_eq!(queued(&queue), [Message::from("3"), Message::from("4")]);
+    }
+
+    #[test]
+    fn coalesce_replaces_messages_with_the_same_key() {
+        let (queue, participant) = (OutboundQueue::default(), Uuid::new_v4());
+        let config = overflowing(OverflowPolicy::Coalesce);
+
+        queue.push(participant, &config, outgoing(Some("position"), "at 1s")).unwrap();
+        queue.push(participant, &config, outgoing(None, "hello")).unwrap();
+        queue.push(participant, &config, outgoing(Some("position"), "at 2s")).unwrap();
+        let unkeyed = queue.push(participant, &config, outgoing(None, "dropped"));
+        let new_key = queue.push(participant, &config, outgoing(Some("volume"), "dropped"));
+
+        assert!(matches!(unkeyed, Err(MessageSenderError::QueueFull(_))));
+        assert!(matches!(new_key, Err(MessageSenderError::QueueFull(_))));
+        assert_eq!(queued(&queue), [Message::from("at 2s"), Message::from("hello")]);
+    }
+
+    #[tokio::test]
+    async fn disconnect_closes_the_writer_on_overflow() {
+        let participant = Uuid::new_v4();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let sink = sink::unfold(sender, |sender, message| async move {
+            sender.send(message).map_err(axum::Error::new)?;
+            Ok::<_, axum::Error>(sender)
+        });
+        let writer = ParticipantWriter::spawn(participant, Box::pin(sink));
+        let config = overflowing(OverflowPolicy::Disconnect);
+
+        // the writer task doesn't run before the test yields, so nothing drains in between
+        writer.queue.push(participant, &config, outgoing(None, "1")).unwrap();
+        writer.queue.push(participant, &config, outgoing(None, "2")).unwrap();
+        let result = writer.queue.push(participant, &config, outgoing(None, "3"));
+
+        assert!(matches!(result, Err(MessageSenderError::ParticipantDisconnected(p, _)) if p == participant));
+        assert!(matches!(
+            receiver.recv().await,
+            Some(Message::Close(Some(CloseFrame { code: close_code::POLICY, .. })))
+        ));
+        assert_eq!(receiver.recv().await, None);
+        assert!(writer.queue.push(participant, &config, outgoing(None, "4")).is_err());
+    }
+
+    #[tokio::test]
+    async fn disconnected_participant_is_reported_and_forgotten() {
+        let proxy = spawn_actor();
+        let (participant, receiver) = register(&proxy).await;
+        drop(receiver);
+
+        // the first message fails in the writer task, closing the participant's queue
+        proxy.send(participant, Outbound::Text("hello")).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+
+        let result = proxy.send(participant, Outbound::Text("hello")).await;
+        assert!(matches!(result, Err(MessageSenderError::ParticipantDisconnected(p, _)) if p == participant));
+        let result = proxy.send(participant, Outbound::Text("hello")).await;
+        assert!(matches!(result, Err(MessageSenderError::MessageSenderError(_))));
+    }
+}
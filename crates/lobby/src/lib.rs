@@ -1,37 +1,202 @@
 use std::error::Error;
 use std::fmt::Debug;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use axum::Router;
+use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use uuid::Uuid;
+
+pub use crate::signal::SignalKind;
+use crate::admin::{AdminState, AdminToken};
 use crate::api::AppState;
-use crate::domain::MessageHandler;
-use crate::infrastructure::{init_actor_proxy, InMemoryRoomRepo};
+use crate::bus::{InProcessBus, MessageBus, RoutingMessageSender};
+use crate::config::{BusConfig, HubConfig, LobbyConfig, ShutdownConfig};
+use crate::docs::ApiDocs;
+use crate::domain::{MessageHandler, Participant, RoomRepository};
+use crate::health::Health;
+use crate::identity::ParticipantCookies;
+use crate::infrastructure::{
+    init_actor_proxy, CoalesceKey, Drain, DynProfileRepo, DynRoomRepo, InMemoryProfileRepo, InMemoryRoomRepo,
+};
+use crate::metrics::{LobbyMetrics, Metrics};
+use crate::rate_limit::RateLimits;
+use crate::session::Sessions;
+use crate::signal::Signals;
+use crate::tcp_bus::{TcpBus, TcpProfileRepo, TcpRoomRepo};
 
+mod admin;
 mod api;
 mod app;
+mod bus;
+pub mod config;
+mod docs;
 pub mod domain;
+pub mod health;
+mod identity;
 mod infrastructure;
+pub mod metrics;
+mod rate_limit;
+mod session;
+mod signal;
+mod tcp_bus;
+pub mod testing;
+
+/// A lobby ready to be served.
+pub struct Lobby {
+    pub router: Router,
+    /// Meant to be kept off the public network.
+    pub admin: Option<Router>,
+    pub shutdown: Shutdown,
+    pub metrics: Metrics,
+    pub health: Health,
+}
 
 pub async fn setup<Inbound, Outbound, Err>(
-    message_handler: Arc<dyn MessageHandler<Inbound, Outbound=Outbound, Err=Err> + Send + Sync + 'static>
-) -> anyhow::Result<Router>
+    message_handler: Arc<dyn MessageHandler<Inbound, Outbound=Outbound, Err=Err> + Send + Sync + 'static>,
+    config: LobbyConfig,
+) -> anyhow::Result<Lobby>
 where
-    Inbound: DeserializeOwned + Debug + Clone + Send + Sync + 'static,
-    Outbound: Serialize + Debug + Clone + Send + Sync + 'static,
+    Inbound: DeserializeOwned + JsonSchema + Debug + Clone + Send + Sync + 'static,
+    Outbound: Serialize + JsonSchema + Debug + Clone + Send + Sync + 'static,
     Err: Error + Send + Sync + 'static,
     Err: Clone,
 {
-    let (actor, message_sender) = init_actor_proxy::<Outbound>(100);
-    let room_repo = InMemoryRoomRepo::new();
+    let coalesce_handler = message_handler.clone();
+    let coalesce_key: CoalesceKey<Outbound> = Arc::new(move |msg| coalesce_handler.coalesce_key(msg));
+    let (actor, local_sender) = init_actor_proxy::<Outbound>(
+        config.actor_buffer,
+        config.outbound_queue,
+        coalesce_key.clone(),
+    );
+    let (bus, room_repo, profile_repo): (Arc<dyn MessageBus>, DynRoomRepo, DynProfileRepo) = match &config.bus {
+        BusConfig::InProcess => {
+            let room_repo = match &config.rooms_snapshot {
+                Some(snapshot) => InMemoryRoomRepo::with_snapshot(snapshot.clone()).await?,
+                None => InMemoryRoomRepo::new(),
+            };
+            (Arc::new(InProcessBus::default()), Arc::new(room_repo), Arc::new(InMemoryProfileRepo::default()))
+        }
+        BusConfig::Tcp { hub, secret } => {
+            let bus = Arc::new(TcpBus::connect(hub, &config.name, secret).await?);
+            (bus.clone(), Arc::new(TcpRoomRepo::new(bus.clone())), Arc::new(TcpProfileRepo::new(bus)))
+        }
+    };
+    let node = Uuid::new_v4();
+    let deliveries = bus.join(node).await?;
+    let message_sender = RoutingMessageSender::new(node, local_sender.clone(), bus, coalesce_key);
+    let accepting = Arc::new(AtomicBool::new(true));
 
+    let shutdown = Shutdown {
+        accepting: accepting.clone(),
+        sender: Arc::new(local_sender.clone()),
+        room_repo: room_repo.clone(),
+        config: config.shutdown,
+    };
+    let queue_depth_sender = local_sender.clone();
+    let metrics = Metrics {
+        lobby: Arc::new(LobbyMetrics::new(&config.name)?),
+        room_repo: room_repo.clone(),
+        actor_queue_depth: Arc::new(move || queue_depth_sender.queue_depth()),
+    };
+    let health = Health {
+        name: config.name.clone(),
+        accepting: accepting.clone(),
+        sender: Arc::new(local_sender.clone()),
+        room_repo: room_repo.clone(),
+        probe_timeout: Duration::from_millis(config.health.probe_timeout_ms),
+    };
     let app_state = AppState {
         room_repo,
+        profile_repo,
         message_sender,
         message_handler,
+        accepting,
+        room_limits: config.rooms,
+        cookies: Arc::new(ParticipantCookies::new(&config.cookies)?),
+        sessions: Arc::new(Sessions::new(&config.tokens)?),
+        rate_limits: Arc::new(RateLimits::new(&config.rate_limits)),
+        signals: Arc::new(Signals::new(&config.signals)),
+        metrics: metrics.clone(),
+        health: health.clone(),
+        docs: Arc::new(ApiDocs::new::<Inbound, Outbound>(&config.name)),
+    };
+
+    tokio::spawn(actor.supervise());
+    tokio::spawn(bus::deliver(deliveries, local_sender));
+    tokio::spawn(api::expire_signals(
+        Arc::downgrade(&app_state.signals),
+        app_state.room_repo.clone(),
+        app_state.message_sender.clone(),
+    ));
+
+    let admin = match &config.admin.token {
+        Some(token) => Some(admin::router(AdminState {
+            app: app_state.clone(),
+            token: Arc::new(AdminToken::new(token)?),
+        })),
+        None => None,
     };
 
-    tokio::spawn(async move { actor.process().await; });
+    Ok(Lobby {
+        router: api::router(app_state),
+        admin,
+        shutdown,
+        metrics,
+        health,
+    })
+}
+
+/// Frames the lobby itself sends to clients, next to the handler's outbound messages.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum LobbyNotice {
+    ServerGoingAway {
+        reconnect_after_ms: u64,
+        reconnect_url: Option<String>,
+    },
+    /// The last message was dropped; the next one will be accepted after `retry_after_ms`.
+    RateLimited { retry_after_ms: u64 },
+    /// Sent right before the lobby closes the participant's socket.
+    Kicked { reason: String },
+    /// A message from the operators to everybody connected.
+    Announcement { message: String },
+    Signal { from: Participant, kind: SignalKind, active: bool },
+}
+
+/// Never reach the handler, so its inbound messages shouldn't share their names.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum LobbyRequest {
+    Signal { kind: SignalKind, active: bool },
+}
+
+/// Gracefully stops a lobby.
+#[derive(Clone)]
+pub struct Shutdown {
+    accepting: Arc<AtomicBool>,
+    sender: Arc<dyn Drain>,
+    room_repo: DynRoomRepo,
+    config: ShutdownConfig,
+}
+
+impl Shutdown {
+    pub async fn shutdown(&self) -> anyhow::Result<()> {
+        self.accepting.store(false, Ordering::SeqCst);
+        let notice = serde_json::to_string(&LobbyNotice::ServerGoingAway {
+            reconnect_after_ms: self.config.reconnect_after_ms,
+            reconnect_url: self.config.reconnect_url.clone(),
+        })?;
+        self.sender
+            .drain(notice, Duration::from_millis(self.config.deadline_ms))
+            .await?;
+        self.room_repo.persist().await?;
+        Ok(())
+    }
+}
 
-    Ok(api::router(app_state))
+/// Runs the hub that nodes configured with `BusConfig::Tcp` connect to.
+pub async fn serve_bus_hub(listener: TcpListener, config: HubConfig) -> anyhow::Result<()> {
+    tcp_bus::serve_hub(listener, config).await
 }
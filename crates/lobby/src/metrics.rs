@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use prometheus::proto::MetricFamily;
+use prometheus::{Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+use crate::app;
+use crate::domain::MessageSenderError;
+use crate::infrastructure::DynRoomRepo;
+
+/// Latency buckets, in seconds, from 100µs to 2.5s.
+const LATENCY_BUCKETS: &[f64] = &[0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+/// Instruments updated as the lobby runs, all labelled with the lobby's name.
+pub(crate) struct LobbyMetrics {
+    registry: Registry,
+    rooms_open: IntGauge,
+    participants_connected: IntGauge,
+    pub(crate) messages_in: IntCounter,
+    pub(crate) messages_out: IntCounter,
+    send_failures: IntCounterVec,
+    actor_queue_depth: IntGauge,
+    pub(crate) handler_latency: Histogram,
+    pub(crate) send_latency: Histogram,
+}
+
+impl LobbyMetrics {
+    pub(crate) fn new(handler: &str) -> anyhow::Result<Self> {
+        let registry = Registry::new_custom(None, Some([("handler".to_string(), handler.to_string())].into()))?;
+        let metrics = Self {
+            rooms_open: IntGauge::new("lobby_rooms_open", "Rooms currently open")?,
+            participants_connected: IntGauge::new(
+                "lobby_participants_connected",
+                "Participants with a socket connected to this node",
+            )?,
+            messages_in: IntCounter::new("lobby_messages_in_total", "Messages received from participants")?,
+            messages_out: IntCounter::new("lobby_messages_out_total", "Messages queued for participants")?,
+            send_failures: IntCounterVec::new(
+                Opts::new("lobby_send_failures_total", "Messages that could not be queued for a participant"),
+                &["reason"],
+            )?,
+            actor_queue_depth: IntGauge::new(
+                "lobby_actor_queue_depth",
+                "Commands waiting for the message sender actor",
+            )?,
+            handler_latency: Histogram::with_opts(
+                HistogramOpts::new("lobby_handler_duration_seconds", "Time spent in the message handler")
+                    .buckets(LATENCY_BUCKETS.to_vec()),
+            )?,
+            send_latency: Histogram::with_opts(
+                HistogramOpts::new("lobby_send_duration_seconds", "Time spent queueing a handler's response")
+                    .buckets(LATENCY_BUCKETS.to_vec()),
+            )?,
+            registry,
+        };
+        metrics.registry.register(Box::new(metrics.rooms_open.clone()))?;
+        metrics.registry.register(Box::new(metrics.participants_connected.clone()))?;
+        metrics.registry.register(Box::new(metrics.messages_in.clone()))?;
+        metrics.registry.register(Box::new(metrics.messages_out.clone()))?;
+        metrics.registry.register(Box::new(metrics.send_failures.clone()))?;
+        metrics.registry.register(Box::new(metrics.actor_queue_depth.clone()))?;
+        metrics.registry.register(Box::new(metrics.handler_latency.clone()))?;
+        metrics.registry.register(Box::new(metrics.send_latency.clone()))?;
+        Ok(metrics)
+    }
+
+    /// Counts a participant as connected until the returned guard is dropped.
+    pub(crate) fn connected(&self) -> ConnectionGuard {
+        self.participants_connected.inc();
+        ConnectionGuard(self.participants_connected.clone())
+    }
+
+    pub(crate) fn send_failed(&self, error: &MessageSenderError) {
+        let reason = match error {
+            MessageSenderError::ParticipantDisconnected(..) => "participant_disconnected",
+            MessageSenderError::QueueFull(_) => "queue_full",
+            MessageSenderError::MessageSenderError(_) => "message_sender_error",
+        };
+        self.send_failures.with_label_values(&[reason]).inc();
+    }
+}
+
+pub(crate) struct ConnectionGuard(IntGauge);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+/// Renders the metrics of a lobby; see `render_all` to serve several lobbies together.
+#[derive(Clone)]
+pub struct Metrics {
+    pub(crate) lobby: Arc<LobbyMetrics>,
+    pub(crate) room_repo: DynRoomRepo,
+    pub(crate) actor_queue_depth: Arc<dyn Fn() -> usize + Send + Sync>,
+}
+
+impl Metrics {
+    /// Samples the gauges that are cheaper to read on demand than to keep up to date.
+    async fn gather(&self) -> Vec<MetricFamily> {
+        match app::list_rooms(&self.room_repo).await {
+            Ok(rooms) => self.lobby.rooms_open.set(rooms.len() as i64),
+            Err(e) => tracing::warn!("failed to count open rooms: {e}"),
+        }
+        self.lobby.actor_queue_depth.set((self.actor_queue_depth)() as i64);
+        self.lobby.registry.gather()
+    }
+
+    /// The lobby's metrics in the Prometheus text format.
+    pub async fn render(&self) -> String {
+        render_all(std::slice::from_ref(self)).await
+    }
+}
+
+pub async fn render_all(metrics: &[Metrics]) -> String {
+    let mut families: BTreeMap<String, MetricFamily> = BTreeMap::new();
+    for lobby in metrics {
+        for mut family in lobby.gather().await {
+            match families.get_mut(family.get_name()) {
+                Some(merged) => merged.mut_metric().extend(family.take_metric()),
+                None => {
+                    families.insert(family.get_name().to_string(), family);
+                }
+            }
+        }
+    }
+    let families: Vec<MetricFamily> = families.into_values().collect();
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&families, &mut buffer) {
+        tracing::error!("failed to encode metrics: {e}");
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
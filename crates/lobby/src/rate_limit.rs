@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use axum::extract::{ConnectInfo, FromRef, FromRequestParts};
+use axum::http::request::Parts;
+use crate::api::ApiError;
+use crate::config::{RateLimitConfig, TokenBucketConfig};
+use crate::domain::Participant;
+
+/// Buckets are only pruned once there are this many, so the common case never scans.
+const PRUNE_THRESHOLD: usize = 1_024;
+/// Pruning scans at most once in this long, however many buckets there are.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The lobby's rate limiters.
+pub(crate) struct RateLimits {
+    pub(crate) rooms_by_participant: RateLimiter<Participant>,
+    // requests from unknown addresses share a bucket, so they can't dodge the limit
+    pub(crate) rooms_by_ip: RateLimiter<Option<IpAddr>>,
+    pub(crate) messages: RateLimiter<Participant>,
+    pub(crate) kick_after: Option<u32>,
+    trust_forwarded_for: bool,
+}
+
+impl RateLimits {
+    pub(crate) fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            rooms_by_participant: RateLimiter::new(config.room_creation),
+            rooms_by_ip: RateLimiter::new(config.room_creation),
+            messages: RateLimiter::new(config.messages),
+            kick_after: config.kick_after,
+            trust_forwarded_for: config.trust_forwarded_for,
+        }
+    }
+
+    /// The address is checked first, as requests without a cookie are new participants.
+    pub(crate) fn check_room_creation(&self, participant: Participant, ip: Option<IpAddr>) -> Result<(), Duration> {
+        self.rooms_by_ip.check(ip)?;
+        self.rooms_by_participant.check(participant)
+    }
+}
+
+pub(crate) struct RateLimiter<K> {
+    config: TokenBucketConfig,
+    buckets: Mutex<Buckets<K>>,
+}
+
+struct Buckets<K> {
+    by_key: HashMap<K, Bucket>,
+    pruned_at: Option<Instant>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, config: &TokenBucketConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * config.per_second).min(config.burst as f64);
+        self.updated = now;
+    }
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+    pub(crate) fn new(config: TokenBucketConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(Buckets { by_key: HashMap::new(), pruned_at: None }),
+        }
+    }
+
+    /// Takes a token for `key`, or returns how long until one is available.
+    pub(crate) fn check(&self, key: K) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: K, now: Instant) -> Result<(), Duration> {
+        let burst = self.config.burst as f64;
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        let due = buckets
+            .pruned_at
+            .is_none_or(|pruned_at| now.saturating_duration_since(pruned_at) >= PRUNE_INTERVAL);
+        if buckets.by_key.len() >= PRUNE_THRESHOLD && due {
+            // a full bucket is no different from a missing one
+            buckets.by_key.retain(|_, bucket| {
+                bucket.refill(&self.config, now);
+                bucket.tokens < burst
+            });
+            buckets.pruned_at = Some(now);
+        }
+        let bucket = buckets.by_key.entry(key).or_insert(Bucket { tokens: burst, updated: now });
+        bucket.refill(&self.config, now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        let wait = (1.0 - bucket.tokens) / self.config.per_second;
+        Err(Duration::try_from_secs_f64(wait).unwrap_or(Duration::MAX))
+    }
+}
+
+pub(crate) struct ClientIp(pub(crate) Option<IpAddr>);
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    Arc<RateLimits>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if Arc::<RateLimits>::from_ref(state).trust_forwarded_for {
+            let forwarded = parts
+                .headers
+                .get("x-forwarded-for")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .and_then(|ip| ip.trim().parse().ok());
+            if forwarded.is_some() {
+                return Ok(ClientIp(forwarded));
+            }
+        }
+        let connected = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        Ok(ClientIp(connected))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_allow_bursts_then_refill_over_time() {
+        let limiter = RateLimiter::new(TokenBucketConfig { burst: 2, per_second: 4.0 });
+        let start = Instant::now();
+        assert!(limiter.check_at("a", start).is_ok());
+        assert!(limiter.check_at("a", start).is_ok());
+        assert_eq!(limiter.check_at("a", start), Err(Duration::from_millis(250)));
+        // other keys have their own bucket
+        assert!(limiter.check_at("b", start).is_ok());
+
+        assert!(limiter.check_at("a", start + Duration::from_millis(250)).is_ok());
+        assert!(limiter.check_at("a", start + Duration::from_millis(250)).is_err());
+        // never more than the burst, however long it's been
+        let later = start + Duration::from_secs(60);
+        assert!(limiter.check_at("a", later).is_ok());
+        assert!(limiter.check_at("a", later).is_ok());
+        assert!(limiter.check_at("a", later).is_err());
+    }
+
+    #[test]
+    fn full_buckets_are_pruned() {
+        let limiter = RateLimiter::new(TokenBucketConfig { burst: 1, per_second: 1.0 });
+        let start = Instant::now();
+        for key in 0..PRUNE_THRESHOLD {
+            assert!(limiter.check_at(key, start).is_ok());
+        }
+        assert!(limiter.check_at(PRUNE_THRESHOLD, start + Duration::from_secs(1)).is_ok());
+        assert_eq!(limiter.buckets.lock().unwrap().by_key.len(), 1);
+    }
+
+    #[test]
+    fn busy_buckets_are_scanned_at_most_once_an_interval() {
+        let limiter = RateLimiter::new(TokenBucketConfig { burst: 1, per_second: 0.001 });
+        let start = Instant::now();
+        for key in 0..=PRUNE_THRESHOLD {
+            assert!(limiter.check_at(key, start).is_ok());
+        }
+        assert_eq!(limiter.buckets.lock().unwrap().pruned_at, Some(start));
+
+        assert!(limiter.check_at(PRUNE_THRESHOLD + 1, start + PRUNE_INTERVAL / 2).is_ok());
+        assert_eq!(limiter.buckets.lock().unwrap().pruned_at, Some(start));
+        assert!(limiter.check_at(PRUNE_THRESHOLD + 2, start + PRUNE_INTERVAL).is_ok());
+        assert_eq!(limiter.buckets.lock().unwrap().pruned_at, Some(start + PRUNE_INTERVAL));
+    }
+
+    #[test]
+    fn room_creation_from_unknown_addresses_shares_a_bucket() {
+        let room_creation = TokenBucketConfig { burst: 1, per_second: 0.001 };
+        let limits = RateLimits::new(&RateLimitConfig { room_creation, ..RateLimitConfig::default() });
+        assert!(limits.check_room_creation(Participant::new_v4(), None).is_ok());
+        assert!(limits.check_room_creation(Participant::new_v4(), None).is_err());
+    }
+}
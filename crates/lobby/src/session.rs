@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use anyhow::bail;
+use axum::http::HeaderMap;
+use axum::http::header::AUTHORIZATION;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha2::Sha256;
+use uuid::Uuid;
+use crate::api::ApiError;
+use crate::config::TokenConfig;
+use crate::domain::Participant;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// A bearer token and the participant it identifies.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub(crate) struct Session {
+    token: String,
+    participant: Participant,
+    expires_at: DateTime<Utc>,
+}
+
+/// What a verified token asserts.
+struct Claims {
+    participant: Participant,
+    session: Uuid,
+    expires_at: i64,
+}
+
+/// A token is `<participant>.<session>.<expires at>.<signature>`; revocations are kept by this node only.
+pub(crate) struct Sessions {
+    /// The first key signs, the rest only verify.
+    keys: Vec<Vec<u8>>,
+    ttl_secs: u64,
+    revoked: Mutex<HashMap<Uuid, i64>>,
+}
+
+impl Sessions {
+    pub(crate) fn new(config: &TokenConfig) -> anyhow::Result<Self> {
+        let mut keys = Vec::with_capacity(config.keys.len());
+        for key in &config.keys {
+            if key.len() < 32 {
+                bail!("token keys must be at least 32 bytes long");
+            }
+            keys.push(key.as_bytes().to_vec());
+        }
+        if keys.is_empty() {
+            tracing::warn!("no token keys configured, issued tokens won't survive a restart");
+            let mut key = vec![0; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+            keys.push(key);
+        }
+        Ok(Self {
+            keys,
+            ttl_secs: config.ttl_secs,
+            revoked: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub(crate) fn issue(&self, participant: Participant) -> Session {
+        let now = Utc::now().timestamp();
+        let expires_at = now.saturating_add(self.ttl_secs as i64);
+        let payload = format!("{participant}.{}.{expires_at}", Uuid::new_v4());
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&self.keys[0], &payload).finalize().into_bytes());
+        Session {
+            token: format!("{payload}.{signature}"),
+            participant,
+            expires_at: DateTime::from_timestamp(expires_at, 0).unwrap_or(DateTime::<Utc>::MAX_UTC),
+        }
+    }
+
+    /// Returns the participant `token` identifies.
+    pub(crate) fn verify(&self, token: &str) -> Result<Participant, ApiError> {
+        self.claims(token).map(|claims| claims.participant)
+    }
+
+    /// Swaps a still valid token for a fresh one, revoking the old one.
+    pub(crate) fn refresh(&self, token: &str) -> Result<Session, ApiError> {
+        let claims = self.claims(token)?;
+        self.revoke_claims(&claims);
+        Ok(self.issue(claims.participant))
+    }
+
+    pub(crate) fn revoke(&self, token: &str) -> Result<(), ApiError> {
+        let claims = self.claims(token)?;
+        self.revoke_claims(&claims);
+        Ok(())
+    }
+
+    fn revoke_claims(&self, claims: &Claims) {
+        let now = Utc::now().timestamp();
+        let mut revoked = self.revoked.lock().unwrap_or_else(|e| e.into_inner());
+        revoked.retain(|_, expires_at| *expires_at > now);
+        revoked.insert(claims.session, claims.expires_at);
+    }
+
+    fn claims(&self, token: &str) -> Result<Claims, ApiError> {
+        let (payload, signature) = token.rsplit_once('.').ok_or(ApiError::InvalidToken)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| ApiError::InvalidToken)?;
+        if !self.keys.iter().any(|key| self.mac(key, payload).verify_slice(&signature).is_ok()) {
+            return Err(ApiError::InvalidToken);
+        }
+        let mut parts = payload.split('.');
+        let (Some(participant), Some(session), Some(expires_at), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(ApiError::InvalidToken);
+        };
+        let claims = Claims {
+            participant: Uuid::from_str(participant).map_err(|_| ApiError::InvalidToken)?,
+            session: Uuid::from_str(session).map_err(|_| ApiError::InvalidToken)?,
+            expires_at: expires_at.parse().map_err(|_| ApiError::InvalidToken)?,
+        };
+        if claims.expires_at <= Utc::now().timestamp() {
+            return Err(ApiError::ExpiredToken);
+        }
+        if self.revoked.lock().unwrap_or_else(|e| e.into_inner()).contains_key(&claims.session) {
+            return Err(ApiError::RevokedToken);
+        }
+        Ok(claims)
+    }
+
+    fn mac(&self, key: &[u8], payload: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(key).expect("hmac accepts keys of any length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+/// The token in an `Authorization: Bearer` header, if the request has one.
+pub(crate) fn bearer_token(headers: &HeaderMap) -> Option<Result<&str, ApiError>> {
+    let value = headers.get(AUTHORIZATION)?;
+    Some(
+        value
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .ok_or(ApiError::InvalidToken),
+    )
+}
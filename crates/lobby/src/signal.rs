@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use crate::config::SignalConfig;
+use crate::domain::{Participant, RoomId};
+
+/// Ephemeral states participants share with the rest of their room.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SignalKind {
+    Typing,
+    Listening,
+    Buffering,
+}
+
+/// Only changes are relayed, besides the active signals sent to joiners.
+pub(crate) struct Signals {
+    ttl: Duration,
+    active: Mutex<HashMap<(RoomId, Participant, SignalKind), Instant>>,
+}
+
+impl Signals {
+    pub(crate) fn new(config: &SignalConfig) -> Self {
+        Self {
+            ttl: Duration::from_millis(config.ttl_ms),
+            active: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// How often expired signals should be looked for.
+    pub(crate) fn sweep_interval(&self) -> Duration {
+        (self.ttl / 2).max(Duration::from_millis(100))
+    }
+
+    /// Records the signal, returning whether it changed and the room should hear about it.
+    pub(crate) fn set(&self, room_id: RoomId, participant: Participant, kind: SignalKind, active: bool) -> bool {
+        self.set_at(room_id, participant, kind, active, Instant::now())
+    }
+
+    fn set_at(&self, room_id: RoomId, participant: Participant, kind: SignalKind, active: bool, now: Instant) -> bool {
+        let mut signals = self.active.lock().unwrap_or_else(PoisonError::into_inner);
+        let key = (room_id, participant, kind);
+        match active {
+            true => signals.insert(key, now + self.ttl).is_none(),
+            false => signals.remove(&key).is_some(),
+        }
+    }
+
+    /// The signals active in the room, for participants joining it.
+    pub(crate) fn active_in(&self, room_id: RoomId) -> Vec<(Participant, SignalKind)> {
+        let signals = self.active.lock().unwrap_or_else(PoisonError::into_inner);
+        signals
+            .keys()
+            .filter(|(signal_room, _, _)| *signal_room == room_id)
+            .map(|(_, participant, kind)| (*participant, *kind))
+            .collect()
+    }
+
+    /// Forgets the participant's signals in the room, returning those that were active.
+    pub(crate) fn clear(&self, room_id: RoomId, participant: Participant) -> Vec<SignalKind> {
+        let mut signals = self.active.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut cleared = Vec::new();
+        signals.retain(|(signal_room, signal_participant, kind), _| {
+            let theirs = *signal_room == room_id && *signal_participant == participant;
+            if theirs {
+                cleared.push(*kind);
+            }
+            !theirs
+        });
+        cleared
+    }
+
+    /// Forgets the signals that weren't repeated in time, returning them.
+    pub(crate) fn expire(&self) -> Vec<(RoomId, Participant, SignalKind)> {
+        self.expire_at(Instant::now())
+    }
+
+    fn expire_at(&self, now: Instant) -> Vec<(RoomId, Participant, SignalKind)> {
+        let mut signals = self.active.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut expired = Vec::new();
+        signals.retain(|key, expires_at| {
+            if *expires_at <= now {
+                expired.push(*key);
+                return false;
+            }
+            true
+        });
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn only_changes_are_relayed_until_the_signal_expires() {
+        let signals = Signals::new(&SignalConfig { ttl_ms: 1_000 });
+        let (room, alice) = (Uuid::new_v4(), Uuid::new_v4());
+        let start = Instant::now();
+
+        assert!(signals.set_at(room, alice, SignalKind::Typing, true, start));
+        assert!(!signals.set_at(room, alice, SignalKind::Typing, true, start + Duration::from_millis(800)));
+        assert!(signals.expire_at(start + Duration::from_millis(1_500)).is_empty());
+        assert_eq!(
+            signals.expire_at(start + Duration::from_millis(1_800)),
+            [(room, alice, SignalKind::Typing)]
+        );
+        assert!(!signals.set_at(room, alice, SignalKind::Typing, false, start + Duration::from_millis(1_900)));
+    }
+
+    #[test]
+    fn leaving_clears_only_that_participants_signals() {
+        let signals = Signals::new(&SignalConfig::default());
+        let (room, alice, bob) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        signals.set(room, alice, SignalKind::Buffering, true);
+        signals.set(room, bob, SignalKind::Typing, true);
+
+        assert_eq!(signals.clear(room, alice), [SignalKind::Buffering]);
+        assert_eq!(signals.active_in(room), [(bob, SignalKind::Typing)]);
+        assert!(signals.active_in(Uuid::new_v4()).is_empty());
+        assert!(signals.set(room, alice, SignalKind::Buffering, true));
+        assert!(!signals.set(room, bob, SignalKind::Typing, true));
+    }
+}
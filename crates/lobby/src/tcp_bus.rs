@@ -0,0 +1,634 @@
+use crate::admin::AdminToken;
+use crate::bus::{BusError, DELIVERY_BUFFER, Delivery, DeliveryError, Envelope, MessageBus, NodeId};
+use crate::config::HubConfig;
+use crate::domain::{Participant, Profile, ProfileRepository, QuotaExceeded, Room, RoomId, RoomQuota, RoomRepository};
+use crate::infrastructure::{InMemoryProfileRepo, InMemoryRoomRepo, InfrastructureError};
+use anyhow::{Context, anyhow, bail};
+use async_trait::async_trait;
+use futures_util::stream::{Stream, StreamExt, poll_fn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{Notify, mpsc, oneshot};
+use tokio::task::JoinSet;
+
+/// Newline delimited JSON frames exchanged between nodes and the hub.
+#[derive(Debug, Serialize, Deserialize)]
+enum Frame {
+    Hello { node: NodeId, secret: String },
+    Claim { participant: Participant },
+    Release { participant: Participant },
+    Publish { id: u64, to: Participant, envelope: Envelope },
+    Deliver { id: u64, to: Participant, envelope: Envelope },
+    Delivered { id: u64, result: Result<(), DeliveryError> },
+    /// `lobby` names the handler whose rooms `op` is about.
+    Repo { id: u64, lobby: String, op: RepoOp },
+    RepoReply { id: u64, result: Result<RepoValue, String> },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum RepoOp {
+    Get { room_id: RoomId },
+    GetAll,
+    Save { room: Room },
+    Insert { room: Room, quota: RoomQuota },
+    Replace { current: Room, room: Room },
+    Delete { room_id: RoomId },
+    GetProfiles { participants: Vec<Participant> },
+    SaveProfile { participant: Participant, profile: Profile },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum RepoValue {
+    Room(Option<Room>),
+    Rooms(Vec<Room>),
+    Saved(Room),
+    Inserted(Result<Room, QuotaExceeded>),
+    Replaced(bool),
+    Deleted,
+    Profiles(HashMap<Participant, Profile>),
+    ProfileSaved(Profile),
+}
+
+async fn write_frames(mut writer: OwnedWriteHalf, mut frames: impl Stream<Item = Frame> + Unpin) {
+    while let Some(frame) = frames.next().await {
+        let mut line = match serde_json::to_vec(&frame) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::error!("failed to serialize bus frame: {e}");
+                continue;
+            }
+        };
+        line.push(b'\n');
+        if let Err(e) = writer.write_all(&line).await {
+            tracing::warn!("bus connection closed: {e}");
+            return;
+        }
+    }
+}
+
+async fn next_frame(lines: &mut tokio::io::Lines<BufReader<OwnedReadHalf>>) -> anyhow::Result<Option<Frame>> {
+    let Some(line) = lines.next_line().await? else {
+        return Ok(None);
+    };
+    Ok(Some(serde_json::from_str(&line).context("malformed bus frame")?))
+}
+
+/// Delays between attempts to reconnect to the hub, doubling from the first to the second.
+const RECONNECT_BACKOFF: (Duration, Duration) = (Duration::from_millis(100), Duration::from_secs(5));
+
+#[derive(Default)]
+struct BusState {
+    // `None` while reconnecting to the hub
+    frames: Option<mpsc::UnboundedSender<Frame>>,
+    node: Option<NodeId>,
+    secret: String,
+    // introduced to the hub again on every reconnect
+    claims: HashSet<Participant>,
+    local: Option<mpsc::Sender<Delivery>>,
+    deliveries: HashMap<u64, oneshot::Sender<Result<(), DeliveryError>>>,
+    repo: HashMap<u64, oneshot::Sender<Result<RepoValue, String>>>,
+}
+
+pub(crate) struct TcpBus {
+    lobby: String,
+    state: Arc<Mutex<BusState>>,
+    next_id: AtomicU64,
+}
+
+impl TcpBus {
+    pub(crate) async fn connect(hub: &str, lobby: &str, secret: &str) -> Result<Self, BusError> {
+        let stream = TcpStream::connect(hub)
+            .await
+            .with_context(|| format!("failed to connect to bus hub at {hub}"))?;
+        let state = Arc::new(Mutex::new(BusState { secret: secret.to_string(), ..BusState::default() }));
+        let reader = attach(&state, stream);
+        tokio::spawn(stay_connected(hub.to_string(), Arc::downgrade(&state), reader));
+        Ok(Self { lobby: lobby.to_string(), state, next_id: AtomicU64::new(0) })
+    }
+
+    fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn send(&self, frame: Frame) -> Result<(), BusError> {
+        lock(&self.state)
+            .frames
+            .as_ref()
+            .and_then(|frames| frames.send(frame).ok())
+            .ok_or_else(|| BusError(anyhow!("bus connection closed")))
+    }
+
+    async fn repo(&self, op: RepoOp) -> Result<RepoValue, InfrastructureError> {
+        let id = self.next_id();
+        let (result_sender, result_receiver) = oneshot::channel();
+        {
+            let mut state = lock(&self.state);
+            let Some(frames) = &state.frames else {
+                return Err(InfrastructureError(anyhow!("bus connection closed")));
+            };
+            let _ = frames.send(Frame::Repo { id, lobby: self.lobby.clone(), op });
+            state.repo.insert(id, result_sender);
+        }
+        result_receiver
+            .await
+            .map_err(|_| InfrastructureError(anyhow!("bus connection closed")))?
+            .map_err(|e| InfrastructureError(anyhow!(e)))
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn attach(state: &Mutex<BusState>, stream: TcpStream) -> OwnedReadHalf {
+    let (reader, writer) = stream.into_split();
+    let (frames, mut frames_receiver) = mpsc::unbounded_channel();
+    tokio::spawn(write_frames(writer, poll_fn(move |cx| frames_receiver.poll_recv(cx))));
+    let mut state = lock(state);
+    if let Some(node) = state.node {
+        let _ = frames.send(Frame::Hello { node, secret: state.secret.clone() });
+        for participant in &state.claims {
+            let _ = frames.send(Frame::Claim { participant: *participant });
+        }
+    }
+    state.frames = Some(frames);
+    reader
+}
+
+async fn stay_connected(hub: String, state: Weak<Mutex<BusState>>, mut reader: OwnedReadHalf) {
+    loop {
+        if let Err(e) = read_from_hub(reader, &state).await {
+            tracing::error!("bus connection failed: {e:#}");
+        }
+        {
+            let Some(state) = state.upgrade() else {
+                return;
+            };
+            let mut state = lock(&state);
+            state.frames = None;
+            // dropping the result senders fails whoever waits on them
+            state.deliveries.clear();
+            state.repo.clear();
+        }
+        let mut backoff = RECONNECT_BACKOFF.0;
+        reader = loop {
+            tokio::time::sleep(backoff).await;
+            let Some(state) = state.upgrade() else {
+                return;
+            };
+            match TcpStream::connect(&hub).await {
+                Ok(stream) => break attach(&state, stream),
+                Err(e) => tracing::warn!("failed to reconnect to bus hub at {hub}: {e}"),
+            }
+            backoff = (backoff * 2).min(RECONNECT_BACKOFF.1);
+        };
+        tracing::info!("reconnected to bus hub at {hub}");
+    }
+}
+
+async fn read_from_hub(reader: OwnedReadHalf, state: &Weak<Mutex<BusState>>) -> anyhow::Result<()> {
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(frame) = next_frame(&mut lines).await? {
+        let Some(state) = state.upgrade() else {
+            return Ok(());
+        };
+        match frame {
+            Frame::Deliver { id, to, envelope } => {
+                let (local, frames) = {
+                    let state = lock(&state);
+                    (state.local.clone(), state.frames.clone())
+                };
+                let Some(frames) = frames else {
+                    continue;
+                };
+                let Some(local) = local else {
+                    let _ = frames.send(Frame::Delivered { id, result: Err(DeliveryError::NotFound) });
+                    continue;
+                };
+                let (result_sender, result_receiver) = oneshot::channel();
+                if local.send(Delivery { to, envelope, result_sender }).await.is_err() {
+                    let _ = frames.send(Frame::Delivered { id, result: Err(DeliveryError::NotFound) });
+                    continue;
+                }
+                tokio::spawn(async move {
+                    let result = result_receiver
+                        .await
+                        .unwrap_or_else(|_| Err(DeliveryError::Failed("node dropped the delivery".to_string())));
+                    let _ = frames.send(Frame::Delivered { id, result });
+                });
+            }
+            Frame::Delivered { id, result } => {
+                if let Some(result_sender) = lock(&state).deliveries.remove(&id) {
+                    let _ = result_sender.send(result);
+                }
+            }
+            Frame::RepoReply { id, result } => {
+                if let Some(result_sender) = lock(&state).repo.remove(&id) {
+                    let _ = result_sender.send(result);
+                }
+            }
+            frame => tracing::warn!("unexpected frame from bus hub: {frame:?}"),
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl MessageBus for TcpBus {
+    async fn join(&self, node: NodeId) -> Result<mpsc::Receiver<Delivery>, BusError> {
+        let (sender, receiver) = mpsc::channel(DELIVERY_BUFFER);
+        let secret = {
+            let mut state = lock(&self.state);
+            state.local = Some(sender);
+            state.node = Some(node);
+            state.secret.clone()
+        };
+        self.send(Frame::Hello { node, secret })?;
+        Ok(receiver)
+    }
+
+    async fn claim(&self, _node: NodeId, participant: Participant) -> Result<(), BusError> {
+        lock(&self.state).claims.insert(participant);
+        self.send(Frame::Claim { participant })
+    }
+
+    async fn release(&self, _node: NodeId, participant: Participant) -> Result<(), BusError> {
+        lock(&self.state).claims.remove(&participant);
+        self.send(Frame::Release { participant })
+    }
+
+    async fn publish(&self, to: Participant, envelope: Envelope) -> Result<(), DeliveryError> {
+        let id = self.next_id();
+        let (result_sender, result_receiver) = oneshot::channel();
+        {
+            let mut state = lock(&self.state);
+            let Some(frames) = &state.frames else {
+                return Err(DeliveryError::Failed("bus connection closed".to_string()));
+            };
+            let _ = frames.send(Frame::Publish { id, to, envelope });
+            state.deliveries.insert(id, result_sender);
+        }
+        result_receiver
+            .await
+            .map_err(|_| DeliveryError::Failed("bus connection closed".to_string()))?
+    }
+}
+
+/// Room repository kept by the hub and shared by every node connected to it.
+pub(crate) struct TcpRoomRepo {
+    bus: Arc<TcpBus>,
+}
+
+impl TcpRoomRepo {
+    pub(crate) fn new(bus: Arc<TcpBus>) -> Self {
+        Self { bus }
+    }
+}
+
+fn unexpected(value: RepoValue) -> InfrastructureError {
+    InfrastructureError(anyhow!("unexpected repository reply: {value:?}"))
+}
+
+#[async_trait]
+impl RoomRepository for TcpRoomRepo {
+    type Err = InfrastructureError;
+
+    async fn get(&self, room_id: RoomId) -> Result<Option<Room>, Self::Err> {
+        match self.bus.repo(RepoOp::Get { room_id }).await? {
+            RepoValue::Room(room) => Ok(room),
+            value => Err(unexpected(value)),
+        }
+    }
+
+    async fn get_all(&self) -> Result<Vec<Room>, Self::Err> {
+        match self.bus.repo(RepoOp::GetAll).await? {
+            RepoValue::Rooms(rooms) => Ok(rooms),
+            value => Err(unexpected(value)),
+        }
+    }
+
+    async fn save(&self, room: Room) -> Result<Room, Self::Err> {
+        match self.bus.repo(RepoOp::Save { room }).await? {
+            RepoValue::Saved(room) => Ok(room),
+            value => Err(unexpected(value)),
+        }
+    }
+
+    async fn insert(&self, room: Room, quota: RoomQuota) -> Result<Result<Room, QuotaExceeded>, Self::Err> {
+        match self.bus.repo(RepoOp::Insert { room, quota }).await? {
+            RepoValue::Inserted(inserted) => Ok(inserted),
+            value => Err(unexpected(value)),
+        }
+    }
+
+    async fn replace(&self, current: &Room, room: Room) -> Result<bool, Self::Err> {
+        let current = current.clone();
+        match self.bus.repo(RepoOp::Replace { current, room }).await? {
+            RepoValue::Replaced(replaced) => Ok(replaced),
+            value => Err(unexpected(value)),
+        }
+    }
+
+    async fn delete(&self, room_id: RoomId) -> Result<(), Self::Err> {
+        match self.bus.repo(RepoOp::Delete { room_id }).await? {
+            RepoValue::Deleted => Ok(()),
+            value => Err(unexpected(value)),
+        }
+    }
+}
+
+/// Profiles kept by the hub, next to the rooms.
+pub(crate) struct TcpProfileRepo {
+    bus: Arc<TcpBus>,
+}
+
+impl TcpProfileRepo {
+    pub(crate) fn new(bus: Arc<TcpBus>) -> Self {
+        Self { bus }
+    }
+}
+
+#[async_trait]
+impl ProfileRepository for TcpProfileRepo {
+    type Err = InfrastructureError;
+
+    async fn get_many(&self, participants: &[Participant]) -> Result<HashMap<Participant, Profile>, Self::Err> {
+        let participants = participants.to_vec();
+        match self.bus.repo(RepoOp::GetProfiles { participants }).await? {
+            RepoValue::Profiles(profiles) => Ok(profiles),
+            value => Err(unexpected(value)),
+        }
+    }
+
+    async fn save(&self, participant: Participant, profile: Profile) -> Result<Profile, Self::Err> {
+        match self.bus.repo(RepoOp::SaveProfile { participant, profile }).await? {
+            RepoValue::ProfileSaved(profile) => Ok(profile),
+            value => Err(unexpected(value)),
+        }
+    }
+}
+
+struct Hub {
+    secret: AdminToken,
+    reconnect_grace: Duration,
+    queue_capacity: usize,
+    // by lobby, as every handler has rooms of its own
+    rooms: Mutex<HashMap<String, Arc<InMemoryRoomRepo>>>,
+    profiles: InMemoryProfileRepo,
+    state: Mutex<HubState>,
+}
+
+/// Identifies one connection to the hub, as a node reconnecting keeps its `NodeId`.
+type ConnectionId = u64;
+
+/// Frames waiting to be written to a node, which is disconnected once it falls too far behind.
+#[derive(Clone)]
+struct NodeConnection {
+    frames: mpsc::Sender<Frame>,
+    overflowed: Arc<Notify>,
+}
+
+impl NodeConnection {
+    fn send(&self, frame: Frame) {
+        if let Err(TrySendError::Full(_)) = self.frames.try_send(frame) {
+            self.overflowed.notify_one();
+        }
+    }
+}
+
+#[derive(Default)]
+struct HubState {
+    connections: HashMap<ConnectionId, NodeConnection>,
+    nodes: HashMap<NodeId, ConnectionId>,
+    owners: HashMap<Participant, NodeId>,
+    // hub delivery id -> (origin connection, origin publish id, destination connection)
+    pending: HashMap<u64, (ConnectionId, u64, ConnectionId)>,
+    next_id: u64,
+    next_connection: ConnectionId,
+}
+
+/// Dropping the returned future closes every connection.
+pub(crate) async fn serve_hub(listener: TcpListener, config: HubConfig) -> anyhow::Result<()> {
+    if config.secret.len() < 32 {
+        bail!("the hub secret must be at least 32 bytes long");
+    }
+    let hub = Arc::new(Hub {
+        secret: AdminToken::new(&config.secret)?,
+        reconnect_grace: Duration::from_millis(config.reconnect_grace_ms),
+        queue_capacity: config.queue_capacity.max(1),
+        rooms: Mutex::default(),
+        profiles: InMemoryProfileRepo::default(),
+        state: Mutex::default(),
+    });
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, peer) = accepted?;
+                let hub = hub.clone();
+                connections.spawn(async move {
+                    if let Err(e) = hub.serve_node(stream).await {
+                        tracing::warn!("bus node {peer} failed: {e:#}");
+                    }
+                });
+            }
+            Some(_) = connections.join_next() => {}
+        }
+    }
+}
+
+impl Hub {
+    async fn serve_node(self: Arc<Self>, stream: TcpStream) -> anyhow::Result<()> {
+        let (reader, writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        let Some(Frame::Hello { node, secret }) = next_frame(&mut lines).await? else {
+            bail!("bus node did not introduce itself");
+        };
+        if !self.secret.verify(&secret) {
+            bail!("bus node {node} presented the wrong secret");
+        }
+        let (frames, mut frames_receiver) = mpsc::channel(self.queue_capacity);
+        tokio::spawn(write_frames(writer, poll_fn(move |cx| frames_receiver.poll_recv(cx))));
+        let connection = NodeConnection { frames, overflowed: Arc::new(Notify::new()) };
+        let id = {
+            let mut state = lock(&self.state);
+            let id = state.next_connection;
+            state.next_connection += 1;
+            state.connections.insert(id, connection.clone());
+            state.nodes.insert(node, id);
+            id
+        };
+        tracing::info!("bus node joined: {node}");
+
+        let result = self.route(node, id, &mut lines, &connection).await;
+        self.leave(node, id);
+        result
+    }
+
+    async fn route(
+        &self,
+        node: NodeId,
+        id: ConnectionId,
+        lines: &mut tokio::io::Lines<BufReader<OwnedReadHalf>>,
+        connection: &NodeConnection,
+    ) -> anyhow::Result<()> {
+        loop {
+            let frame = tokio::select! {
+                frame = next_frame(lines) => frame?,
+                _ = connection.overflowed.notified() => bail!("bus node {node} fell too far behind"),
+            };
+            let Some(frame) = frame else {
+                return Ok(());
+            };
+            match frame {
+                Frame::Claim { participant } => {
+                    lock(&self.state).owners.insert(participant, node);
+                }
+                Frame::Release { participant } => {
+                    let mut state = lock(&self.state);
+                    if state.owners.get(&participant) == Some(&node) {
+                        state.owners.remove(&participant);
+                    }
+                }
+                Frame::Publish { id: publish_id, to, envelope } => {
+                    let mut state = lock(&self.state);
+                    let owner = state
+                        .owners
+                        .get(&to)
+                        .and_then(|owner| state.nodes.get(owner))
+                        .and_then(|owner| Some((*owner, state.connections.get(owner)?.frames.clone())));
+                    let Some((owner, owner_frames)) = owner else {
+                        connection.send(Frame::Delivered { id: publish_id, result: Err(DeliveryError::NotFound) });
+                        continue;
+                    };
+                    let hub_id = state.next_id;
+                    state.next_id += 1;
+                    // a node that doesn't keep up only has its own deliveries refused
+                    let result = match owner_frames.try_send(Frame::Deliver { id: hub_id, to, envelope }) {
+                        Ok(()) => {
+                            state.pending.insert(hub_id, (id, publish_id, owner));
+                            continue;
+                        }
+                        Err(TrySendError::Full(_)) => DeliveryError::QueueFull,
+                        Err(TrySendError::Closed(_)) => DeliveryError::NotFound,
+                    };
+                    connection.send(Frame::Delivered { id: publish_id, result: Err(result) });
+                }
+                Frame::Delivered { id, result } => {
+                    let mut state = lock(&self.state);
+                    let Some((origin, origin_id, _)) = state.pending.remove(&id) else {
+                        continue;
+                    };
+                    if let Some(origin) = state.connections.get(&origin) {
+                        origin.send(Frame::Delivered { id: origin_id, result });
+                    }
+                }
+                Frame::Repo { id, lobby, op } => {
+                    let result = self.repo(&lobby, op).await.map_err(|e| e.to_string());
+                    connection.send(Frame::RepoReply { id, result });
+                }
+                frame => tracing::warn!("unexpected frame from bus node {node}: {frame:?}"),
+            }
+        }
+    }
+
+    async fn repo(&self, lobby: &str, op: RepoOp) -> Result<RepoValue, InfrastructureError> {
+        let rooms = self.rooms(lobby);
+        Ok(match op {
+            RepoOp::Get { room_id } => RepoValue::Room(rooms.get(room_id).await?),
+            RepoOp::GetAll => RepoValue::Rooms(rooms.get_all().await?),
+            RepoOp::Save { room } => RepoValue::Saved(rooms.save(room).await?),
+            RepoOp::Insert { room, quota } => RepoValue::Inserted(rooms.insert(room, quota).await?),
+            RepoOp::Replace { current, room } => RepoValue::Replaced(rooms.replace(&current, room).await?),
+            RepoOp::Delete { room_id } => {
+                rooms.delete(room_id).await?;
+                RepoValue::Deleted
+            }
+            RepoOp::GetProfiles { participants } => RepoValue::Profiles(self.profiles.get_many(&participants).await?),
+            RepoOp::SaveProfile { participant, profile } => {
+                RepoValue::ProfileSaved(self.profiles.save(participant, profile).await?)
+            }
+        })
+    }
+
+    fn rooms(&self, lobby: &str) -> Arc<InMemoryRoomRepo> {
+        lock(&self.rooms).entry(lobby.to_string()).or_default().clone()
+    }
+
+    fn leave(self: &Arc<Self>, node: NodeId, connection: ConnectionId) {
+        tracing::info!("bus node left: {node}");
+        let mut state = lock(&self.state);
+        state.connections.remove(&connection);
+        // unless the node already reconnected
+        if state.nodes.get(&node) == Some(&connection) {
+            state.nodes.remove(&node);
+            let mut orphaned = HashSet::new();
+            state.owners.retain(|participant, owner| {
+                if *owner == node {
+                    orphaned.insert(*participant);
+                }
+                *owner != node
+            });
+            if !orphaned.is_empty() {
+                tokio::spawn(self.clone().evict(node, orphaned));
+            }
+        }
+        let HubState { connections, pending, .. } = &mut *state;
+        pending.retain(|_, (origin, origin_id, destination)| {
+            if *destination != connection {
+                return *origin != connection;
+            }
+            if let Some(origin) = connections.get(origin) {
+                origin.send(Frame::Delivered {
+                    id: *origin_id,
+                    result: Err(DeliveryError::ParticipantDisconnected),
+                });
+            }
+            false
+        });
+    }
+
+    /// Drops the participants of a node that didn't come back in time from their rooms, freeing their seats.
+    async fn evict(self: Arc<Self>, node: NodeId, mut participants: HashSet<Participant>) {
+        tokio::time::sleep(self.reconnect_grace).await;
+        {
+            let state = lock(&self.state);
+            if state.nodes.contains_key(&node) {
+                return;
+            }
+            participants.retain(|participant| !state.owners.contains_key(participant));
+        }
+        let lobbies: Vec<_> = lock(&self.rooms).values().cloned().collect();
+        for rooms in lobbies {
+            if let Err(e) = evict_from(&rooms, &participants).await {
+                tracing::error!("failed to drop the participants of bus node {node} from their rooms: {e:#}");
+            }
+        }
+        tracing::info!("dropped the participants of bus node {node} from their rooms");
+    }
+}
+
+async fn evict_from(rooms: &InMemoryRoomRepo, participants: &HashSet<Participant>) -> Result<(), InfrastructureError> {
+    loop {
+        let mut raced = false;
+        for current in rooms.get_all().await? {
+            if !current.participants.iter().any(|participant| participants.contains(participant)) {
+                continue;
+            }
+            let mut room = current.clone();
+            room.participants.retain(|participant| !participants.contains(participant));
+            raced |= !rooms.replace(&current, room).await?;
+        }
+        if !raced {
+            return Ok(());
+        }
+    }
+}
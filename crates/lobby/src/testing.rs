@@ -0,0 +1,216 @@
+//! Drives a [`MessageHandler`] through a room without a server or sockets.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::sync::{Arc, Mutex, PoisonError};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use uuid::Uuid;
+use crate::app::{self, RoomAppError};
+use crate::domain::{
+    MessageHandler, MessageSender, MessageSenderError, Participant, Profile, ProfileRepository, Room, RoomError, RoomId,
+    RoomRepository,
+};
+use crate::infrastructure::{InMemoryProfileRepo, InMemoryRoomRepo, InfrastructureError};
+use crate::metrics::LobbyMetrics;
+
+pub struct TestRoom<Inbound, Outbound, Err> {
+    handler: Arc<dyn MessageHandler<Inbound, Outbound = Outbound, Err = Err> + Send + Sync + 'static>,
+    room_repo: InMemoryRoomRepo,
+    profile_repo: InMemoryProfileRepo,
+    sender: RecordingSender<Outbound>,
+    metrics: LobbyMetrics,
+    room_id: RoomId,
+}
+
+impl<Inbound, Outbound, Err> TestRoom<Inbound, Outbound, Err>
+where
+    Inbound: Send + Sync + 'static,
+    Outbound: Clone + Send + Sync + 'static,
+    Err: Error + Send + Sync + 'static,
+{
+    /// An empty room with room for everyone.
+    pub async fn new(
+        handler: Arc<dyn MessageHandler<Inbound, Outbound = Outbound, Err = Err> + Send + Sync + 'static>,
+    ) -> Self {
+        Self::with_capacity(handler, usize::MAX).await
+    }
+
+    pub async fn with_capacity(
+        handler: Arc<dyn MessageHandler<Inbound, Outbound = Outbound, Err = Err> + Send + Sync + 'static>,
+        capacity: usize,
+    ) -> Self {
+        let room_repo = InMemoryRoomRepo::new();
+        let room = Room::new("test", capacity, Uuid::new_v4());
+        let room_id = room.id;
+        room_repo.save(room).await.expect("in-memory repositories don't fail");
+        Self {
+            handler,
+            room_repo,
+            profile_repo: InMemoryProfileRepo::default(),
+            sender: RecordingSender::new(),
+            metrics: LobbyMetrics::new("test").expect("metrics are well-formed"),
+            room_id,
+        }
+    }
+
+    pub async fn room(&self) -> Room {
+        self.room_repo
+            .get(self.room_id)
+            .await
+            .expect("in-memory repositories don't fail")
+            .expect("test room is never closed")
+    }
+
+    /// Joins a new participant without a profile.
+    pub async fn join(&self) -> Result<Participant, RoomError> {
+        let participant = Uuid::new_v4();
+        self.join_as(participant).await?;
+        Ok(participant)
+    }
+
+    /// Joins a new participant presenting as `profile`, which isn't validated.
+    pub async fn join_with_profile(&self, profile: Profile) -> Result<Participant, RoomError> {
+        let participant = self.join().await?;
+        self.set_profile(participant, profile).await;
+        Ok(participant)
+    }
+
+    /// Joins the participant, delivering them whatever the handler has for newcomers.
+    pub async fn join_as(&self, participant: Participant) -> Result<(), RoomError> {
+        app::join_room(&self.room_repo, self.room_id, participant).await.map_err(room_error)?;
+        self.sender.reconnect(participant);
+        app::welcome(
+            &self.room_repo,
+            &self.profile_repo,
+            &self.sender,
+            self.handler.as_ref(),
+            &self.metrics,
+            self.room_id,
+            participant,
+        )
+        .await
+        .map_err(room_error)
+    }
+
+    pub async fn set_profile(&self, participant: Participant, profile: Profile) {
+        self.profile_repo
+            .save(participant, profile)
+            .await
+            .expect("in-memory repositories don't fail");
+    }
+
+    /// Leaves the room the way closing a socket does.
+    pub async fn leave(&self, participant: Participant) {
+        app::leave_room(&self.room_repo, self.room_id, participant)
+            .await
+            .map_err(room_error)
+            .expect("test room is never closed");
+    }
+
+    /// They stay in the room until a message fails to reach them.
+    pub fn disconnect(&self, participant: Participant) {
+        self.sender.disconnect(participant);
+    }
+
+    /// Hands `msg` to the handler and delivers its response.
+    pub async fn send(&self, from: Participant, msg: Inbound) -> Result<(), RoomError> {
+        app::handle_message(
+            &self.room_repo,
+            &self.profile_repo,
+            &self.sender,
+            self.handler.as_ref(),
+            &self.metrics,
+            self.room_id,
+            from,
+            msg,
+        )
+        .await
+        .map_err(room_error)
+    }
+
+    pub fn sender(&self) -> &RecordingSender<Outbound> {
+        &self.sender
+    }
+
+    /// Messages delivered to the participant since they were last taken.
+    pub fn take(&self, participant: Participant) -> Vec<Outbound> {
+        self.sender.take(participant)
+    }
+}
+
+/// The only failures left once the repositories are in memory come from the room itself.
+fn room_error(e: RoomAppError) -> RoomError {
+    match e {
+        RoomAppError::RoomDomain(e) => e,
+        e => unreachable!("test room failed outside the domain: {e}"),
+    }
+}
+
+/// A [`MessageSender`] that keeps every message per participant instead of sending it.
+pub struct RecordingSender<Outbound> {
+    state: Arc<Mutex<Recording<Outbound>>>,
+}
+
+struct Recording<Outbound> {
+    received: HashMap<Participant, Vec<Outbound>>,
+    disconnected: HashSet<Participant>,
+}
+
+impl<Outbound> Clone for RecordingSender<Outbound> {
+    fn clone(&self) -> Self {
+        Self { state: self.state.clone() }
+    }
+}
+
+impl<Outbound> Default for RecordingSender<Outbound> {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(Recording {
+                received: HashMap::new(),
+                disconnected: HashSet::new(),
+            })),
+        }
+    }
+}
+
+impl<Outbound: Clone> RecordingSender<Outbound> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn disconnect(&self, participant: Participant) {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner).disconnected.insert(participant);
+    }
+
+    pub fn reconnect(&self, participant: Participant) {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner).disconnected.remove(&participant);
+    }
+
+    /// Every message delivered to the participant, oldest first.
+    pub fn received(&self, participant: Participant) -> Vec<Outbound> {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.received.get(&participant).cloned().unwrap_or_default()
+    }
+
+    /// Like [`Self::received`], forgetting the messages so the next call only sees newer ones.
+    pub fn take(&self, participant: Participant) -> Vec<Outbound> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.received.remove(&participant).unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl<Outbound: Send + 'static> MessageSender<Outbound> for RecordingSender<Outbound> {
+    async fn send(&self, to: Participant, outbound_msg: Outbound) -> Result<(), MessageSenderError> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.disconnected.contains(&to) {
+            return Err(MessageSenderError::ParticipantDisconnected(
+                to,
+                Box::new(InfrastructureError(anyhow!("participant disconnected in test: {to}"))),
+            ));
+        }
+        state.received.entry(to).or_default().push(outbound_msg);
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use std::sync::Arc;
+use axum::Router;
+use common::{EchoHandler, create_room, join, next_message, request, request_with_headers, serve_router};
+use lobby::config::{AdminConfig, LobbyConfig};
+use lobby::domain::Participant;
+use serde_json::json;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+const ADMIN_TOKEN: &str = "an admin token that is at least thirty-two bytes long";
+
+async fn serve() -> String {
+    let config = LobbyConfig {
+        admin: AdminConfig { token: Some(ADMIN_TOKEN.to_string()) },
+        ..common::config()
+    };
+    let lobby = lobby::setup(Arc::new(EchoHandler), config).await.unwrap();
+    serve_router(Router::new().merge(lobby.router).nest("/admin", lobby.admin.unwrap())).await
+}
+
+async fn admin(addr: &str, method: &str, path: &str, body: &str) -> (String, String) {
+    let authorization = format!("Bearer {ADMIN_TOKEN}");
+    request_with_headers(addr, method, &format!("/admin{path}"), &[("Authorization", &authorization)], body).await
+}
+
+async fn next_json(ws: &mut common::Socket) -> serde_json::Value {
+    serde_json::from_str(next_message(ws).await.to_text().unwrap()).unwrap()
+}
+
+async fn assert_kicked(ws: &mut common::Socket, reason: &str) {
+    assert_eq!(next_json(ws).await, json!({ "Kicked": { "reason": reason } }));
+    let Message::Close(Some(close_frame)) = next_message(ws).await else {
+        panic!("expected a close frame");
+    };
+    assert_eq!(close_frame.code, CloseCode::Policy);
+}
+
+#[tokio::test]
+async fn the_admin_api_requires_the_admin_token() {
+    let addr = serve().await;
+    let (head, _) = request(&addr, "GET", "/admin/rooms", "", "").await;
+    assert!(head.starts_with("HTTP/1.1 401"), "{head}");
+
+    let (head, _) = request_with_headers(&addr, "GET", "/admin/rooms", &[("Authorization", "Bearer not the admin token")], "").await;
+    assert!(head.starts_with("HTTP/1.1 401"), "{head}");
+
+    // nor does a participant's session token do
+    let (_, body) = request(&addr, "POST", "/sessions", "", "").await;
+    let session: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let authorization = format!("Bearer {}", session["token"].as_str().unwrap());
+    let (head, _) = request_with_headers(&addr, "GET", "/admin/rooms", &[("Authorization", &authorization)], "").await;
+    assert!(head.starts_with("HTTP/1.1 401"), "{head}");
+}
+
+#[tokio::test]
+async fn operators_can_inspect_and_intervene() {
+    let addr = serve().await;
+    let alice = Participant::new_v4();
+    let bob = Participant::new_v4();
+    let room = create_room(&addr, alice, 2).await;
+    let mut alice_ws = join(&addr, room.id, alice).await;
+    let mut bob_ws = join(&addr, room.id, bob).await;
+
+    let (head, body) = admin(&addr, "GET", "/rooms", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    let rooms: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        rooms[0]["connections"],
+        json!({
+            alice.to_string(): { "queued": 0, "closing": false },
+            bob.to_string(): { "queued": 0, "closing": false },
+        })
+    );
+
+    let (head, body) = admin(&addr, "POST", "/announcements", r#"{"message":"maintenance at noon"}"#).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&body).unwrap(), json!({ "delivered": 2 }));
+    for ws in [&mut alice_ws, &mut bob_ws] {
+        assert_eq!(next_json(ws).await, json!({ "Announcement": { "message": "maintenance at noon" } }));
+    }
+
+    let (head, _) = admin(&addr, "DELETE", &format!("/participants/{bob}"), "").await;
+    assert!(head.starts_with("HTTP/1.1 204"), "{head}");
+    assert_kicked(&mut bob_ws, "disconnected by an operator").await;
+
+    let (head, body) = admin(&addr, "GET", &format!("/rooms/{}", room.id), "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    let state: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(state["participants"], json!([alice]));
+    assert_eq!(state["handler_state"], json!({ "participants": 1 }));
+
+    let (head, _) = admin(&addr, "DELETE", &format!("/rooms/{}", room.id), "").await;
+    assert!(head.starts_with("HTTP/1.1 204"), "{head}");
+    assert_kicked(&mut alice_ws, "room closed by an operator").await;
+    let (_, body) = request(&addr, "GET", "/rooms", "", "").await;
+    assert_eq!(body, "[]");
+
+    let (head, _) = admin(&addr, "DELETE", &format!("/rooms/{}", room.id), "").await;
+    assert!(head.starts_with("HTTP/1.1 404"), "{head}");
+}
+
+#[tokio::test]
+async fn there_is_no_admin_api_without_a_token() {
+    let lobby = lobby::setup(Arc::new(EchoHandler), common::config()).await.unwrap();
+    assert!(lobby.admin.is_none());
+}
@@ -0,0 +1,192 @@
+mod common;
+
+use std::time::Duration;
+use common::{Outbound, create_room, join, next_outbound, request};
+use futures_util::SinkExt;
+use lobby::config::{BusConfig, HubConfig, LobbyConfig};
+use lobby::domain::{Participant, Room};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+const HUB_SECRET: &str = "a cluster secret of thirty-two bytes or more";
+
+fn hub_config() -> HubConfig {
+    HubConfig { secret: HUB_SECRET.to_string(), reconnect_grace_ms: 200, ..HubConfig::default() }
+}
+
+async fn start_node(hub: &str) -> String {
+    start_lobby(hub, &common::config().name).await
+}
+
+async fn start_lobby(hub: &str, name: &str) -> String {
+    start_lobby_with_secret(hub, name, HUB_SECRET).await
+}
+
+async fn start_lobby_with_secret(hub: &str, name: &str, secret: &str) -> String {
+    let config = LobbyConfig {
+        name: name.to_string(),
+        bus: BusConfig::Tcp { hub: hub.to_string(), secret: secret.to_string() },
+        ..common::config()
+    };
+    common::serve(config).await
+}
+
+#[tokio::test]
+async fn messages_reach_participants_connected_to_other_nodes() {
+    let hub_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let hub = hub_listener.local_addr().unwrap().to_string();
+    tokio::spawn(lobby::serve_bus_hub(hub_listener, hub_config()));
+    let node_a = start_node(&hub).await;
+    let node_b = start_node(&hub).await;
+
+    let alice = Participant::new_v4();
+    let bob = Participant::new_v4();
+    let room = create_room(&node_a, alice, 2).await;
+
+    // rooms are shared through the hub
+    let (_, body) = request(&node_b, "GET", "/rooms", "", "").await;
+    let rooms: Vec<Room> = serde_json::from_str(&body).unwrap();
+    assert_eq!(rooms.iter().map(|r| r.id).collect::<Vec<_>>(), vec![room.id]);
+
+    let mut alice_ws = join(&node_a, room.id, alice).await;
+    let mut bob_ws = join(&node_b, room.id, bob).await;
+
+    alice_ws.send(Message::text(r#"{"Shout":{"content":"hello"}}"#)).await.unwrap();
+    let heard = Outbound::Heard { from: alice, content: "hello".to_string() };
+    assert_eq!(next_outbound(&mut alice_ws).await, heard);
+    assert_eq!(next_outbound(&mut bob_ws).await, heard);
+
+    bob_ws
+        .send(Message::text(format!(r#"{{"Whisper":{{"to":"{alice}","content":"psst"}}}}"#)))
+        .await
+        .unwrap();
+    assert_eq!(next_outbound(&mut alice_ws).await, Outbound::Heard { from: bob, content: "psst".to_string() });
+
+    // the room is closed for every node
+    let (head, _) = request(&node_b, "DELETE", &format!("/rooms/{}", room.id), &common::cookie(alice), "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    let (_, body) = request(&node_a, "GET", "/rooms", "", "").await;
+    assert_eq!(body, "[]");
+}
+
+async fn rooms_once_reachable(node: &str) -> Vec<Room> {
+    for _ in 0..100 {
+        let (head, body) = request(node, "GET", "/rooms", "", "").await;
+        if head.starts_with("HTTP/1.1 200") {
+            return serde_json::from_str(&body).unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("node {node} never reached the hub again");
+}
+
+#[tokio::test]
+async fn nodes_reconnect_when_the_hub_restarts() {
+    let hub_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let hub = hub_listener.local_addr().unwrap().to_string();
+    let serving = tokio::spawn(lobby::serve_bus_hub(hub_listener, hub_config()));
+    let node_a = start_node(&hub).await;
+    let node_b = start_node(&hub).await;
+
+    serving.abort();
+    let _ = serving.await;
+    let hub_listener = TcpListener::bind(&hub).await.unwrap();
+    tokio::spawn(lobby::serve_bus_hub(hub_listener, hub_config()));
+
+    // the hub keeps rooms in memory, so they start over
+    assert!(rooms_once_reachable(&node_a).await.is_empty());
+    assert!(rooms_once_reachable(&node_b).await.is_empty());
+
+    let alice = Participant::new_v4();
+    let bob = Participant::new_v4();
+    let room = create_room(&node_a, alice, 2).await;
+    let mut alice_ws = join(&node_a, room.id, alice).await;
+    let mut bob_ws = join(&node_b, room.id, bob).await;
+
+    bob_ws.send(Message::text(r#"{"Shout":{"content":"still there?"}}"#)).await.unwrap();
+    let heard = Outbound::Heard { from: bob, content: "still there?".to_string() };
+    assert_eq!(next_outbound(&mut alice_ws).await, heard);
+    assert_eq!(next_outbound(&mut bob_ws).await, heard);
+}
+
+#[tokio::test]
+async fn concurrent_joins_on_two_nodes_all_count() {
+    let hub_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let hub = hub_listener.local_addr().unwrap().to_string();
+    tokio::spawn(lobby::serve_bus_hub(hub_listener, hub_config()));
+    let node_a = start_node(&hub).await;
+    let node_b = start_node(&hub).await;
+    let room = create_room(&node_a, Participant::new_v4(), 10).await;
+
+    let joins = (0..10).map(|i| {
+        let node = if i % 2 == 0 { &node_a } else { &node_b };
+        join(node, room.id, Participant::new_v4())
+    });
+    let _sockets = futures_util::future::join_all(joins).await;
+
+    let (_, body) = request(&node_b, "GET", "/rooms", "", "").await;
+    let rooms: Vec<Room> = serde_json::from_str(&body).unwrap();
+    assert_eq!(rooms[0].participants.len(), 10);
+}
+
+#[tokio::test]
+async fn every_lobby_has_rooms_of_its_own_on_the_hub() {
+    let hub_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let hub = hub_listener.local_addr().unwrap().to_string();
+    tokio::spawn(lobby::serve_bus_hub(hub_listener, hub_config()));
+    let chat = start_lobby(&hub, "chat").await;
+    let other_chat = start_lobby(&hub, "chat").await;
+    let echo = start_lobby(&hub, "echo").await;
+
+    let room = create_room(&chat, Participant::new_v4(), 2).await;
+
+    let (_, body) = request(&other_chat, "GET", "/rooms", "", "").await;
+    let rooms: Vec<Room> = serde_json::from_str(&body).unwrap();
+    assert_eq!(rooms.iter().map(|r| r.id).collect::<Vec<_>>(), vec![room.id]);
+    let (_, body) = request(&echo, "GET", "/rooms", "", "").await;
+    assert_eq!(body, "[]");
+}
+
+#[tokio::test]
+async fn nodes_without_the_secret_are_turned_away() {
+    let hub_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let hub = hub_listener.local_addr().unwrap().to_string();
+    tokio::spawn(lobby::serve_bus_hub(hub_listener, hub_config()));
+    let node = start_node(&hub).await;
+    create_room(&node, Participant::new_v4(), 2).await;
+
+    let intruder = start_lobby_with_secret(&hub, &common::config().name, "a guess that is thirty-two bytes long").await;
+
+    let (head, _) = request(&intruder, "GET", "/rooms", "", "").await;
+    assert!(head.starts_with("HTTP/1.1 500"), "{head}");
+}
+
+#[tokio::test]
+async fn seats_held_on_a_node_that_died_are_freed() {
+    let hub_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let hub = hub_listener.local_addr().unwrap().to_string();
+    tokio::spawn(lobby::serve_bus_hub(hub_listener, hub_config()));
+    let node_a = start_node(&hub).await;
+    // a runtime of its own, so the node can be killed along with every task it spawned
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let hub_b = hub.clone();
+    let node_b = runtime.spawn(async move { start_node(&hub_b).await }).await.unwrap();
+
+    let (alice, bob, carol) = (Participant::new_v4(), Participant::new_v4(), Participant::new_v4());
+    let room = create_room(&node_a, alice, 2).await;
+    let _alice_ws = join(&node_a, room.id, alice).await;
+    let _bob_ws = join(&node_b, room.id, bob).await;
+    assert_eq!(rooms_once_reachable(&node_a).await[0].participants, [alice, bob]);
+
+    runtime.shutdown_background();
+
+    for _ in 0..100 {
+        if rooms_once_reachable(&node_a).await[0].participants == [alice] {
+            let _carol_ws = join(&node_a, room.id, carol).await;
+            assert_eq!(rooms_once_reachable(&node_a).await[0].participants, [alice, carol]);
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("bob kept his seat after his node died");
+}
@@ -0,0 +1,162 @@
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use axum::Router;
+use axum::response::IntoResponse;
+use axum_extra::extract::cookie::{Cookie, Key, SignedCookieJar};
+use futures_util::StreamExt;
+use lobby::config::{CookieConfig, LobbyConfig};
+use lobby::domain::{MessageHandler, MessageResponse, Participant, Room, RoomContext, RoomId};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+pub type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+pub struct EchoHandler;
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub enum Inbound {
+    Shout { content: String },
+    Whisper { to: Participant, content: String },
+    WhoAmI,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub enum Outbound {
+    Heard { from: Participant, content: String },
+    YouAre { display_name: String },
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum EchoError {}
+
+#[async_trait]
+impl MessageHandler<Inbound> for EchoHandler {
+    type Outbound = Outbound;
+    type Err = EchoError;
+
+    async fn handle_message(&self, room: &RoomContext<'_>, from: Participant, msg: Inbound) -> Result<MessageResponse<Outbound>, EchoError> {
+        Ok(match msg {
+            Inbound::Shout { content } => MessageResponse::Broadcast { msg: Outbound::Heard { from, content } },
+            Inbound::Whisper { to, content } => MessageResponse::Unicast { to, msg: Outbound::Heard { from, content } },
+            Inbound::WhoAmI => MessageResponse::Unicast { to: from, msg: Outbound::YouAre { display_name: room.display_name(from) } },
+        })
+    }
+    async fn debug_state(&self, room: &RoomContext<'_>) -> serde_json::Value {
+        serde_json::json!({ "participants": room.participants.len() })
+    }
+}
+
+pub const COOKIE_KEY: &str = "a test key that is at least thirty-two bytes long";
+
+/// Lobby configuration whose participant cookies can be forged with `cookie`.
+pub fn config() -> LobbyConfig {
+    LobbyConfig {
+        cookies: CookieConfig {
+            keys: vec![COOKIE_KEY.to_string()],
+            ..CookieConfig::default()
+        },
+        ..LobbyConfig::default()
+    }
+}
+
+/// Serves an echo lobby set up with `config` on an ephemeral port, returning its address.
+pub async fn serve(config: LobbyConfig) -> String {
+    serve_router(lobby::setup(Arc::new(EchoHandler), config).await.unwrap().router).await
+}
+
+/// Like `serve`, but the lobby sees the peer address of every request, as per-IP limits need.
+pub async fn serve_with_connect_info(config: LobbyConfig) -> String {
+    let router = lobby::setup(Arc::new(EchoHandler), config).await.unwrap().router;
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let app = router.into_make_service_with_connect_info::<SocketAddr>();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    addr
+}
+
+/// Serves `router` on an ephemeral port, returning its address.
+pub async fn serve_router(router: Router) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    addr
+}
+
+/// `Cookie` header identifying `participant`, signed with `key`.
+pub fn signed_cookie(participant: Participant, key: &str) -> String {
+    let expires_at = chrono::Utc::now().timestamp() + 3600;
+    let cookie_jar = SignedCookieJar::new(Key::derive_from(key.as_bytes()))
+        .add(Cookie::new("participant", format!("{participant}.{expires_at}")));
+    let response = (cookie_jar, ()).into_response();
+    let set_cookie = response.headers()["set-cookie"].to_str().unwrap();
+    set_cookie.split(';').next().unwrap().to_string()
+}
+
+/// `Cookie` header identifying `participant` to a lobby set up with `config`.
+pub fn cookie(participant: Participant) -> String {
+    signed_cookie(participant, COOKIE_KEY)
+}
+
+/// Sends a bare HTTP/1.1 request, returning the response head and body.
+pub async fn request(addr: &str, method: &str, path: &str, cookie: &str, body: &str) -> (String, String) {
+    request_with_headers(addr, method, path, &[("Cookie", cookie)], body).await
+}
+
+pub async fn request_with_headers(
+    addr: &str,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> (String, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let headers: String = headers.iter().flat_map(|(name, value)| [name, ": ", value, "\r\n"]).collect();
+    let request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\n{headers}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head.to_string(), body.to_string())
+}
+
+pub async fn create_room(addr: &str, owner: Participant, capacity: usize) -> Room {
+    let body = format!(r#"{{"name":"test","capacity":{capacity}}}"#);
+    let (_, body) = request(addr, "POST", "/rooms", &cookie(owner), &body).await;
+    serde_json::from_str(&body).unwrap()
+}
+
+pub async fn join(addr: &str, room_id: RoomId, participant: Participant) -> Socket {
+    let mut request = format!("ws://{addr}/rooms/{room_id}").into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("cookie", cookie(participant).parse().unwrap());
+    let (ws, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+    // let the socket register with the sender actor
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    ws
+}
+
+pub async fn next_message(ws: &mut Socket) -> Message {
+    tokio::time::timeout(Duration::from_secs(5), ws.next())
+        .await
+        .expect("timed out waiting for a message")
+        .unwrap()
+        .unwrap()
+}
+
+pub async fn next_outbound(ws: &mut Socket) -> Outbound {
+    serde_json::from_str(next_message(ws).await.to_text().unwrap()).unwrap()
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::{COOKIE_KEY, cookie, request, serve, signed_cookie};
+use lobby::config::{CookieConfig, LobbyConfig, SameSitePolicy};
+use lobby::domain::Participant;
+
+const CREATE_ROOM: &str = r#"{"name":"test","capacity":2}"#;
+
+fn set_cookie(head: &str) -> Option<&str> {
+    head.lines().find_map(|line| line.strip_prefix("set-cookie: "))
+}
+
+#[tokio::test]
+async fn new_participants_get_a_signed_http_only_cookie() {
+    let addr = serve(common::config()).await;
+    let (head, _) = request(&addr, "POST", "/rooms", "", CREATE_ROOM).await;
+    let issued = set_cookie(&head).unwrap_or_else(|| panic!("no participant cookie issued: {head}"));
+    assert!(issued.contains("HttpOnly"), "{issued}");
+    assert!(issued.contains("SameSite=Lax"), "{issued}");
+    assert!(issued.contains("Max-Age=2592000"), "{issued}");
+    assert!(!issued.contains("Secure"), "{issued}");
+
+    // the issued cookie is accepted as is and not issued again
+    let issued = issued.split(';').next().unwrap();
+    let (head, _) = request(&addr, "POST", "/rooms", issued, CREATE_ROOM).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    assert_eq!(set_cookie(&head), None);
+}
+
+#[tokio::test]
+async fn cookie_attributes_follow_the_config() {
+    let config = LobbyConfig {
+        cookies: CookieConfig {
+            secure: true,
+            same_site: SameSitePolicy::Strict,
+            ..CookieConfig::default()
+        },
+        ..LobbyConfig::default()
+    };
+    let addr = serve(config).await;
+    let (head, _) = request(&addr, "POST", "/rooms", "", CREATE_ROOM).await;
+    let issued = set_cookie(&head).unwrap();
+    assert!(issued.contains("Secure"), "{issued}");
+    assert!(issued.contains("SameSite=Strict"), "{issued}");
+}
+
+#[tokio::test]
+async fn unsigned_cookies_are_rejected_and_tampered_ones_replaced() {
+    let addr = serve(common::config()).await;
+    let owner = Participant::new_v4();
+    let room = common::create_room(&addr, owner, 2).await;
+
+    // what an impersonator learns from `GET /rooms`
+    let (head, body) = request(&addr, "DELETE", &format!("/rooms/{}", room.id), &format!("participant={owner}"), "").await;
+    assert!(head.starts_with("HTTP/1.1 401"), "{head}");
+    assert!(body.contains("not signed"), "{body}");
+
+    // a forged cookie only earns a new identity, which doesn't own the room
+    let forged = cookie(owner).replace(&owner.to_string(), &Participant::new_v4().to_string());
+    let (head, body) = request(&addr, "DELETE", &format!("/rooms/{}", room.id), &forged, "").await;
+    assert!(head.starts_with("HTTP/1.1 401"), "{head}");
+    assert_ne!(body, "invalid participant cookie");
+
+    let (head, _) = request(&addr, "DELETE", &format!("/rooms/{}", room.id), &cookie(owner), "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+}
+
+#[tokio::test]
+async fn cookies_signed_with_a_retired_key_are_re_signed() {
+    let new_key = "a freshly rotated key, also thirty-two bytes or more";
+    let config = LobbyConfig {
+        cookies: CookieConfig {
+            keys: vec![new_key.to_string(), COOKIE_KEY.to_string()],
+            ..CookieConfig::default()
+        },
+        ..LobbyConfig::default()
+    };
+    let addr = serve(config).await;
+    let owner = Participant::new_v4();
+
+    let (head, _) = request(&addr, "POST", "/rooms", &cookie(owner), CREATE_ROOM).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    let re_signed = set_cookie(&head).unwrap().split(';').next().unwrap();
+    assert_ne!(re_signed, cookie(owner));
+    assert!(re_signed.contains(&owner.to_string()), "{re_signed}");
+
+    let (head, _) = request(&addr, "POST", "/rooms", &signed_cookie(owner, new_key), CREATE_ROOM).await;
+    assert_eq!(set_cookie(&head), None);
+
+    let (head, _) = request(&addr, "POST", "/rooms", &signed_cookie(owner, "an unknown key that nobody has ever configured"), CREATE_ROOM).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    assert!(!set_cookie(&head).unwrap().contains(&owner.to_string()), "{head}");
+}
+
+#[tokio::test]
+async fn cookies_from_before_a_restart_with_another_key_get_a_new_identity() {
+    let addr = serve(LobbyConfig::default()).await;
+    let (head, _) = request(&addr, "POST", "/rooms", "", CREATE_ROOM).await;
+    let before = set_cookie(&head).unwrap().split(';').next().unwrap().to_string();
+
+    // no keys configured, so the restarted lobby signs with a key of its own
+    let addr = serve(LobbyConfig::default()).await;
+    let (head, _) = request(&addr, "POST", "/rooms", &before, CREATE_ROOM).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    let after = set_cookie(&head).unwrap_or_else(|| panic!("no participant cookie issued: {head}"));
+    assert_ne!(after.split(';').next().unwrap(), before);
+}
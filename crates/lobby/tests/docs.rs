@@ -0,0 +1,78 @@
+mod common;
+
+use common::{request, serve};
+use serde_json::Value;
+
+async fn document(addr: &str, path: &str) -> Value {
+    let (head, body) = request(addr, "GET", path, "", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    serde_json::from_str(&body).unwrap()
+}
+
+/// Every `$ref` in `value` must point somewhere in `document`.
+fn assert_refs_resolve(document: &Value, value: &Value) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(reference)) = object.get("$ref") {
+                let pointer = reference.strip_prefix('#').expect("only local references");
+                assert!(document.pointer(pointer).is_some(), "dangling reference {reference}");
+            }
+            object.values().for_each(|value| assert_refs_resolve(document, value));
+        }
+        Value::Array(values) => values.iter().for_each(|value| assert_refs_resolve(document, value)),
+        _ => {}
+    }
+}
+
+/// The names of the variants an externally tagged enum schema accepts.
+fn variants(schema: &Value) -> Vec<String> {
+    let mut variants: Vec<_> = schema["oneOf"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|variant| {
+            // unit variants are plain strings, the others objects with a single property
+            let names = variant.get("enum").or_else(|| variant.get("required")).unwrap();
+            names.as_array().unwrap().iter().map(|name| name.as_str().unwrap().to_string()).collect::<Vec<_>>()
+        })
+        .collect();
+    variants.sort();
+    variants
+}
+
+#[tokio::test]
+async fn openapi_describes_the_rest_routes() {
+    let addr = serve(common::config()).await;
+    let openapi = document(&addr, "/openapi.json").await;
+    assert_eq!(openapi["openapi"], "3.1.0");
+    assert_refs_resolve(&openapi, &openapi);
+
+    let paths = openapi["paths"].as_object().unwrap();
+    for path in ["/rooms", "/rooms/{room_id}", "/me", "/participants/{participant}", "/sessions", "/sessions/refresh"] {
+        assert!(paths.contains_key(path), "{path} is not described");
+    }
+    let schemas = &openapi["components"]["schemas"];
+    let room = &schemas["RoomResponse"];
+    for field in ["id", "name", "participants", "capacity", "created_at", "created_by", "profiles"] {
+        assert!(room["properties"].get(field).is_some(), "RoomResponse.{field} is not described");
+    }
+    assert_eq!(schemas["CreateRoomRequest"]["required"], serde_json::json!(["name", "capacity"]));
+}
+
+#[tokio::test]
+async fn asyncapi_describes_the_handler_messages() {
+    let addr = serve(common::config()).await;
+    let asyncapi = document(&addr, "/asyncapi.json").await;
+    assert_eq!(asyncapi["asyncapi"], "3.0.0");
+    assert_refs_resolve(&asyncapi, &asyncapi);
+
+    let schemas = &asyncapi["components"]["schemas"];
+    assert_eq!(asyncapi["components"]["messages"]["inbound"]["payload"]["$ref"], "#/components/schemas/Inbound");
+    assert_eq!(variants(&schemas["Inbound"]), ["Shout", "Whisper", "WhoAmI"]);
+    assert_eq!(variants(&schemas["Outbound"]), ["Heard", "YouAre"]);
+    assert_eq!(
+        variants(&schemas["LobbyNotice"]),
+        ["Announcement", "Kicked", "RateLimited", "ServerGoingAway", "Signal"]
+    );
+    assert_eq!(variants(&schemas["LobbyRequest"]), ["Signal"]);
+}
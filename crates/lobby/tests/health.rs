@@ -0,0 +1,59 @@
+mod common;
+
+use std::sync::Arc;
+use common::{EchoHandler, request, serve_router};
+use serde_json::json;
+
+#[tokio::test]
+async fn readiness_reports_each_component_and_fails_while_shutting_down() {
+    let lobby = lobby::setup(Arc::new(EchoHandler), common::config()).await.unwrap();
+    let addr = serve_router(lobby.router).await;
+
+    let (head, body) = request(&addr, "GET", "/healthz", "", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&body).unwrap(), json!({ "status": "ok", "components": {} }));
+
+    let (head, body) = request(&addr, "GET", "/readyz", "", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+        json!({
+            "status": "ok",
+            "components": {
+                "accepting": { "status": "ok" },
+                "message_sender": { "status": "ok" },
+                "room_repository": { "status": "ok" },
+            },
+        })
+    );
+
+    lobby.shutdown.shutdown().await.unwrap();
+    let (head, body) = request(&addr, "GET", "/readyz", "", "").await;
+    assert!(head.starts_with("HTTP/1.1 503"), "{head}");
+    let report: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["status"], "failing");
+    assert_eq!(report["components"]["accepting"], json!({ "status": "failing", "error": "shutting down" }));
+    assert_eq!(report["components"]["message_sender"], json!({ "status": "ok" }));
+
+    // still alive, just not ready
+    let (head, _) = request(&addr, "GET", "/healthz", "", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+}
+
+#[tokio::test]
+async fn lobbies_are_checked_together() {
+    let chat = lobby::setup(Arc::new(EchoHandler), lobby::config::LobbyConfig { name: "chat".to_string(), ..common::config() })
+        .await
+        .unwrap();
+    let echo = lobby::setup(Arc::new(EchoHandler), lobby::config::LobbyConfig { name: "echo".to_string(), ..common::config() })
+        .await
+        .unwrap();
+    echo.shutdown.shutdown().await.unwrap();
+
+    let report = lobby::health::check_all(&[chat.health, echo.health]).await;
+    let report = serde_json::to_value(&report).unwrap();
+    assert_eq!(report["status"], "failing");
+    assert_eq!(report["components"]["chat.accepting"]["status"], "ok");
+    assert_eq!(report["components"]["echo.accepting"]["status"], "failing");
+    assert_eq!(report["components"].as_object().unwrap().len(), 6);
+}
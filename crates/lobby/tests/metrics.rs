@@ -0,0 +1,49 @@
+mod common;
+
+use common::{Outbound, create_room, join, next_outbound, request, serve};
+use futures_util::SinkExt;
+use lobby::config::LobbyConfig;
+use lobby::domain::Participant;
+use tokio_tungstenite::tungstenite::Message;
+
+/// The value of the sample `name`, which must be the only one with that name.
+fn sample(metrics: &str, name: &str) -> f64 {
+    let prefix = format!("{name}{{handler=\"echo\"");
+    let mut samples = metrics.lines().filter(|line| line.starts_with(&prefix));
+    let line = samples.next().unwrap_or_else(|| panic!("no sample for {name} in:\n{metrics}"));
+    assert!(samples.next().is_none(), "more than one sample for {name}");
+    line.rsplit(' ').next().unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn metrics_track_rooms_sockets_and_messages() {
+    let config = LobbyConfig { name: "echo".to_string(), ..common::config() };
+    let addr = serve(config).await;
+
+    let alice = Participant::new_v4();
+    let room = create_room(&addr, alice, 2).await;
+    let mut ws = join(&addr, room.id, alice).await;
+    ws.send(Message::text(r#""WhoAmI""#)).await.unwrap();
+    assert!(matches!(next_outbound(&mut ws).await, Outbound::YouAre { .. }));
+
+    let (head, body) = request(&addr, "GET", "/metrics", "", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    assert!(head.to_lowercase().contains("content-type: text/plain; version=0.0.4"), "{head}");
+    assert_eq!(sample(&body, "lobby_rooms_open"), 1.0);
+    assert_eq!(sample(&body, "lobby_participants_connected"), 1.0);
+    assert_eq!(sample(&body, "lobby_messages_in_total"), 1.0);
+    assert_eq!(sample(&body, "lobby_messages_out_total"), 1.0);
+    assert_eq!(sample(&body, "lobby_handler_duration_seconds_count"), 1.0);
+    assert_eq!(sample(&body, "lobby_send_duration_seconds_count"), 1.0);
+    assert_eq!(sample(&body, "lobby_actor_queue_depth"), 0.0);
+
+    ws.close(None).await.unwrap();
+    for _ in 0..50 {
+        let (_, body) = request(&addr, "GET", "/metrics", "", "").await;
+        if sample(&body, "lobby_participants_connected") == 0.0 {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("participant still counted as connected after closing the socket");
+}
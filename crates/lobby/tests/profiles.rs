@@ -0,0 +1,69 @@
+mod common;
+
+use common::{Outbound, cookie, create_room, join, next_outbound, request, serve};
+use futures_util::SinkExt;
+use lobby::domain::Participant;
+use serde_json::json;
+use tokio_tungstenite::tungstenite::Message;
+
+async fn put_profile(addr: &str, participant: Participant, profile: serde_json::Value) -> (String, String) {
+    request(addr, "PUT", "/me", &cookie(participant), &profile.to_string()).await
+}
+
+#[tokio::test]
+async fn profiles_can_be_set_and_looked_up() {
+    let addr = serve(common::config()).await;
+    let alice = Participant::new_v4();
+    let profile = json!({ "display_name": "  Alice ", "avatar": { "color": "#ff8800", "emoji": "🦊" }, "locale": "en-GB" });
+    let (head, _) = put_profile(&addr, alice, profile).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+
+    let (head, body) = request(&addr, "GET", &format!("/participants/{alice}"), "", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+        json!({ "display_name": "Alice", "avatar": { "color": "#ff8800", "emoji": "🦊" }, "locale": "en-GB" })
+    );
+
+    let (head, _) = request(&addr, "GET", &format!("/participants/{}", Participant::new_v4()), "", "").await;
+    assert!(head.starts_with("HTTP/1.1 404"), "{head}");
+}
+
+#[tokio::test]
+async fn invalid_profiles_are_rejected() {
+    let addr = serve(common::config()).await;
+    let alice = Participant::new_v4();
+    for (profile, error) in [
+        (json!({ "display_name": " " }), "display name must be 1 to 32 characters"),
+        (json!({ "display_name": "Alice", "avatar": { "color": "orange" } }), "avatar color must look like #rrggbb"),
+        (json!({ "display_name": "Alice", "locale": "english please" }), "locale must be a BCP 47 language tag"),
+    ] {
+        let (head, body) = put_profile(&addr, alice, profile).await;
+        assert!(head.starts_with("HTTP/1.1 400"), "{head}");
+        assert_eq!(body, error);
+    }
+}
+
+#[tokio::test]
+async fn rooms_and_handlers_see_resolved_profiles() {
+    let addr = serve(common::config()).await;
+    let alice = Participant::new_v4();
+    let bob = Participant::new_v4();
+    put_profile(&addr, alice, json!({ "display_name": "Alice" })).await;
+
+    let room = create_room(&addr, alice, 2).await;
+    let mut alice_ws = join(&addr, room.id, alice).await;
+    let mut bob_ws = join(&addr, room.id, bob).await;
+
+    let (_, body) = request(&addr, "GET", "/rooms", "", "").await;
+    let rooms: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(rooms[0]["profiles"], json!({ alice.to_string(): { "display_name": "Alice", "avatar": { "color": null, "emoji": null }, "locale": null } }));
+
+    alice_ws.send(Message::text(r#""WhoAmI""#)).await.unwrap();
+    assert_eq!(next_outbound(&mut alice_ws).await, Outbound::YouAre { display_name: "Alice".to_string() });
+    bob_ws.send(Message::text(r#""WhoAmI""#)).await.unwrap();
+    assert_eq!(
+        next_outbound(&mut bob_ws).await,
+        Outbound::YouAre { display_name: bob.simple().to_string()[..8].to_string() }
+    );
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::{Outbound, cookie, create_room, join, next_message, next_outbound, request};
+use futures_util::SinkExt;
+use lobby::config::{LobbyConfig, RateLimitConfig, TokenBucketConfig};
+use lobby::domain::Participant;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+const CREATE_ROOM: &str = r#"{"name":"test","capacity":2}"#;
+const SLOW: TokenBucketConfig = TokenBucketConfig { burst: 2, per_second: 0.001 };
+
+async fn serve(rate_limits: RateLimitConfig) -> String {
+    common::serve_with_connect_info(LobbyConfig { rate_limits, ..common::config() }).await
+}
+
+#[tokio::test]
+async fn room_creation_is_limited_per_participant() {
+    let addr = serve(RateLimitConfig { room_creation: SLOW, ..RateLimitConfig::default() }).await;
+    let alice = cookie(Participant::new_v4());
+    for _ in 0..2 {
+        let (head, _) = request(&addr, "POST", "/rooms", &alice, CREATE_ROOM).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    }
+    let (head, body) = request(&addr, "POST", "/rooms", &alice, CREATE_ROOM).await;
+    assert!(head.starts_with("HTTP/1.1 429"), "{head}");
+    assert!(head.to_lowercase().contains("retry-after: 1000"), "{head}");
+    assert_eq!(body, "too many requests, retry in 1000s");
+}
+
+#[tokio::test]
+async fn room_creation_is_limited_per_ip_address() {
+    let room_creation = TokenBucketConfig { burst: 3, per_second: 0.001 };
+    let addr = serve(RateLimitConfig { room_creation, ..RateLimitConfig::default() }).await;
+    for _ in 0..3 {
+        let (head, _) = request(&addr, "POST", "/rooms", &cookie(Participant::new_v4()), CREATE_ROOM).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    }
+    let (head, _) = request(&addr, "POST", "/rooms", &cookie(Participant::new_v4()), CREATE_ROOM).await;
+    assert!(head.starts_with("HTTP/1.1 429"), "{head}");
+}
+
+#[tokio::test]
+async fn dropping_the_cookie_does_not_dodge_the_room_creation_limit() {
+    let addr = serve(RateLimitConfig { room_creation: SLOW, ..RateLimitConfig::default() }).await;
+    for _ in 0..2 {
+        let (head, _) = request(&addr, "POST", "/rooms", "", CREATE_ROOM).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    }
+    let (head, _) = request(&addr, "POST", "/rooms", "", CREATE_ROOM).await;
+    assert!(head.starts_with("HTTP/1.1 429"), "{head}");
+}
+
+#[tokio::test]
+async fn flooding_participants_are_told_to_slow_down_then_kicked() {
+    let addr = serve(RateLimitConfig { messages: SLOW, kick_after: Some(2), ..RateLimitConfig::default() }).await;
+    let alice = Participant::new_v4();
+    let room = create_room(&addr, alice, 2).await;
+    let mut ws = join(&addr, room.id, alice).await;
+    let shout = Message::text(r#"{"Shout":{"content":"hi"}}"#);
+
+    for _ in 0..2 {
+        ws.send(shout.clone()).await.unwrap();
+        assert_eq!(next_outbound(&mut ws).await, Outbound::Heard { from: alice, content: "hi".to_string() });
+    }
+    ws.send(shout.clone()).await.unwrap();
+    let notice: serde_json::Value = serde_json::from_str(next_message(&mut ws).await.to_text().unwrap()).unwrap();
+    assert!(notice["RateLimited"]["retry_after_ms"].as_u64().unwrap() > 0, "{notice}");
+
+    ws.send(shout).await.unwrap();
+    let notice: serde_json::Value = serde_json::from_str(next_message(&mut ws).await.to_text().unwrap()).unwrap();
+    assert_eq!(notice, serde_json::json!({ "Kicked": { "reason": "rate limit exceeded" } }));
+    let Message::Close(Some(close_frame)) = next_message(&mut ws).await else {
+        panic!("expected a close frame");
+    };
+    assert_eq!(close_frame.code, CloseCode::Policy);
+
+    let (_, body) = request(&addr, "GET", "/rooms", "", "").await;
+    let rooms: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(rooms[0]["participants"], serde_json::json!([]));
+}
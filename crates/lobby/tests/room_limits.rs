@@ -0,0 +1,82 @@
+mod common;
+
+use common::{cookie, request};
+use lobby::config::{LobbyConfig, RateLimitConfig, RoomLimits, TokenBucketConfig};
+use lobby::domain::Participant;
+use serde_json::json;
+
+async fn serve(rooms: RoomLimits) -> String {
+    common::serve(LobbyConfig { rooms, ..common::config() }).await
+}
+
+async fn create(addr: &str, owner: Participant, body: serde_json::Value) -> (String, serde_json::Value) {
+    let (head, body) = request(addr, "POST", "/rooms", &cookie(owner), &body.to_string()).await;
+    (head, serde_json::from_str(&body).unwrap_or(serde_json::Value::Null))
+}
+
+#[tokio::test]
+async fn invalid_fields_are_reported_together() {
+    let addr = serve(RoomLimits { max_name_len: 8, ..RoomLimits::default() }).await;
+    let owner = Participant::new_v4();
+
+    let (head, body) = create(&addr, owner, json!({ "name": "far too long a name", "capacity": 0 })).await;
+    assert!(head.starts_with("HTTP/1.1 422"), "{head}");
+    assert_eq!(
+        body,
+        json!({
+            "message": "invalid room",
+            "errors": [
+                { "field": "name", "message": "must be 1 to 8 characters" },
+                { "field": "capacity", "message": "must be between 1 and 100" },
+            ],
+        })
+    );
+
+    let (head, body) = create(&addr, owner, json!({ "name": "a\u{7}b", "capacity": 1_000_000_000 })).await;
+    assert!(head.starts_with("HTTP/1.1 422"), "{head}");
+    assert_eq!(body["errors"][0], json!({ "field": "name", "message": "must not contain control characters" }));
+    assert_eq!(body["errors"][1]["field"], "capacity");
+
+    let (head, body) = create(&addr, owner, json!({ "name": "  ok  ", "capacity": 100 })).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    assert_eq!(body["name"], "ok");
+}
+
+#[tokio::test]
+async fn room_quotas_are_enforced() {
+    let addr = serve(RoomLimits { max_rooms: 3, max_rooms_per_owner: 2, ..RoomLimits::default() }).await;
+    let alice = Participant::new_v4();
+    let room = json!({ "name": "room", "capacity": 2 });
+
+    for _ in 0..2 {
+        let (head, _) = create(&addr, alice, room.clone()).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    }
+    let (head, body) = create(&addr, alice, room.clone()).await;
+    assert!(head.starts_with("HTTP/1.1 422"), "{head}");
+    assert_eq!(body["errors"], json!([{ "field": null, "message": "no participant may own more than 2 rooms" }]));
+
+    let (head, _) = create(&addr, Participant::new_v4(), room.clone()).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    let (head, body) = create(&addr, Participant::new_v4(), room).await;
+    assert!(head.starts_with("HTTP/1.1 422"), "{head}");
+    assert_eq!(body["errors"], json!([{ "field": null, "message": "no more than 3 rooms may be open" }]));
+}
+
+#[tokio::test]
+async fn concurrent_creations_stay_within_the_quotas() {
+    let rooms = RoomLimits { max_rooms: 3, max_rooms_per_owner: 2, ..RoomLimits::default() };
+    let room_creation = TokenBucketConfig { burst: 100, per_second: 1.0 };
+    let rate_limits = RateLimitConfig { room_creation, ..RateLimitConfig::default() };
+    let addr = common::serve(LobbyConfig { rooms, rate_limits, ..common::config() }).await;
+    let room = json!({ "name": "room", "capacity": 2 });
+
+    let alice = Participant::new_v4();
+    let created = futures_util::future::join_all((0..10).map(|_| create(&addr, alice, room.clone()))).await;
+    assert_eq!(created.iter().filter(|(head, _)| head.starts_with("HTTP/1.1 200")).count(), 2);
+
+    let created = futures_util::future::join_all((0..10).map(|_| create(&addr, Participant::new_v4(), room.clone()))).await;
+    assert_eq!(created.iter().filter(|(head, _)| head.starts_with("HTTP/1.1 200")).count(), 1);
+    let (_, body) = request(&addr, "GET", "/rooms", "", "").await;
+    assert_eq!(serde_json::from_str::<Vec<serde_json::Value>>(&body).unwrap().len(), 3);
+}
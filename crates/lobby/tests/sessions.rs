@@ -0,0 +1,98 @@
+mod common;
+
+use std::time::Duration;
+use common::{Outbound, cookie, next_outbound, request, request_with_headers, serve};
+use futures_util::SinkExt;
+use lobby::domain::{Participant, Room};
+use serde::Deserialize;
+use tokio_tungstenite::tungstenite::Message;
+
+const CREATE_ROOM: &str = r#"{"name":"test","capacity":2}"#;
+
+#[derive(Deserialize)]
+struct Session {
+    token: String,
+    participant: Participant,
+}
+
+async fn with_token(addr: &str, method: &str, path: &str, token: &str, body: &str) -> (String, String) {
+    request_with_headers(addr, method, path, &[("Authorization", &format!("Bearer {token}"))], body).await
+}
+
+async fn create_session(addr: &str, cookie: &str) -> Session {
+    let (head, body) = request(addr, "POST", "/sessions", cookie, "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    serde_json::from_str(&body).unwrap()
+}
+
+#[tokio::test]
+async fn bearer_tokens_identify_participants_without_cookies() {
+    let addr = serve(common::config()).await;
+    let session = create_session(&addr, "").await;
+
+    let (head, body) = with_token(&addr, "POST", "/rooms", &session.token, CREATE_ROOM).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    assert!(!head.to_lowercase().contains("set-cookie"), "{head}");
+    let room: Room = serde_json::from_str(&body).unwrap();
+    assert_eq!(room.created_by, session.participant);
+
+    let mut ws = {
+        let url = format!("ws://{addr}/rooms/{}?token={}", room.id, session.token);
+        tokio_tungstenite::connect_async(url).await.unwrap().0
+    };
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    ws.send(Message::text(r#"{"Shout":{"content":"hi"}}"#)).await.unwrap();
+    assert_eq!(
+        next_outbound(&mut ws).await,
+        Outbound::Heard { from: session.participant, content: "hi".to_string() }
+    );
+
+    let (head, _) = with_token(&addr, "DELETE", &format!("/rooms/{}", room.id), &session.token, "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+}
+
+#[tokio::test]
+async fn sessions_are_bound_to_the_cookie_participant() {
+    let addr = serve(common::config()).await;
+    let participant = Participant::new_v4();
+    let session = create_session(&addr, &cookie(participant)).await;
+    assert_eq!(session.participant, participant);
+}
+
+#[tokio::test]
+async fn refreshed_and_revoked_tokens_are_rejected() {
+    let addr = serve(common::config()).await;
+    let session = create_session(&addr, "").await;
+
+    let (head, body) = with_token(&addr, "POST", "/sessions/refresh", &session.token, "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    let refreshed: Session = serde_json::from_str(&body).unwrap();
+    assert_eq!(refreshed.participant, session.participant);
+    assert_ne!(refreshed.token, session.token);
+
+    let (head, body) = with_token(&addr, "POST", "/rooms", &session.token, CREATE_ROOM).await;
+    assert!(head.starts_with("HTTP/1.1 401"), "{head}");
+    assert_eq!(body, "bearer token revoked");
+
+    let (head, _) = with_token(&addr, "DELETE", "/sessions", &refreshed.token, "").await;
+    assert!(head.starts_with("HTTP/1.1 204"), "{head}");
+    let (head, _) = with_token(&addr, "POST", "/rooms", &refreshed.token, CREATE_ROOM).await;
+    assert!(head.starts_with("HTTP/1.1 401"), "{head}");
+}
+
+#[tokio::test]
+async fn forged_tokens_are_rejected() {
+    let addr = serve(common::config()).await;
+    let session = create_session(&addr, "").await;
+    let forged = session
+        .token
+        .replacen(&session.participant.to_string(), &Participant::new_v4().to_string(), 1);
+
+    let (head, body) = with_token(&addr, "POST", "/rooms", &forged, CREATE_ROOM).await;
+    assert!(head.starts_with("HTTP/1.1 401"), "{head}");
+    assert!(head.to_lowercase().contains("www-authenticate: bearer"), "{head}");
+    assert_eq!(body, "invalid bearer token");
+
+    let (head, _) = request_with_headers(&addr, "POST", "/rooms", &[("Authorization", "Basic Zm9vOmJhcg==")], CREATE_ROOM).await;
+    assert!(head.starts_with("HTTP/1.1 401"), "{head}");
+}
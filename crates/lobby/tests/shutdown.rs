@@ -0,0 +1,62 @@
+mod common;
+
+use std::sync::Arc;
+use common::{EchoHandler, create_room, join, next_message, request, serve_router};
+use lobby::config::{LobbyConfig, ShutdownConfig};
+use lobby::domain::{Participant, Room};
+use tokio_tungstenite::tungstenite::{Error, Message};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+#[tokio::test]
+async fn shutdown_notifies_participants_closes_sockets_and_persists_rooms() {
+    let snapshot = std::env::temp_dir().join(format!("lobby-rooms-{}.json", Participant::new_v4()));
+    let config = LobbyConfig {
+        rooms_snapshot: Some(snapshot.clone()),
+        shutdown: ShutdownConfig {
+            reconnect_url: Some("wss://elsewhere.example".to_string()),
+            ..ShutdownConfig::default()
+        },
+        ..common::config()
+    };
+    let lobby = lobby::setup(Arc::new(EchoHandler), config).await.unwrap();
+    let addr = serve_router(lobby.router).await;
+
+    let owner = Participant::new_v4();
+    let room = create_room(&addr, owner, 2).await;
+    let mut ws = join(&addr, room.id, owner).await;
+
+    lobby.shutdown.shutdown().await.unwrap();
+
+    let notice: serde_json::Value = serde_json::from_str(next_message(&mut ws).await.to_text().unwrap()).unwrap();
+    assert_eq!(
+        notice,
+        serde_json::json!({ "ServerGoingAway": { "reconnect_after_ms": 1000, "reconnect_url": "wss://elsewhere.example" } })
+    );
+    let Message::Close(Some(close_frame)) = next_message(&mut ws).await else {
+        panic!("expected a close frame");
+    };
+    assert_eq!(close_frame.code, CloseCode::Away);
+
+    let (head, _) = request(&addr, "POST", "/rooms", "", r#"{"name":"late","capacity":2}"#).await;
+    assert!(head.starts_with("HTTP/1.1 503"), "{head}");
+    let Err(Error::Http(response)) = tokio_tungstenite::connect_async(format!("ws://{addr}/rooms/{}", room.id)).await else {
+        panic!("expected the join to be rejected");
+    };
+    assert_eq!(response.status(), 503);
+
+    let persisted: Vec<Room> = serde_json::from_slice(&std::fs::read(&snapshot).unwrap()).unwrap();
+    std::fs::remove_file(&snapshot).unwrap();
+    assert_eq!(persisted.len(), 1);
+    assert_eq!(persisted[0].id, room.id);
+    assert!(persisted[0].participants.is_empty());
+
+    // a restarted lobby picks the rooms up again
+    let config = LobbyConfig { rooms_snapshot: Some(snapshot.clone()), ..common::config() };
+    std::fs::write(&snapshot, serde_json::to_vec(&persisted).unwrap()).unwrap();
+    let restarted = lobby::setup(Arc::new(EchoHandler), config).await.unwrap();
+    std::fs::remove_file(&snapshot).unwrap();
+    let addr = serve_router(restarted.router).await;
+    let (_, body) = request(&addr, "GET", "/rooms", "", "").await;
+    let rooms: Vec<Room> = serde_json::from_str(&body).unwrap();
+    assert_eq!(rooms.len(), 1);
+}
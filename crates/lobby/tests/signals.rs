@@ -0,0 +1,76 @@
+mod common;
+
+use std::time::Duration;
+use common::{Outbound, Socket, create_room, join, next_message, next_outbound};
+use futures_util::SinkExt;
+use lobby::config::{LobbyConfig, SignalConfig};
+use lobby::domain::Participant;
+use lobby::{LobbyNotice, SignalKind};
+use tokio_tungstenite::tungstenite::Message;
+
+async fn serve(ttl_ms: u64) -> String {
+    let config = LobbyConfig { signals: SignalConfig { ttl_ms }, ..common::config() };
+    common::serve(config).await
+}
+
+async fn signal(ws: &mut Socket, kind: &str, active: bool) {
+    let request = format!(r#"{{"Signal":{{"kind":"{kind}","active":{active}}}}}"#);
+    ws.send(Message::text(request)).await.unwrap();
+}
+
+async fn next_notice(ws: &mut Socket) -> LobbyNotice {
+    serde_json::from_str(next_message(ws).await.to_text().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn signals_reach_the_rest_of_the_room_once_until_they_expire() {
+    let addr = serve(300).await;
+    let (alice, bob) = (Participant::new_v4(), Participant::new_v4());
+    let room = create_room(&addr, alice, 2).await;
+    let mut alice_ws = join(&addr, room.id, alice).await;
+    let mut bob_ws = join(&addr, room.id, bob).await;
+
+    signal(&mut alice_ws, "typing", true).await;
+    signal(&mut alice_ws, "typing", true).await;
+
+    let typing = LobbyNotice::Signal { from: alice, kind: SignalKind::Typing, active: true };
+    assert_eq!(next_notice(&mut bob_ws).await, typing);
+    let stopped = LobbyNotice::Signal { from: alice, kind: SignalKind::Typing, active: false };
+    assert_eq!(next_notice(&mut bob_ws).await, stopped);
+    // the sender hears neither their own signal nor its expiry, and the handler never sees it
+    alice_ws.send(Message::text(r#""WhoAmI""#)).await.unwrap();
+    assert!(matches!(next_outbound(&mut alice_ws).await, Outbound::YouAre { .. }));
+}
+
+#[tokio::test]
+async fn leaving_ends_the_participants_signals() {
+    let addr = serve(60_000).await;
+    let (alice, bob) = (Participant::new_v4(), Participant::new_v4());
+    let room = create_room(&addr, alice, 2).await;
+    let mut alice_ws = join(&addr, room.id, alice).await;
+    let mut bob_ws = join(&addr, room.id, bob).await;
+    signal(&mut bob_ws, "buffering", true).await;
+    let buffering = LobbyNotice::Signal { from: bob, kind: SignalKind::Buffering, active: true };
+    assert_eq!(next_notice(&mut alice_ws).await, buffering);
+
+    bob_ws.close(None).await.unwrap();
+
+    let notice = tokio::time::timeout(Duration::from_secs(5), next_notice(&mut alice_ws)).await.unwrap();
+    assert_eq!(notice, LobbyNotice::Signal { from: bob, kind: SignalKind::Buffering, active: false });
+}
+
+#[tokio::test]
+async fn joining_participants_learn_the_active_signals() {
+    let addr = serve(60_000).await;
+    let (alice, bob, carol) = (Participant::new_v4(), Participant::new_v4(), Participant::new_v4());
+    let room = create_room(&addr, alice, 3).await;
+    let mut alice_ws = join(&addr, room.id, alice).await;
+    let mut bob_ws = join(&addr, room.id, bob).await;
+    signal(&mut alice_ws, "buffering", true).await;
+    let buffering = LobbyNotice::Signal { from: alice, kind: SignalKind::Buffering, active: true };
+    assert_eq!(next_notice(&mut bob_ws).await, buffering);
+
+    let mut carol_ws = join(&addr, room.id, carol).await;
+
+    assert_eq!(next_notice(&mut carol_ws).await, buffering);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::sync::Arc;
+use common::{EchoHandler, Inbound, Outbound};
+use lobby::domain::{Profile, RoomError};
+use lobby::testing::TestRoom;
+use uuid::Uuid;
+
+fn shout(content: &str) -> Inbound {
+    Inbound::Shout { content: content.to_string() }
+}
+
+#[tokio::test]
+async fn responses_are_recorded_per_participant() {
+    let room = TestRoom::new(Arc::new(EchoHandler)).await;
+    let alice = room.join_with_profile(Profile { display_name: "alice".to_string(), ..Profile::default() }).await.unwrap();
+    let bob = room.join().await.unwrap();
+
+    room.send(alice, shout("hi")).await.unwrap();
+    room.send(bob, Inbound::Whisper { to: alice, content: "psst".to_string() }).await.unwrap();
+    room.send(alice, Inbound::WhoAmI).await.unwrap();
+
+    assert_eq!(
+        room.take(alice),
+        [
+            Outbound::Heard { from: alice, content: "hi".to_string() },
+            Outbound::Heard { from: bob, content: "psst".to_string() },
+            Outbound::YouAre { display_name: "alice".to_string() },
+        ]
+    );
+    assert_eq!(room.take(bob), [Outbound::Heard { from: alice, content: "hi".to_string() }]);
+    assert!(room.take(alice).is_empty());
+}
+
+#[tokio::test]
+async fn leaving_and_full_rooms_are_enforced() {
+    let room = TestRoom::with_capacity(Arc::new(EchoHandler), 2).await;
+    let alice = room.join().await.unwrap();
+    let bob = room.join().await.unwrap();
+    assert!(matches!(room.join().await, Err(RoomError::RoomFull { .. })));
+
+    room.leave(bob).await;
+    room.send(alice, shout("anyone?")).await.unwrap();
+
+    assert!(room.take(bob).is_empty());
+    assert!(matches!(room.send(bob, shout("me!")).await, Err(RoomError::NotParticipant { .. })));
+    room.join_as(Uuid::new_v4()).await.unwrap();
+}
+
+#[tokio::test]
+async fn disconnected_participants_are_dropped_once_a_message_fails_to_reach_them() {
+    let room = TestRoom::new(Arc::new(EchoHandler)).await;
+    let alice = room.join().await.unwrap();
+    let bob = room.join().await.unwrap();
+
+    room.disconnect(bob);
+    assert!(room.room().await.is_participant(bob));
+    room.send(alice, shout("hi")).await.unwrap();
+
+    assert!(!room.room().await.is_participant(bob));
+    assert!(room.take(bob).is_empty());
+    assert_eq!(room.take(alice).len(), 1);
+}
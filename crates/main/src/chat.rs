@@ -0,0 +1,818 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use lobby::domain::{MessageHandler, MessageResponse, Participant, Profile, RoomContext, RoomId};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+use crate::chat::moderation::{Draft, Moderation, ModerationConfig, Moderator, Rejection};
+
+pub mod moderation;
+
+/// History is kept in this process only; reactions skip moderation.
+pub struct ChatMessageHandler {
+    config: ChatConfig,
+    history: Mutex<HashMap<RoomId, RoomHistory>>,
+    moderation: Moderation,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChatConfig {
+    pub history_len: usize,
+    /// How many of the latest messages a participant is sent on joining.
+    pub replay_on_join: usize,
+    pub moderation: ModerationConfig,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self { history_len: 200, replay_on_join: 50, moderation: ModerationConfig::default() }
+    }
+}
+
+/// Unique across servers sharing rooms, and ordered by when this server sent the message.
+pub type MessageId = Uuid;
+
+/// The most messages a single `FetchHistory` returns.
+pub const MAX_HISTORY_PAGE: usize = 100;
+
+/// The most different emoji a single message can be reacted with.
+pub const MAX_REACTIONS: usize = 20;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum ChatInbound {
+    SendPrivateMessage {
+        to: Participant,
+        content: String,
+    },
+    /// `@display name` in the content notifies that participant with `Mentioned`.
+    SendPublicMessage {
+        content: String,
+    },
+    ListParticipants,
+    /// `limit` is capped at [`MAX_HISTORY_PAGE`].
+    FetchHistory {
+        #[serde(default)]
+        before: Option<MessageId>,
+        limit: usize,
+    },
+    /// Only participants mentioned for the first time are notified.
+    EditMessage {
+        id: MessageId,
+        content: String,
+    },
+    /// Allowed to the message's author and the room's owner.
+    DeleteMessage {
+        id: MessageId,
+    },
+    /// Reacting again with the same emoji takes the reaction back.
+    ToggleReaction {
+        id: MessageId,
+        emoji: String,
+    },
+    /// What the room is playing now; `position_ms` is where it was when sent.
+    SetPlayback {
+        track: String,
+        position_ms: u64,
+        duration_ms: u64,
+        playing: bool,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum ChatOutbound {
+    PrivateMessage {
+        id: MessageId,
+        from: Participant,
+        from_name: String,
+        content: String,
+        sent_at: DateTime<Utc>,
+    },
+    PublicMessage {
+        id: MessageId,
+        from: Participant,
+        from_name: String,
+        content: String,
+        mentions: Vec<Participant>,
+        sent_at: DateTime<Utc>,
+    },
+    ListOfParticipants {
+        participants: Vec<ChatParticipant>,
+    },
+    History {
+        messages: Vec<ChatHistoryEntry>,
+        has_more: bool,
+    },
+    MessageEdited {
+        id: MessageId,
+        content: String,
+        mentions: Vec<Participant>,
+        edited_at: DateTime<Utc>,
+    },
+    MessageDeleted {
+        id: MessageId,
+    },
+    /// Every reaction the message has now, by emoji.
+    ReactionsChanged {
+        id: MessageId,
+        reactions: BTreeMap<String, Vec<Participant>>,
+    },
+    /// Sent to a participant a public message mentions, besides the message itself.
+    Mentioned {
+        id: MessageId,
+        from: Participant,
+        from_name: String,
+        content: String,
+    },
+    /// Sent to the participant whose message or edit moderation stopped.
+    MessageRejected {
+        /// The message they tried to edit, if it was an edit.
+        edit_of: Option<MessageId>,
+        reason: Rejection,
+    },
+    /// Also sent to joiners while something is playing.
+    PlaybackChanged {
+        playback: Playback,
+    },
+    /// Sent to the participant whose private message, edit, deletion, reaction or playback couldn't be applied.
+    Refused {
+        reason: ChatError,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ChatParticipant {
+    pub id: Participant,
+    pub display_name: String,
+    pub profile: Option<Profile>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ChatHistoryEntry {
+    pub id: MessageId,
+    pub from: Participant,
+    /// As the sender was shown when the message was sent.
+    pub from_name: String,
+    pub content: String,
+    pub mentions: Vec<Participant>,
+    pub sent_at: DateTime<Utc>,
+    pub edited_at: Option<DateTime<Utc>>,
+    /// Who reacted with each emoji, in the order they did.
+    pub reactions: BTreeMap<String, Vec<Participant>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Playback {
+    pub track: String,
+    /// Where playback was at `set_at`.
+    pub position_ms: u64,
+    pub duration_ms: u64,
+    pub playing: bool,
+    pub set_by: Participant,
+    pub set_at: DateTime<Utc>,
+}
+
+/// Why a chat action was refused, told to the participant asking for it.
+#[derive(Clone, Debug, PartialEq, Error, Serialize, Deserialize, JsonSchema)]
+pub enum ChatError {
+    #[error("{participant} is not in the room")]
+    NotInRoom { participant: Participant },
+    #[error("no message {id} in the history")]
+    MessageNotFound { id: MessageId },
+    #[error("message {id} can't be changed by {participant}")]
+    NotAllowed { id: MessageId, participant: Participant },
+    #[error("reaction must be 1 to 16 characters without whitespace")]
+    InvalidReaction,
+    #[error("message {id} already has {MAX_REACTIONS} different reactions")]
+    TooManyReactions { id: MessageId },
+    #[error("playback position {position_ms}ms is past the track's {duration_ms}ms")]
+    InvalidPlayback { position_ms: u64, duration_ms: u64 },
+}
+
+#[derive(Default)]
+struct RoomHistory {
+    messages: VecDeque<ChatHistoryEntry>,
+    playback: Option<Playback>,
+}
+
+impl RoomHistory {
+    fn push(&mut self, entry: ChatHistoryEntry, len: usize) {
+        if len == 0 {
+            return;
+        }
+        if self.messages.len() == len {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(entry);
+    }
+
+    fn get_mut(&mut self, id: MessageId) -> Result<&mut ChatHistoryEntry, ChatError> {
+        let index = self.index(id).ok_or(ChatError::MessageNotFound { id })?;
+        Ok(&mut self.messages[index])
+    }
+
+    fn index(&self, id: MessageId) -> Option<usize> {
+        let index = self.messages.partition_point(|msg| msg.id < id);
+        self.messages.get(index).is_some_and(|msg| msg.id == id).then_some(index)
+    }
+
+    /// Up to `limit` messages sent before `before`, oldest first, and whether there are older ones.
+    fn page(&self, before: Option<MessageId>, limit: usize) -> (Vec<ChatHistoryEntry>, bool) {
+        let end = match before {
+            Some(before) => self.messages.partition_point(|msg| msg.id < before),
+            None => self.messages.len(),
+        };
+        let start = end.saturating_sub(limit);
+        (self.messages.range(start..end).cloned().collect(), start > 0)
+    }
+}
+
+impl ChatMessageHandler {
+    pub fn new(config: ChatConfig) -> Self {
+        let moderation = Moderation::new(&config.moderation);
+        Self { config, history: Mutex::new(HashMap::new()), moderation }
+    }
+
+    /// Adds a moderation step after the built-in ones.
+    pub fn with_moderator(mut self, moderator: impl Moderator + 'static) -> Self {
+        self.moderation.push(moderator);
+        self
+    }
+
+    fn moderate(&self, room_id: RoomId, from: Participant, content: &str) -> Result<String, Rejection> {
+        self.moderation.check(&Draft { room_id, from, content, at: Instant::now() })
+    }
+
+    /// What `msg` calls for, or why it was refused.
+    fn respond(&self, room: &RoomContext<'_>, from: Participant, msg: ChatInbound) -> Result<MessageResponse<ChatOutbound>, ChatError> {
+        let from_name = room.display_name(from);
+        match msg {
+            ChatInbound::SendPrivateMessage { to, content } => {
+                if !room.is_participant(to) {
+                    return Err(ChatError::NotInRoom { participant: to });
+                }
+                let content = match self.moderate(room.id, from, &content) {
+                    Ok(content) => content,
+                    Err(reason) => return Ok(rejected(from, None, reason)),
+                };
+                let msg = ChatOutbound::PrivateMessage { id: Uuid::now_v7(), from, from_name, content, sent_at: Utc::now() };
+                Ok(MessageResponse::Unicast { to, msg })
+            }
+            ChatInbound::SendPublicMessage { content } => {
+                let content = match self.moderate(room.id, from, &content) {
+                    Ok(content) => content,
+                    Err(reason) => return Ok(rejected(from, None, reason)),
+                };
+                let mentions = mentions(room, from, &content);
+                let entry = self.with_history(room.id, |history| {
+                    let entry = ChatHistoryEntry {
+                        id: Uuid::now_v7(),
+                        from,
+                        from_name,
+                        content,
+                        mentions,
+                        sent_at: Utc::now(),
+                        edited_at: None,
+                        reactions: BTreeMap::new(),
+                    };
+                    history.push(entry.clone(), self.config.history_len);
+                    entry
+                });
+                let msg = ChatOutbound::PublicMessage {
+                    id: entry.id,
+                    from,
+                    from_name: entry.from_name.clone(),
+                    content: entry.content.clone(),
+                    mentions: entry.mentions.clone(),
+                    sent_at: entry.sent_at,
+                };
+                Ok(with_mentions(msg, entry.mentions.clone(), &entry))
+            }
+            ChatInbound::ListParticipants => {
+                let participants = room
+                    .participants
+                    .iter()
+                    .map(|id| ChatParticipant {
+                        id: *id,
+                        display_name: room.display_name(*id),
+                        profile: room.profiles.get(id).cloned(),
+                    })
+                    .collect();
+                Ok(MessageResponse::Unicast { to: from, msg: ChatOutbound::ListOfParticipants { participants } })
+            }
+            ChatInbound::FetchHistory { before, limit } => {
+                let (messages, has_more) = self.page(room.id, before, limit.min(MAX_HISTORY_PAGE));
+                Ok(MessageResponse::Unicast { to: from, msg: ChatOutbound::History { messages, has_more } })
+            }
+            ChatInbound::EditMessage { id, content } => {
+                // only edits that could be applied count towards the sender's flood limits
+                self.with_history(room.id, |history| match history.get_mut(id)?.from == from {
+                    true => Ok(()),
+                    false => Err(ChatError::NotAllowed { id, participant: from }),
+                })?;
+                let content = match self.moderate(room.id, from, &content) {
+                    Ok(content) => content,
+                    Err(reason) => return Ok(rejected(from, Some(id), reason)),
+                };
+                let mentions = mentions(room, from, &content);
+                let edited_at = Utc::now();
+                let (entry, newly_mentioned) = self.with_history(room.id, |history| {
+                    let entry = history.get_mut(id)?;
+                    if entry.from != from {
+                        return Err(ChatError::NotAllowed { id, participant: from });
+                    }
+                    let newly_mentioned = mentions.iter().copied().filter(|p| !entry.mentions.contains(p)).collect();
+                    entry.content = content;
+                    entry.mentions = mentions;
+                    entry.edited_at = Some(edited_at);
+                    Ok((entry.clone(), newly_mentioned))
+                })?;
+                let msg = ChatOutbound::MessageEdited {
+                    id,
+                    content: entry.content.clone(),
+                    mentions: entry.mentions.clone(),
+                    edited_at,
+                };
+                Ok(with_mentions(msg, newly_mentioned, &entry))
+            }
+            ChatInbound::DeleteMessage { id } => {
+                self.with_history(room.id, |history| {
+                    let index = history.index(id).ok_or(ChatError::MessageNotFound { id })?;
+                    if history.messages[index].from != from && room.created_by != from {
+                        return Err(ChatError::NotAllowed { id, participant: from });
+                    }
+                    history.messages.remove(index);
+                    Ok(())
+                })?;
+                Ok(MessageResponse::Broadcast { msg: ChatOutbound::MessageDeleted { id } })
+            }
+            ChatInbound::ToggleReaction { id, emoji } => {
+                let len = emoji.chars().count();
+                if len == 0 || len > 16 || emoji.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                    return Err(ChatError::InvalidReaction);
+                }
+                let reactions = self.with_history(room.id, |history| {
+                    let entry = history.get_mut(id)?;
+                    if !entry.reactions.contains_key(&emoji) && entry.reactions.len() >= MAX_REACTIONS {
+                        return Err(ChatError::TooManyReactions { id });
+                    }
+                    let reactors = entry.reactions.entry(emoji.clone()).or_default();
+                    match reactors.iter().position(|p| *p == from) {
+                        Some(at) => {
+                            reactors.remove(at);
+                        }
+                        None => reactors.push(from),
+                    }
+                    if reactors.is_empty() {
+                        entry.reactions.remove(&emoji);
+                    }
+                    Ok(entry.reactions.clone())
+                })?;
+                Ok(MessageResponse::Broadcast { msg: ChatOutbound::ReactionsChanged { id, reactions } })
+            }
+            ChatInbound::SetPlayback { track, position_ms, duration_ms, playing } => {
+                if position_ms > duration_ms {
+                    return Err(ChatError::InvalidPlayback { position_ms, duration_ms });
+                }
+                let playback = Playback { track, position_ms, duration_ms, playing, set_by: from, set_at: Utc::now() };
+                self.with_history(room.id, |history| history.playback = Some(playback.clone()));
+                Ok(MessageResponse::Broadcast { msg: ChatOutbound::PlaybackChanged { playback } })
+            }
+        }
+    }
+
+    fn with_history<T>(&self, room_id: RoomId, f: impl FnOnce(&mut RoomHistory) -> T) -> T {
+        let mut history = self.history.lock().unwrap_or_else(PoisonError::into_inner);
+        f(history.entry(room_id).or_default())
+    }
+
+    fn page(&self, room_id: RoomId, before: Option<MessageId>, limit: usize) -> (Vec<ChatHistoryEntry>, bool) {
+        let history = self.history.lock().unwrap_or_else(PoisonError::into_inner);
+        history.get(&room_id).map(|room| room.page(before, limit)).unwrap_or_default()
+    }
+}
+
+impl Default for ChatMessageHandler {
+    fn default() -> Self {
+        Self::new(ChatConfig::default())
+    }
+}
+
+/// Names several participants go by mention nobody.
+fn mentions(room: &RoomContext<'_>, from: Participant, content: &str) -> Vec<Participant> {
+    let others: Vec<_> = room
+        .participants
+        .iter()
+        .filter(|participant| **participant != from)
+        .map(|participant| (*participant, room.display_name(*participant)))
+        .collect();
+    others
+        .iter()
+        .filter(|(_, name)| others.iter().filter(|(_, other)| other == name).count() == 1)
+        .filter(|(_, name)| {
+            let handle = format!("@{name}");
+            content
+                .match_indices(&handle)
+                .any(|(at, _)| !content[at + handle.len()..].starts_with(char::is_alphanumeric))
+        })
+        .map(|(participant, _)| *participant)
+        .collect()
+}
+
+/// `msg` for everyone, and a `Mentioned` for each of `mentioned`.
+fn with_mentions(
+    msg: ChatOutbound,
+    mentioned: Vec<Participant>,
+    entry: &ChatHistoryEntry,
+) -> MessageResponse<ChatOutbound> {
+    let mut responses = vec![MessageResponse::Broadcast { msg }];
+    responses.extend(mentioned.into_iter().map(|to| MessageResponse::Unicast {
+        to,
+        msg: ChatOutbound::Mentioned {
+            id: entry.id,
+            from: entry.from,
+            from_name: entry.from_name.clone(),
+            content: entry.content.clone(),
+        },
+    }));
+    MessageResponse::Multiple { responses }
+}
+
+fn rejected(to: Participant, edit_of: Option<MessageId>, reason: Rejection) -> MessageResponse<ChatOutbound> {
+    MessageResponse::Unicast { to, msg: ChatOutbound::MessageRejected { edit_of, reason } }
+}
+
+#[async_trait]
+impl MessageHandler<ChatInbound> for ChatMessageHandler {
+    type Outbound = ChatOutbound;
+    type Err = ChatError;
+
+    async fn handle_message(&self, room: &RoomContext<'_>, from: Participant, msg: ChatInbound) -> Result<MessageResponse<Self::Outbound>, Self::Err> {
+        Ok(self.respond(room, from, msg).unwrap_or_else(|reason| MessageResponse::Unicast {
+            to: from,
+            msg: ChatOutbound::Refused { reason },
+        }))
+    }
+
+    /// The latest messages, unless nothing was said yet, and what is playing.
+    async fn on_join(&self, room: &RoomContext<'_>, _participant: Participant) -> Vec<Self::Outbound> {
+        let (messages, has_more) = self.page(room.id, None, self.config.replay_on_join);
+        let playback = self.with_history(room.id, |history| history.playback.clone());
+        let history = (!messages.is_empty()).then_some(ChatOutbound::History { messages, has_more });
+        history.into_iter().chain(playback.map(|playback| ChatOutbound::PlaybackChanged { playback })).collect()
+    }
+
+    /// Only the latest playback matters to a participant that fell behind.
+    fn coalesce_key(&self, msg: &Self::Outbound) -> Option<String> {
+        matches!(msg, ChatOutbound::PlaybackChanged { .. }).then(|| "playback".to_string())
+    }
+
+    async fn room_closed(&self, room_id: RoomId) {
+        self.history.lock().unwrap_or_else(PoisonError::into_inner).remove(&room_id);
+        self.moderation.room_closed(room_id);
+    }
+
+    async fn debug_state(&self, room: &RoomContext<'_>) -> serde_json::Value {
+        let history = self.history.lock().unwrap_or_else(PoisonError::into_inner);
+        match history.get(&room.id) {
+            Some(history) => serde_json::json!({ "history_len": history.messages.len() }),
+            None => serde_json::json!({ "history_len": 0 }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use lobby::testing::TestRoom;
+    use crate::chat::moderation::{FloodConfig, WordAction, WordlistConfig};
+
+    #[tokio::test]
+    async fn private_messages_only_reach_their_recipient() {
+        let room = TestRoom::new(Arc::new(ChatMessageHandler::default())).await;
+        let alice = room.join_with_profile(Profile { display_name: "alice".to_string(), ..Profile::default() }).await.unwrap();
+        let bob = room.join().await.unwrap();
+        let carol = room.join().await.unwrap();
+
+        room.send(alice, ChatInbound::SendPrivateMessage { to: bob, content: "psst".to_string() }).await.unwrap();
+
+        let received = room.take(bob);
+        assert!(matches!(
+            received.as_slice(),
+            [ChatOutbound::PrivateMessage { from, from_name, content, .. }]
+                if *from == alice && from_name == "alice" && content == "psst"
+        ));
+        assert!(room.take(alice).is_empty());
+        assert!(room.take(carol).is_empty());
+    }
+
+    #[tokio::test]
+    async fn private_messages_to_someone_outside_the_room_are_refused() {
+        let config = ChatConfig {
+            moderation: ModerationConfig { flood: FloodConfig { max_messages: 1, ..FloodConfig::default() }, ..ModerationConfig::default() },
+            ..ChatConfig::default()
+        };
+        let room = TestRoom::new(Arc::new(ChatMessageHandler::new(config))).await;
+        let (alice, bob) = (room.join().await.unwrap(), room.join().await.unwrap());
+        let stranger = Participant::new_v4();
+
+        room.send(alice, ChatInbound::SendPrivateMessage { to: stranger, content: "psst".to_string() }).await.unwrap();
+        room.send(alice, ChatInbound::SendPrivateMessage { to: bob, content: "psst".to_string() }).await.unwrap();
+
+        let not_in_room = ChatError::NotInRoom { participant: stranger };
+        assert_eq!(room.take(alice), [ChatOutbound::Refused { reason: not_in_room }]);
+        assert!(matches!(room.take(bob).as_slice(), [ChatOutbound::PrivateMessage { .. }]));
+    }
+
+    #[tokio::test]
+    async fn participants_are_listed_with_their_profiles() {
+        let room = TestRoom::new(Arc::new(ChatMessageHandler::default())).await;
+        let alice = room.join_with_profile(Profile { display_name: "alice".to_string(), ..Profile::default() }).await.unwrap();
+        let bob = room.join().await.unwrap();
+
+        room.send(bob, ChatInbound::ListParticipants).await.unwrap();
+
+        let received = room.take(bob);
+        let [ChatOutbound::ListOfParticipants { participants }] = received.as_slice() else {
+            panic!("expected the list of participants, got {received:?}");
+        };
+        let names: Vec<_> = participants.iter().map(|p| (p.id, p.display_name.as_str(), p.profile.is_some())).collect();
+        assert_eq!(names, [(alice, "alice", true), (bob, &bob.simple().to_string()[..8], false)]);
+    }
+
+    #[tokio::test]
+    async fn history_is_bounded_and_paged_backwards() {
+        let handler = ChatMessageHandler::new(ChatConfig { history_len: 3, replay_on_join: 2, ..ChatConfig::default() });
+        let room = TestRoom::new(Arc::new(handler)).await;
+        let alice = room.join().await.unwrap();
+        for n in 0..5 {
+            room.send(alice, ChatInbound::SendPublicMessage { content: n.to_string() }).await.unwrap();
+        }
+        let ids = public_ids(&room.take(alice));
+
+        room.send(alice, ChatInbound::FetchHistory { before: None, limit: 10 }).await.unwrap();
+        room.send(alice, ChatInbound::FetchHistory { before: Some(ids[4]), limit: 1 }).await.unwrap();
+        room.send(alice, ChatInbound::FetchHistory { before: Some(ids[3]), limit: 1 }).await.unwrap();
+
+        let pages: Vec<_> = room
+            .take(alice)
+            .into_iter()
+            .map(|msg| match msg {
+                ChatOutbound::History { messages, has_more } => (messages.iter().map(|m| m.id).collect::<Vec<_>>(), has_more),
+                msg => panic!("expected a page of history, got {msg:?}"),
+            })
+            .collect();
+        assert_eq!(pages, [(ids[2..].to_vec(), false), (vec![ids[3]], true), (vec![ids[2]], false)]);
+    }
+
+    #[tokio::test]
+    async fn joiners_are_sent_the_latest_messages() {
+        let handler = ChatMessageHandler::new(ChatConfig { history_len: 10, replay_on_join: 2, ..ChatConfig::default() });
+        let room = TestRoom::new(Arc::new(handler)).await;
+        let alice = room.join_with_profile(Profile { display_name: "alice".to_string(), ..Profile::default() }).await.unwrap();
+        assert!(room.take(alice).is_empty());
+        for content in ["one", "two", "three"] {
+            room.send(alice, ChatInbound::SendPublicMessage { content: content.to_string() }).await.unwrap();
+        }
+
+        let bob = room.join().await.unwrap();
+
+        let received = room.take(bob);
+        let [ChatOutbound::History { messages, has_more: true }] = received.as_slice() else {
+            panic!("expected the latest history, got {received:?}");
+        };
+        let said: Vec<_> = messages.iter().map(|m| (m.from_name.as_str(), m.content.as_str())).collect();
+        assert_eq!(said, [("alice", "two"), ("alice", "three")]);
+    }
+
+    fn public_ids(received: &[ChatOutbound]) -> Vec<MessageId> {
+        received
+            .iter()
+            .filter_map(|msg| match msg {
+                ChatOutbound::PublicMessage { id, .. } => Some(*id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    async fn named(room: &TestRoom<ChatInbound, ChatOutbound, ChatError>, name: &str) -> Participant {
+        room.join_with_profile(Profile { display_name: name.to_string(), ..Profile::default() }).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn mentions_notify_the_named_participants() {
+        let room = TestRoom::new(Arc::new(ChatMessageHandler::default())).await;
+        let alice = named(&room, "alice").await;
+        let (bob, bobby) = (named(&room, "bob").await, named(&room, "bobby").await);
+
+        room.send(alice, ChatInbound::SendPublicMessage { content: "@bob, popcorn?".to_string() }).await.unwrap();
+
+        let received = room.take(bob);
+        let [ChatOutbound::PublicMessage { mentions, .. }, ChatOutbound::Mentioned { from, content, .. }] =
+            received.as_slice()
+        else {
+            panic!("expected the message and a mention, got {received:?}");
+        };
+        assert_eq!((mentions.as_slice(), *from, content.as_str()), ([bob].as_slice(), alice, "@bob, popcorn?"));
+        assert!(matches!(room.take(bobby).as_slice(), [ChatOutbound::PublicMessage { .. }]));
+    }
+
+    #[tokio::test]
+    async fn names_shared_by_several_participants_mention_nobody() {
+        let room = TestRoom::new(Arc::new(ChatMessageHandler::default())).await;
+        let alice = named(&room, "alice").await;
+        let (bob, other_bob) = (named(&room, "bob").await, named(&room, "bob").await);
+
+        room.send(alice, ChatInbound::SendPublicMessage { content: "@bob, popcorn?".to_string() }).await.unwrap();
+
+        for bob in [bob, other_bob] {
+            assert!(matches!(
+                room.take(bob).as_slice(),
+                [ChatOutbound::PublicMessage { mentions, .. }] if mentions.is_empty()
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn authors_edit_while_authors_and_owners_delete() {
+        let room = TestRoom::new(Arc::new(ChatMessageHandler::default())).await;
+        let owner = room.room().await.created_by;
+        room.join_as(owner).await.unwrap();
+        let (alice, bob) = (named(&room, "alice").await, named(&room, "bob").await);
+        room.send(alice, ChatInbound::SendPublicMessage { content: "helo".to_string() }).await.unwrap();
+        room.send(alice, ChatInbound::SendPublicMessage { content: "spoiler".to_string() }).await.unwrap();
+        let [helo, spoiler] = public_ids(&room.take(bob))[..] else {
+            panic!("expected both messages");
+        };
+
+        let edit = |content: &str| ChatInbound::EditMessage { id: helo, content: content.to_string() };
+        room.send(bob, edit("hacked")).await.unwrap();
+        room.send(alice, edit("hello @bob")).await.unwrap();
+        room.take(bob);
+        let unknown = Uuid::now_v7();
+        room.send(bob, ChatInbound::DeleteMessage { id: helo }).await.unwrap();
+        room.send(owner, ChatInbound::DeleteMessage { id: spoiler }).await.unwrap();
+        room.send(owner, ChatInbound::DeleteMessage { id: unknown }).await.unwrap();
+
+        let not_allowed = ChatError::NotAllowed { id: helo, participant: bob };
+        assert!(matches!(
+            room.take(bob).as_slice(),
+            [ChatOutbound::Refused { reason }, ChatOutbound::MessageDeleted { id }] if *reason == not_allowed && *id == spoiler
+        ));
+        assert_eq!(room.take(owner).pop(), Some(ChatOutbound::Refused { reason: ChatError::MessageNotFound { id: unknown } }));
+
+        room.send(bob, ChatInbound::FetchHistory { before: None, limit: 10 }).await.unwrap();
+        let received = room.take(bob);
+        let [ChatOutbound::History { messages, .. }] = received.as_slice() else {
+            panic!("expected the history, got {received:?}");
+        };
+        let kept: Vec<_> = messages
+            .iter()
+            .map(|m| (m.id, m.content.as_str(), m.mentions.clone(), m.edited_at.is_some()))
+            .collect();
+        assert_eq!(kept, [(helo, "hello @bob", vec![bob], true)]);
+    }
+
+    #[tokio::test]
+    async fn moderation_masks_or_turns_back_public_messages() {
+        let config = ChatConfig {
+            moderation: ModerationConfig {
+                max_chars: 20,
+                words: WordlistConfig { blocked: vec!["darn".to_string()], action: WordAction::Mask },
+                ..ModerationConfig::default()
+            },
+            ..ChatConfig::default()
+        };
+        let room = TestRoom::new(Arc::new(ChatMessageHandler::new(config))).await;
+        let (alice, bob) = (named(&room, "alice").await, named(&room, "bob").await);
+
+        room.send(alice, ChatInbound::SendPublicMessage { content: "darn spoilers".to_string() }).await.unwrap();
+        room.send(alice, ChatInbound::SendPublicMessage { content: "x".repeat(21) }).await.unwrap();
+        let id = public_ids(&room.take(bob))[0];
+        room.send(alice, ChatInbound::EditMessage { id, content: "x".repeat(21) }).await.unwrap();
+
+        let too_long = Rejection::TooLong { max_chars: 20 };
+        assert!(matches!(
+            room.take(alice).as_slice(),
+            [
+                ChatOutbound::PublicMessage { content, .. },
+                ChatOutbound::MessageRejected { edit_of: None, reason: first },
+                ChatOutbound::MessageRejected { edit_of: Some(edit_of), reason: second },
+            ] if content == "**** spoilers" && *first == too_long && *edit_of == id && *second == too_long
+        ));
+        assert!(room.take(bob).is_empty());
+    }
+
+    #[tokio::test]
+    async fn moderation_covers_private_messages_but_not_reactions() {
+        let config = ChatConfig {
+            moderation: ModerationConfig {
+                words: WordlistConfig { blocked: vec!["darn".to_string()], action: WordAction::Reject },
+                flood: FloodConfig { max_messages: 1, ..FloodConfig::default() },
+                ..ModerationConfig::default()
+            },
+            ..ChatConfig::default()
+        };
+        let room = TestRoom::new(Arc::new(ChatMessageHandler::new(config))).await;
+        let (alice, bob) = (named(&room, "alice").await, named(&room, "bob").await);
+        room.send(alice, ChatInbound::SendPublicMessage { content: "hi".to_string() }).await.unwrap();
+        let id = public_ids(&room.take(alice))[0];
+
+        // an edit refused for its author doesn't count towards bob's flood limits
+        room.send(bob, ChatInbound::EditMessage { id, content: "hacked".to_string() }).await.unwrap();
+        room.send(bob, ChatInbound::SendPrivateMessage { to: alice, content: "darn".to_string() }).await.unwrap();
+        for _ in 0..3 {
+            room.send(bob, ChatInbound::ToggleReaction { id, emoji: "darn".to_string() }).await.unwrap();
+        }
+        room.take(alice);
+        room.send(bob, ChatInbound::SendPrivateMessage { to: alice, content: "sorry".to_string() }).await.unwrap();
+
+        let received = room.take(bob);
+        assert!(matches!(received[0], ChatOutbound::PublicMessage { .. }), "{received:?}");
+        assert!(matches!(received[1], ChatOutbound::Refused { reason: ChatError::NotAllowed { .. } }), "{received:?}");
+        assert!(matches!(received[2], ChatOutbound::MessageRejected { reason: Rejection::BlockedWord, .. }), "{received:?}");
+        assert!(received[3..6].iter().all(|msg| matches!(msg, ChatOutbound::ReactionsChanged { .. })), "{received:?}");
+        assert!(matches!(room.take(alice).as_slice(), [ChatOutbound::PrivateMessage { content, .. }] if content == "sorry"));
+    }
+
+    #[tokio::test]
+    async fn whispers_are_turned_back_and_count_towards_flooding() {
+        let config = ChatConfig {
+            moderation: ModerationConfig {
+                words: WordlistConfig { blocked: vec!["darn".to_string()], action: WordAction::Reject },
+                flood: FloodConfig { max_messages: 1, ..FloodConfig::default() },
+                ..ModerationConfig::default()
+            },
+            ..ChatConfig::default()
+        };
+        let room = TestRoom::new(Arc::new(ChatMessageHandler::new(config))).await;
+        let (alice, bob) = (named(&room, "alice").await, named(&room, "bob").await);
+        let whisper = |content: &str| ChatInbound::SendPrivateMessage { to: bob, content: content.to_string() };
+
+        room.send(alice, whisper("darn")).await.unwrap();
+        room.send(alice, whisper("psst")).await.unwrap();
+        room.send(alice, whisper("psst again")).await.unwrap();
+
+        assert!(matches!(
+            room.take(alice).as_slice(),
+            [
+                ChatOutbound::MessageRejected { edit_of: None, reason: Rejection::BlockedWord },
+                ChatOutbound::MessageRejected { edit_of: None, reason: Rejection::Flooding { .. } },
+            ]
+        ));
+        assert!(matches!(room.take(bob).as_slice(), [ChatOutbound::PrivateMessage { content, .. }] if content == "psst"));
+    }
+
+    #[tokio::test]
+    async fn reactions_toggle_per_participant() {
+        let room = TestRoom::new(Arc::new(ChatMessageHandler::default())).await;
+        let (alice, bob) = (named(&room, "alice").await, named(&room, "bob").await);
+        room.send(alice, ChatInbound::SendPublicMessage { content: "movie?".to_string() }).await.unwrap();
+        let id = public_ids(&room.take(alice))[0];
+        let react = |emoji: &str| ChatInbound::ToggleReaction { id, emoji: emoji.to_string() };
+
+        room.send(alice, react("👍")).await.unwrap();
+        room.send(bob, react("👍")).await.unwrap();
+        room.send(alice, react("👍")).await.unwrap();
+        room.send(bob, react("thumbs up")).await.unwrap();
+
+        let Some(ChatOutbound::ReactionsChanged { id: changed, reactions }) = room.take(alice).pop() else {
+            panic!("expected the reactions to change");
+        };
+        assert_eq!(changed, id);
+        assert_eq!(reactions, BTreeMap::from([("👍".to_string(), vec![bob])]));
+        assert_eq!(room.take(bob).pop(), Some(ChatOutbound::Refused { reason: ChatError::InvalidReaction }));
+    }
+
+    #[tokio::test]
+    async fn playback_is_broadcast_and_sent_to_joiners() {
+        let handler = Arc::new(ChatMessageHandler::default());
+        let room = TestRoom::new(handler.clone()).await;
+        let alice = room.join().await.unwrap();
+        let set = |position_ms| ChatInbound::SetPlayback {
+            track: "intro.mp3".to_string(),
+            position_ms,
+            duration_ms: 90_000,
+            playing: true,
+        };
+
+        room.send(alice, set(120_000)).await.unwrap();
+        room.send(alice, set(30_000)).await.unwrap();
+        let bob = room.join().await.unwrap();
+
+        let received = room.take(alice);
+        let [ChatOutbound::Refused { reason: ChatError::InvalidPlayback { .. } }, changed @ ChatOutbound::PlaybackChanged { playback }] =
+            received.as_slice()
+        else {
+            panic!("expected the first playback to be refused, got {received:?}");
+        };
+        assert_eq!((playback.position_ms, playback.set_by), (30_000, alice));
+        assert_eq!(room.take(bob).as_slice(), [changed.clone()]);
+        assert_eq!(handler.coalesce_key(changed), Some("playback".to_string()));
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use lobby::domain::{Participant, RoomId};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Target of the tracing events recording every message a moderator masked or rejected.
+pub const AUDIT_TARGET: &str = "audit";
+
+/// Flood trackers are only pruned once there are this many, so the common case never scans.
+const PRUNE_THRESHOLD: usize = 1_024;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModerationConfig {
+    /// Longest public or private message, in characters.
+    pub max_chars: usize,
+    pub words: WordlistConfig,
+    pub links: LinkPolicyConfig,
+    pub flood: FloodConfig,
+}
+
+impl Default for ModerationConfig {
+    fn default() -> Self {
+        Self {
+            max_chars: 2_000,
+            words: WordlistConfig::default(),
+            links: LinkPolicyConfig::default(),
+            flood: FloodConfig::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WordlistConfig {
+    /// Matched as whole words, ignoring case.
+    pub blocked: Vec<String>,
+    pub action: WordAction,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WordAction {
+    /// Replace every character of a blocked word with `*`.
+    #[default]
+    Mask,
+    Reject,
+}
+
+/// Domains match their subdomains too.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinkPolicyConfig {
+    /// When not empty, links to any other domain are rejected.
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FloodConfig {
+    /// Public and private messages and edits a participant may send to a room within the window.
+    pub max_messages: usize,
+    /// Times the same content may be sent within the window.
+    pub max_repeats: usize,
+    pub window_ms: u64,
+}
+
+impl Default for FloodConfig {
+    fn default() -> Self {
+        Self { max_messages: 20, max_repeats: 3, window_ms: 10_000 }
+    }
+}
+
+/// Why a message wasn't sent, told to its sender.
+#[derive(Clone, Debug, PartialEq, Error, Serialize, Deserialize, JsonSchema)]
+pub enum Rejection {
+    #[error("message is longer than {max_chars} characters")]
+    TooLong { max_chars: usize },
+    #[error("message contains a blocked word")]
+    BlockedWord,
+    #[error("links to {domain} aren't allowed")]
+    BlockedLink { domain: String },
+    #[error("too many messages; retry in {retry_after_ms}ms")]
+    Flooding { retry_after_ms: u64 },
+    #[error("the same message was sent too many times")]
+    Repeated,
+    /// From a moderator the server was extended with.
+    #[error("{reason}")]
+    Other { reason: String },
+}
+
+/// A public or private message, or the new content of an edited one, waiting to be moderated.
+pub struct Draft<'a> {
+    pub room_id: RoomId,
+    pub from: Participant,
+    pub content: &'a str,
+    pub at: Instant,
+}
+
+pub enum Verdict {
+    Allow,
+    /// Send this content instead.
+    Rewrite(String),
+    Reject(Rejection),
+}
+
+/// One step of the moderation pipeline, see [`Moderation`].
+pub trait Moderator: Send + Sync {
+    /// Shown in the audit trail.
+    fn name(&self) -> &'static str;
+
+    fn moderate(&self, draft: &Draft<'_>) -> Verdict;
+
+    /// Forgets whatever the moderator keeps about a room that was closed.
+    fn room_closed(&self, _room_id: RoomId) {}
+}
+
+pub struct Moderation {
+    steps: Vec<Box<dyn Moderator>>,
+}
+
+impl Moderation {
+    /// The flood detector comes last, so only messages that passed the others count.
+    pub fn new(config: &ModerationConfig) -> Self {
+        let mut moderation = Self { steps: Vec::new() };
+        moderation.push(LengthLimit { max_chars: config.max_chars });
+        if !config.words.blocked.is_empty() {
+            moderation.push(Wordlist::new(&config.words));
+        }
+        if !config.links.allow.is_empty() || !config.links.deny.is_empty() {
+            moderation.push(LinkPolicy::new(&config.links));
+        }
+        moderation.push(FloodDetector::new(&config.flood));
+        moderation
+    }
+
+    /// Adds a step after the existing ones.
+    pub fn push(&mut self, moderator: impl Moderator + 'static) {
+        self.steps.push(Box::new(moderator));
+    }
+
+    /// The content to send, possibly rewritten, or why it mustn't be.
+    pub fn check(&self, draft: &Draft<'_>) -> Result<String, Rejection> {
+        let mut content = draft.content.to_string();
+        for step in &self.steps {
+            let draft = Draft { content: &content, ..*draft };
+            match step.moderate(&draft) {
+                Verdict::Allow => {}
+                Verdict::Rewrite(rewritten) => {
+                    tracing::info!(
+                        target: AUDIT_TARGET,
+                        room_id = %draft.room_id,
+                        participant = %draft.from,
+                        moderator = step.name(),
+                        original = draft.content,
+                        "message rewritten"
+                    );
+                    content = rewritten;
+                }
+                Verdict::Reject(rejection) => {
+                    tracing::info!(
+                        target: AUDIT_TARGET,
+                        room_id = %draft.room_id,
+                        participant = %draft.from,
+                        moderator = step.name(),
+                        content = draft.content,
+                        "message rejected: {rejection}"
+                    );
+                    return Err(rejection);
+                }
+            }
+        }
+        Ok(content)
+    }
+
+    pub fn room_closed(&self, room_id: RoomId) {
+        for step in &self.steps {
+            step.room_closed(room_id);
+        }
+    }
+}
+
+pub struct LengthLimit {
+    pub max_chars: usize,
+}
+
+impl Moderator for LengthLimit {
+    fn name(&self) -> &'static str {
+        "length_limit"
+    }
+
+    fn moderate(&self, draft: &Draft<'_>) -> Verdict {
+        match draft.content.chars().count() > self.max_chars {
+            true => Verdict::Reject(Rejection::TooLong { max_chars: self.max_chars }),
+            false => Verdict::Allow,
+        }
+    }
+}
+
+pub struct Wordlist {
+    blocked: HashSet<String>,
+    action: WordAction,
+}
+
+impl Wordlist {
+    pub fn new(config: &WordlistConfig) -> Self {
+        Self {
+            blocked: config.blocked.iter().map(|word| word.to_lowercase()).collect(),
+            action: config.action,
+        }
+    }
+}
+
+impl Moderator for Wordlist {
+    fn name(&self) -> &'static str {
+        "wordlist"
+    }
+
+    fn moderate(&self, draft: &Draft<'_>) -> Verdict {
+        let mut masked = String::with_capacity(draft.content.len());
+        let mut blocked = false;
+        // words are the runs of letters and digits, so `darn!` and `(darn)` both match `darn`
+        for (is_word, run) in runs(draft.content) {
+            if is_word && self.blocked.contains(&run.to_lowercase()) {
+                blocked = true;
+                masked.extend(run.chars().map(|_| '*'));
+            } else {
+                masked.push_str(run);
+            }
+        }
+        match (blocked, self.action) {
+            (false, _) => Verdict::Allow,
+            (true, WordAction::Mask) => Verdict::Rewrite(masked),
+            (true, WordAction::Reject) => Verdict::Reject(Rejection::BlockedWord),
+        }
+    }
+}
+
+/// Splits `content` into alternating runs of alphanumeric and other characters.
+fn runs(content: &str) -> impl Iterator<Item = (bool, &str)> {
+    let mut rest = content;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let is_word = first.is_alphanumeric();
+        let end = rest.find(|c: char| c.is_alphanumeric() != is_word).unwrap_or(rest.len());
+        let (run, tail) = rest.split_at(end);
+        rest = tail;
+        Some((is_word, run))
+    })
+}
+
+pub struct LinkPolicy {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl LinkPolicy {
+    pub fn new(config: &LinkPolicyConfig) -> Self {
+        let normalize = |domains: &[String]| domains.iter().map(|domain| domain.trim_matches('.').to_lowercase()).collect();
+        Self { allow: normalize(&config.allow), deny: normalize(&config.deny) }
+    }
+
+    fn permits(&self, host: &str) -> bool {
+        let matches = |domain: &String| host == domain || host.strip_suffix(domain.as_str()).is_some_and(|sub| sub.ends_with('.'));
+        !self.deny.iter().any(matches) && (self.allow.is_empty() || self.allow.iter().any(matches))
+    }
+}
+
+impl Moderator for LinkPolicy {
+    fn name(&self) -> &'static str {
+        "link_policy"
+    }
+
+    fn moderate(&self, draft: &Draft<'_>) -> Verdict {
+        match hosts(draft.content).find(|host| !self.permits(host)) {
+            Some(domain) => Verdict::Reject(Rejection::BlockedLink { domain }),
+            None => Verdict::Allow,
+        }
+    }
+}
+
+/// Lowercased hosts of the links in `content`, with or without a scheme, e.g. `evil.com`.
+fn hosts(content: &str) -> impl Iterator<Item = String> {
+    content.split_whitespace().filter_map(|token| {
+        let lower = token.to_lowercase();
+        let rest = match lower.find("://") {
+            Some(at) => &lower[at + 3..],
+            None => lower.trim_start_matches(|c: char| !c.is_alphanumeric()),
+        };
+        let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+        // drop credentials and port, and the punctuation a sentence may end the link with
+        let host = authority.rsplit('@').next().unwrap_or_default();
+        let host = host.split(':').next().unwrap_or_default();
+        let host = host.trim_end_matches(|c: char| !c.is_alphanumeric());
+        is_domain(host).then(|| host.to_string())
+    })
+}
+
+/// Dot separated labels ending in a top-level domain of letters, so `e.g` or `3.14` aren't.
+fn is_domain(host: &str) -> bool {
+    let labels: Vec<_> = host.split('.').collect();
+    let tld = labels[labels.len() - 1];
+    labels.len() > 1
+        && labels.iter().all(|label| !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '-'))
+        && tld.chars().count() > 1
+        && tld.chars().all(char::is_alphabetic)
+}
+
+pub struct FloodDetector {
+    config: FloodConfig,
+    sent: Mutex<HashMap<(RoomId, Participant), VecDeque<Sent>>>,
+}
+
+/// When a message was let through, and its content ignoring case and surrounding whitespace.
+type Sent = (Instant, String);
+
+impl FloodDetector {
+    pub fn new(config: &FloodConfig) -> Self {
+        Self { config: config.clone(), sent: Mutex::new(HashMap::new()) }
+    }
+}
+
+impl Moderator for FloodDetector {
+    fn name(&self) -> &'static str {
+        "flood_detector"
+    }
+
+    fn moderate(&self, draft: &Draft<'_>) -> Verdict {
+        let window = Duration::from_millis(self.config.window_ms);
+        let mut sent = self.sent.lock().unwrap_or_else(PoisonError::into_inner);
+        if sent.len() >= PRUNE_THRESHOLD {
+            sent.retain(|_, recent| recent.back().is_some_and(|(at, _)| draft.at.saturating_duration_since(*at) < window));
+        }
+        let recent = sent.entry((draft.room_id, draft.from)).or_default();
+        while recent.front().is_some_and(|(at, _)| draft.at.saturating_duration_since(*at) >= window) {
+            recent.pop_front();
+        }
+        if let Some((oldest, _)) = recent.front().filter(|_| recent.len() >= self.config.max_messages) {
+            let retry_after = (*oldest + window).saturating_duration_since(draft.at);
+            return Verdict::Reject(Rejection::Flooding { retry_after_ms: retry_after.as_millis() as u64 });
+        }
+        let content = draft.content.trim().to_lowercase();
+        if recent.iter().filter(|(_, sent)| *sent == content).count() >= self.config.max_repeats {
+            return Verdict::Reject(Rejection::Repeated);
+        }
+        recent.push_back((draft.at, content));
+        Verdict::Allow
+    }
+
+    fn room_closed(&self, room_id: RoomId) {
+        self.sent.lock().unwrap_or_else(PoisonError::into_inner).retain(|(room, _), _| *room != room_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn draft(content: &str, at: Instant) -> Draft<'_> {
+        Draft { room_id: Uuid::nil(), from: Uuid::nil(), content, at }
+    }
+
+    fn check(moderation: &Moderation, content: &str) -> Result<String, Rejection> {
+        moderation.check(&draft(content, Instant::now()))
+    }
+
+    #[test]
+    fn blocked_words_are_masked_or_rejected_as_whole_words() {
+        let words = |action| ModerationConfig {
+            words: WordlistConfig { blocked: vec!["Darn".to_string()], action },
+            ..ModerationConfig::default()
+        };
+        let masking = Moderation::new(&words(WordAction::Mask));
+        let rejecting = Moderation::new(&words(WordAction::Reject));
+
+        assert_eq!(check(&masking, "darn it, DARN!").unwrap(), "**** it, ****!");
+        assert_eq!(check(&masking, "darned good").unwrap(), "darned good");
+        assert_eq!(check(&rejecting, "(darn)"), Err(Rejection::BlockedWord));
+    }
+
+    #[test]
+    fn links_are_checked_against_the_allowed_and_denied_domains() {
+        let policy = LinkPolicy::new(&LinkPolicyConfig {
+            allow: vec!["example.com".to_string(), "youtu.be".to_string()],
+            deny: vec!["ads.example.com".to_string()],
+        });
+        let verdict = |content| match policy.moderate(&draft(content, Instant::now())) {
+            Verdict::Reject(Rejection::BlockedLink { domain }) => Some(domain),
+            _ => None,
+        };
+
+        assert_eq!(verdict("see https://www.Example.com/watch?v=1 or youtu.be"), None);
+        assert_eq!(verdict("https://user@ads.example.com:8443/x"), Some("ads.example.com".to_string()));
+        assert_eq!(verdict("(www.notexample.com)."), Some("www.notexample.com".to_string()));
+        assert_eq!(verdict("no links, just example.com"), None);
+        assert_eq!(verdict("grab it at Evil.com/free, e.g. 3.14 times"), Some("evil.com".to_string()));
+    }
+
+    #[test]
+    fn long_messages_are_rejected() {
+        let moderation = Moderation::new(&ModerationConfig { max_chars: 3, ..ModerationConfig::default() });
+
+        assert_eq!(check(&moderation, "héé").unwrap(), "héé");
+        assert_eq!(check(&moderation, "four"), Err(Rejection::TooLong { max_chars: 3 }));
+    }
+
+    #[test]
+    fn floods_and_repeats_are_rejected_until_the_window_passes() {
+        let detector = FloodDetector::new(&FloodConfig { max_messages: 3, max_repeats: 2, window_ms: 1_000 });
+        let start = Instant::now();
+        let rejection = |content, ms| match detector.moderate(&draft(content, start + Duration::from_millis(ms))) {
+            Verdict::Reject(rejection) => Some(rejection),
+            _ => None,
+        };
+
+        assert_eq!(rejection("hi", 0), None);
+        assert_eq!(rejection("HI ", 100), None);
+        assert_eq!(rejection("hi", 200), Some(Rejection::Repeated));
+        assert_eq!(rejection("there", 300), None);
+        assert_eq!(rejection("again", 400), Some(Rejection::Flooding { retry_after_ms: 600 }));
+        assert_eq!(rejection("again", 1_000), None);
+    }
+}
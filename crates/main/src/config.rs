@@ -0,0 +1,550 @@
+use clap::Parser;
+use lobby::config::{AdminConfig, BusConfig, CookieConfig, HealthConfig, HubConfig, LobbyConfig, OutboundQueueConfig, RateLimitConfig, RoomLimits, ShutdownConfig, SignalConfig, TokenConfig};
+use main::chat::ChatConfig;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// e.g. `SYNC_PLAYER_LOG__LEVEL=info` sets `log.level`.
+const ENV_PREFIX: &str = "SYNC_PLAYER_";
+
+#[derive(Debug, Parser)]
+#[command(about = "Synchronizes music playback across multiple devices")]
+pub struct Cli {
+    /// TOML configuration file.
+    #[arg(short, long, env = "SYNC_PLAYER_CONFIG")]
+    config: Option<PathBuf>,
+    /// Address the HTTP server listens on.
+    #[arg(long)]
+    bind: Option<String>,
+    /// One of trace, debug, info, warn, error.
+    #[arg(long)]
+    log_level: Option<String>,
+    /// One of json, pretty, compact.
+    #[arg(long)]
+    log_format: Option<String>,
+    /// Overrides any configuration value, e.g. `--set rooms.max_capacity=20`.
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    overrides: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: SocketAddr,
+    /// Serve HTTPS instead of plain HTTP.
+    pub tls: Option<TlsConfig>,
+    pub log: LogConfig,
+    pub storage: StorageConfig,
+    /// Also run the cluster hub in this process, listening on this address.
+    pub hub_listen: Option<SocketAddr>,
+    /// `hub.secret` is also what this server presents with `storage.kind = "hub"`.
+    pub hub: HubConfig,
+    pub rooms: RoomLimits,
+    pub actor_buffer: usize,
+    pub outbound_queue: OutboundQueueConfig,
+    pub shutdown: ShutdownConfig,
+    /// `cookies.secure` is implied when serving TLS.
+    pub cookies: CookieConfig,
+    pub tokens: TokenConfig,
+    pub rate_limits: RateLimitConfig,
+    pub signals: SignalConfig,
+    pub health: HealthConfig,
+    /// Each handler's admin API is served under `/admin` followed by the handler's path.
+    pub admin: AdminConfig,
+    pub handlers: Vec<HandlerConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], 8080)),
+            tls: None,
+            log: LogConfig::default(),
+            storage: StorageConfig::default(),
+            hub_listen: None,
+            hub: HubConfig::default(),
+            rooms: RoomLimits::default(),
+            actor_buffer: LobbyConfig::default().actor_buffer,
+            outbound_queue: OutboundQueueConfig::default(),
+            shutdown: ShutdownConfig::default(),
+            cookies: CookieConfig::default(),
+            tokens: TokenConfig::default(),
+            rate_limits: RateLimitConfig::default(),
+            signals: SignalConfig::default(),
+            health: HealthConfig::default(),
+            admin: AdminConfig::default(),
+            handlers: vec![HandlerConfig {
+                kind: HandlerKind::Chat,
+                path: "/chat".to_string(),
+                chat: ChatConfig::default(),
+            }],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM encoded certificate chain.
+    pub cert: PathBuf,
+    /// PEM encoded private key.
+    pub key: PathBuf,
+    /// How often the files are checked for changes.
+    #[serde(default = "TlsConfig::default_reload_interval_ms")]
+    pub reload_interval_ms: u64,
+}
+
+impl TlsConfig {
+    fn default_reload_interval_ms() -> u64 {
+        10_000
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub level: LogLevel,
+    pub format: LogFormat,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Trace,
+    #[default]
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl From<LogLevel> for tracing::Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Trace => tracing::Level::TRACE,
+            LogLevel::Debug => tracing::Level::DEBUG,
+            LogLevel::Info => tracing::Level::INFO,
+            LogLevel::Warn => tracing::Level::WARN,
+            LogLevel::Error => tracing::Level::ERROR,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Json,
+    Pretty,
+    Compact,
+}
+
+/// Where rooms are kept.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum StorageConfig {
+    /// In this process, optionally snapshotted across restarts to `<snapshot>.<handler>`.
+    Memory { snapshot: Option<PathBuf> },
+    /// In the cluster hub, shared with every other server connected to it.
+    Hub { address: String },
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig::Memory { snapshot: None }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HandlerConfig {
+    pub kind: HandlerKind,
+    /// Path prefix the handler's lobby is mounted under.
+    pub path: String,
+    /// Settings of `chat` handlers.
+    #[serde(default)]
+    pub chat: ChatConfig,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HandlerKind {
+    Chat,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("failed to read configuration file {path}: {source}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to parse configuration file {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+    #[error("invalid override `{0}`, expected KEY=VALUE")]
+    Override(String),
+    #[error("invalid configuration: {0}")]
+    Deserialize(#[source] toml::de::Error),
+    #[error("invalid configuration:\n  {}", .0.join("\n  "))]
+    Invalid(Vec<String>),
+}
+
+impl Config {
+    /// Defaults, then the TOML file, environment variables and command line flags.
+    pub fn load(cli: Cli) -> Result<Self, ConfigError> {
+        Self::load_from(cli, std::env::vars())
+    }
+
+    fn load_from(cli: Cli, env: impl IntoIterator<Item = (String, String)>) -> Result<Self, ConfigError> {
+        let mut table = match &cli.config {
+            Some(path) => {
+                let content = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+                    path: path.clone(),
+                    source,
+                })?;
+                toml::from_str(&content).map_err(|source| ConfigError::Parse {
+                    path: path.clone(),
+                    source,
+                })?
+            }
+            None => toml::Table::new(),
+        };
+
+        for (name, value) in env {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            if key == "CONFIG" {
+                continue;
+            }
+            let path: Vec<String> = key.split("__").map(str::to_lowercase).collect();
+            set(&mut table, &path, parse_value(&value));
+        }
+
+        let flags = [("bind", cli.bind), ("log.level", cli.log_level), ("log.format", cli.log_format)];
+        for (key, value) in flags {
+            if let Some(value) = value {
+                set(&mut table, &split_key(key), toml::Value::String(value));
+            }
+        }
+        for assignment in cli.overrides {
+            let (key, value) = assignment
+                .split_once('=')
+                .ok_or_else(|| ConfigError::Override(assignment.clone()))?;
+            set(&mut table, &split_key(key.trim()), parse_value(value.trim()));
+        }
+
+        let config: Config = toml::Value::Table(table)
+            .try_into()
+            .map_err(ConfigError::Deserialize)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let mut errors = Vec::new();
+        if self.rooms.max_capacity == 0 {
+            errors.push("rooms.max_capacity must be at least 1".to_string());
+        }
+        if self.rooms.max_rooms == 0 {
+            errors.push("rooms.max_rooms must be at least 1".to_string());
+        }
+        if self.rooms.max_rooms_per_owner == 0 {
+            errors.push("rooms.max_rooms_per_owner must be at least 1".to_string());
+        }
+        if self.rooms.max_name_len == 0 {
+            errors.push("rooms.max_name_len must be at least 1".to_string());
+        }
+        if self.actor_buffer == 0 {
+            errors.push("actor_buffer must be at least 1".to_string());
+        }
+        if self.outbound_queue.capacity == 0 {
+            errors.push("outbound_queue.capacity must be at least 1".to_string());
+        }
+        if let StorageConfig::Hub { address } = &self.storage {
+            if address.is_empty() {
+                errors.push("storage.address must not be empty".to_string());
+            }
+        }
+        let in_cluster = self.hub_listen.is_some() || matches!(self.storage, StorageConfig::Hub { .. });
+        if in_cluster && self.hub.secret.len() < 32 {
+            errors.push("hub.secret must be at least 32 bytes long".to_string());
+        }
+        if self.hub.queue_capacity == 0 {
+            errors.push("hub.queue_capacity must be at least 1".to_string());
+        }
+        if let Some(tls) = &self.tls {
+            if tls.reload_interval_ms == 0 {
+                errors.push("tls.reload_interval_ms must be at least 1".to_string());
+            }
+        }
+        for (i, key) in self.cookies.keys.iter().enumerate() {
+            if key.len() < 32 {
+                errors.push(format!("cookies.keys[{i}] must be at least 32 bytes long"));
+            }
+        }
+        if self.cookies.max_age_secs == 0 {
+            errors.push("cookies.max_age_secs must be at least 1".to_string());
+        }
+        for (i, key) in self.tokens.keys.iter().enumerate() {
+            if key.len() < 32 {
+                errors.push(format!("tokens.keys[{i}] must be at least 32 bytes long"));
+            }
+        }
+        if self.tokens.ttl_secs == 0 {
+            errors.push("tokens.ttl_secs must be at least 1".to_string());
+        }
+        for (name, bucket) in [
+            ("room_creation", &self.rate_limits.room_creation),
+            ("messages", &self.rate_limits.messages),
+        ] {
+            if bucket.burst == 0 {
+                errors.push(format!("rate_limits.{name}.burst must be at least 1"));
+            }
+            if bucket.per_second.is_nan() || bucket.per_second <= 0.0 {
+                errors.push(format!("rate_limits.{name}.per_second must be positive"));
+            }
+        }
+        if self.admin.token.as_ref().is_some_and(|token| token.len() < 32) {
+            errors.push("admin.token must be at least 32 bytes long".to_string());
+        }
+        if self.health.probe_timeout_ms == 0 {
+            errors.push("health.probe_timeout_ms must be at least 1".to_string());
+        }
+        if self.rate_limits.kick_after == Some(0) {
+            errors.push("rate_limits.kick_after must be at least 1".to_string());
+        }
+        if self.signals.ttl_ms == 0 {
+            errors.push("signals.ttl_ms must be at least 1".to_string());
+        }
+        if self.handlers.is_empty() {
+            errors.push("at least one handler must be mounted".to_string());
+        }
+        let mut paths = HashSet::new();
+        for handler in &self.handlers {
+            if !handler.path.starts_with('/') || handler.path.len() < 2 || handler.path.ends_with('/') {
+                errors.push(format!("handler path `{}` must start and must not end with `/`", handler.path));
+            }
+            if self.admin.token.is_some() && (handler.path == "/admin" || handler.path.starts_with("/admin/")) {
+                errors.push(format!("handler path `{}` is reserved for the admin API", handler.path));
+            }
+            if !paths.insert(&handler.path) {
+                errors.push(format!("handler path `{}` is mounted more than once", handler.path));
+            }
+            let moderation = &handler.chat.moderation;
+            for (name, value) in [
+                ("max_chars", moderation.max_chars),
+                ("flood.max_messages", moderation.flood.max_messages),
+                ("flood.max_repeats", moderation.flood.max_repeats),
+                ("flood.window_ms", moderation.flood.window_ms as usize),
+            ] {
+                if value == 0 {
+                    errors.push(format!("handler `{}`: chat.moderation.{name} must be at least 1", handler.path));
+                }
+            }
+            // only runs of letters and digits are compared against the wordlist
+            for word in &moderation.words.blocked {
+                if word.is_empty() || !word.chars().all(char::is_alphanumeric) {
+                    errors.push(format!("handler `{}`: blocked word `{word}` must be letters and digits only", handler.path));
+                }
+            }
+            for domain in moderation.links.allow.iter().chain(&moderation.links.deny) {
+                if domain.trim_matches('.').is_empty() || domain.contains(|c: char| c.is_whitespace() || c == '/') {
+                    errors.push(format!("handler `{}`: `{domain}` is not a domain", handler.path));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+
+    pub fn lobby_config(&self, handler: &HandlerConfig) -> LobbyConfig {
+        let name = handler.path.trim_matches('/').to_string();
+        let (bus, rooms_snapshot) = match &self.storage {
+            StorageConfig::Memory { snapshot } => {
+                (BusConfig::InProcess, snapshot.as_deref().map(|snapshot| handler_snapshot(snapshot, &name)))
+            }
+            StorageConfig::Hub { address } => {
+                (BusConfig::Tcp { hub: address.clone(), secret: self.hub.secret.clone() }, None)
+            }
+        };
+        LobbyConfig {
+            name,
+            actor_buffer: self.actor_buffer,
+            outbound_queue: self.outbound_queue.clone(),
+            bus,
+            rooms_snapshot,
+            shutdown: self.shutdown.clone(),
+            rooms: self.rooms.clone(),
+            cookies: CookieConfig {
+                secure: self.cookies.secure || self.tls.is_some(),
+                ..self.cookies.clone()
+            },
+            tokens: self.tokens.clone(),
+            rate_limits: self.rate_limits.clone(),
+            signals: self.signals.clone(),
+            health: self.health.clone(),
+            admin: self.admin.clone(),
+        }
+    }
+}
+
+/// `<snapshot>.<handler>`, so that handlers don't overwrite each other's rooms.
+fn handler_snapshot(snapshot: &Path, name: &str) -> PathBuf {
+    if name.is_empty() {
+        return snapshot.to_path_buf();
+    }
+    let mut file = snapshot.as_os_str().to_owned();
+    file.push(format!(".{}", name.replace('/', "-")));
+    file.into()
+}
+
+fn split_key(key: &str) -> Vec<String> {
+    key.split('.').map(str::to_string).collect()
+}
+
+/// Reads `raw` as a TOML value (number, boolean, array, inline table), falling back to a string.
+fn parse_value(raw: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {raw}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+fn set(table: &mut toml::Table, path: &[String], value: toml::Value) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut table = table;
+    for key in parents {
+        let entry = table
+            .entry(key.clone())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if !entry.is_table() {
+            *entry = toml::Value::Table(toml::Table::new());
+        }
+        table = entry.as_table_mut().expect("just made a table");
+    }
+    table.insert(last.clone(), value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use main::chat::ChatMessageHandler;
+    use std::sync::Arc;
+
+    fn load(args: &[&str], env: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let cli = Cli::try_parse_from(std::iter::once("sync-player").chain(args.iter().copied())).unwrap();
+        Config::load_from(cli, env.iter().map(|(k, v)| (k.to_string(), v.to_string())))
+    }
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let file = std::env::temp_dir().join(format!("sync-player-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&file, "bind = \"127.0.0.1:9000\"\n[rooms]\nmax_capacity = 10\nmax_rooms = 5\n").unwrap();
+        let config_path = file.to_str().unwrap();
+
+        let config = load(
+            &["--config", config_path, "--log-level", "warn", "--set", "rooms.max_rooms=7"],
+            &[("SYNC_PLAYER_ROOMS__MAX_CAPACITY", "20"), ("SYNC_PLAYER_LOG__LEVEL", "info"), ("HOME", "/root")],
+        );
+        std::fs::remove_file(&file).unwrap();
+        let config = config.unwrap();
+
+        assert_eq!(config.bind, "127.0.0.1:9000".parse().unwrap());
+        assert_eq!(config.rooms.max_capacity, 20);
+        assert_eq!(config.rooms.max_rooms, 7);
+        assert!(matches!(config.log.level, LogLevel::Warn));
+        assert!(matches!(config.log.format, LogFormat::Json));
+    }
+
+    #[test]
+    fn structured_values_can_be_set_from_the_environment() {
+        let config = load(
+            &[],
+            &[
+                ("SYNC_PLAYER_STORAGE", r#"{ kind = "hub", address = "10.0.0.1:7000" }"#),
+                ("SYNC_PLAYER_HUB__SECRET", "a cluster secret of thirty-two bytes or more"),
+                ("SYNC_PLAYER_HANDLERS", r#"[{ kind = "chat", path = "/a" }, { kind = "chat", path = "/b" }]"#),
+            ],
+        )
+        .unwrap();
+
+        assert!(matches!(config.storage, StorageConfig::Hub { ref address } if address == "10.0.0.1:7000"));
+        assert_eq!(config.handlers.len(), 2);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(matches!(load(&["--set", "rooms.max_capacty=3"], &[]), Err(ConfigError::Deserialize(_))));
+    }
+
+    #[test]
+    fn every_validation_error_is_reported() {
+        let Err(ConfigError::Invalid(errors)) = load(
+            &["--set", "rooms.max_capacity=0", "--set", r#"handlers=[{ kind = "chat", path = "chat/" }, { kind = "chat", path = "chat/" }]"#],
+            &[],
+        ) else {
+            panic!("expected validation errors");
+        };
+        assert_eq!(errors.len(), 4, "{errors:?}");
+    }
+
+    #[test]
+    fn chat_moderation_is_configured_per_handler() {
+        let handlers = r#"[{ kind = "chat", path = "/chat", chat = { moderation = { words = { blocked = ["darn", "oh no"], action = "reject" }, flood = { window_ms = 0 } } } }]"#;
+        let Err(ConfigError::Invalid(errors)) = load(&["--set", &format!("handlers={handlers}")], &[]) else {
+            panic!("expected validation errors");
+        };
+        assert_eq!(
+            errors,
+            [
+                "handler `/chat`: chat.moderation.flood.window_ms must be at least 1",
+                "handler `/chat`: blocked word `oh no` must be letters and digits only",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn each_handler_restores_its_own_rooms() {
+        let snapshot = std::env::temp_dir().join(format!("sync-player-rooms-{}.json", uuid::Uuid::new_v4()));
+        let storage = format!(r#"storage={{ kind = "memory", snapshot = "{}" }}"#, snapshot.display());
+        let handlers = r#"handlers=[{ kind = "chat", path = "/a" }, { kind = "chat", path = "/b" }]"#;
+        let config = load(&["--set", &storage, "--set", handlers], &[]).unwrap();
+
+        async fn serve(config: &Config, handler: &HandlerConfig) -> (client::Client, lobby::Shutdown) {
+            let chat = Arc::new(ChatMessageHandler::new(handler.chat.clone()));
+            let lobby = lobby::setup(chat, config.lobby_config(handler)).await.unwrap();
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move { axum::serve(listener, lobby.router).await });
+            (client::Client::new(&format!("http://{addr}")).unwrap(), lobby.shutdown)
+        }
+        for handler in &config.handlers {
+            let (client, shutdown) = serve(&config, handler).await;
+            client.create_room(&handler.path, 2).await.unwrap();
+            shutdown.shutdown().await.unwrap();
+        }
+
+        for handler in &config.handlers {
+            let (client, _) = serve(&config, handler).await;
+            let rooms = client.list_rooms().await.unwrap();
+            assert_eq!(rooms.iter().map(|room| room.name.as_str()).collect::<Vec<_>>(), [handler.path.as_str()]);
+        }
+        for name in ["a", "b"] {
+            std::fs::remove_file(format!("{}.{name}", snapshot.display())).unwrap();
+        }
+    }
+}
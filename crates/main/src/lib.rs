@@ -0,0 +1,3 @@
+//! Message handlers the server can mount, shared with the clients speaking their protocols.
+
+pub mod chat;
@@ -1,79 +1,134 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
-use async_trait::async_trait;
+use std::time::Duration;
+use anyhow::Context;
 use axum::Router;
-use serde::{Deserialize, Serialize};
-use thiserror::Error;
+use axum::http::header::CONTENT_TYPE;
+use axum::routing::get;
+use axum_server::Handle;
+use clap::Parser;
+use futures_util::future::join_all;
 use tokio::net::TcpListener;
-use lobby::domain::{MessageHandler, MessageResponse, Participant, Room};
+use lobby::Lobby;
+use lobby::health::HealthReport;
+use main::chat::ChatMessageHandler;
+use crate::config::{Cli, Config, HandlerKind, LogFormat};
+
+mod config;
+mod tls;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()>{
-    tracing_subscriber::fmt()
-        .json()
-        .with_max_level(tracing::Level::DEBUG)
-        .with_current_span(false)
-        .init();
-
-    let message_handler = Arc::new(ChatMessageHandler);
-    let lobby_router = lobby::setup(message_handler).await?;
-    let router = Router::new()
-        .nest("/chat", lobby_router);
+    let config = Config::load(Cli::parse())?;
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::from(config.log.level));
+    match config.log.format {
+        LogFormat::Json => subscriber.json().with_current_span(false).init(),
+        LogFormat::Pretty => subscriber.pretty().init(),
+        LogFormat::Compact => subscriber.compact().init(),
+    }
 
-    let listener = TcpListener::bind("0.0.0.0:8080")
-        .await
-        .expect("failed to bind tcp listener");
-    axum::serve(listener, router)
-        .await
-        .expect("http server failed unexpectedly");
-    Ok(())
-}
+    if let Some(hub_listen) = config.hub_listen {
+        let hub = config.hub.clone();
+        let listener = TcpListener::bind(hub_listen).await?;
+        tracing::info!("cluster hub listening on {hub_listen}");
+        tokio::spawn(async move {
+            if let Err(e) = lobby::serve_bus_hub(listener, hub).await {
+                tracing::error!("cluster hub failed: {e:#}");
+            }
+        });
+    }
 
-struct ChatMessageHandler;
+    let mut router = Router::new();
+    let mut shutdowns = Vec::new();
+    let mut metrics = Vec::new();
+    let mut health = Vec::new();
+    for handler in &config.handlers {
+        let lobby_config = config.lobby_config(handler);
+        let Lobby { router: lobby_router, shutdown, metrics: lobby_metrics, health: lobby_health, admin } = match handler.kind {
+            HandlerKind::Chat => lobby::setup(Arc::new(ChatMessageHandler::new(handler.chat.clone())), lobby_config).await?,
+        };
+        router = router.nest(&handler.path, lobby_router);
+        if let Some(admin) = admin {
+            router = router.nest(&format!("/admin{}", handler.path), admin);
+        }
+        shutdowns.push(shutdown);
+        metrics.push(lobby_metrics);
+        health.push(lobby_health);
+    }
+    // one scrape covers every mounted lobby, told apart by their `handler` label
+    let metrics = Arc::new(metrics);
+    router = router.route(
+        "/metrics",
+        get(move || async move {
+            (
+                [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+                lobby::metrics::render_all(&metrics).await,
+            )
+        }),
+    );
+    let health = Arc::new(health);
+    router = router
+        .route("/healthz", get(|| async { HealthReport::alive() }))
+        .route("/readyz", get(move || async move { lobby::health::check_all(&health).await }));
 
-#[derive(Clone, Debug, Deserialize)]
-enum ChatInbound {
-    SendPrivateMessage {
-        to: Participant,
-        content: String,
-    },
-    SendPublicMessage {
-        content: String,
-    },
-    ListParticipants,
-}
+    let handle = Handle::new();
+    let server_handle = handle.clone();
+    let deadline = Duration::from_millis(config.shutdown.deadline_ms);
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        tracing::info!("shutting down");
+        // the lobbies shut down side by side, so together they take no longer than the slowest
+        let shutdowns = join_all(shutdowns.iter().map(|shutdown| shutdown.shutdown()));
+        match tokio::time::timeout(deadline, shutdowns).await {
+            Ok(results) => {
+                for e in results.into_iter().filter_map(Result::err) {
+                    tracing::error!("failed to shut down the lobby gracefully: {e:#}");
+                }
+            }
+            Err(_) => tracing::error!("the lobbies didn't shut down within {deadline:?}"),
+        }
+        server_handle.graceful_shutdown(Some(deadline));
+    });
 
-#[derive(Clone, Debug, Serialize)]
-enum ChatOutbound {
-    PrivateMessage {
-        from: Participant,
-        content: String,
-    },
-    PublicMessage {
-        from: Participant,
-        content: String,
-    },
-    ListOfParticipants {
-        participants: Vec<Participant>,
-    },
+    // connect info lets the lobby rate limit room creation by IP address
+    let app = router.into_make_service_with_connect_info::<SocketAddr>();
+    match &config.tls {
+        Some(tls) => {
+            let rustls_config = tls::load(tls).await?;
+            tracing::info!("listening on https://{}", config.bind);
+            axum_server::bind_rustls(config.bind, rustls_config)
+                .handle(handle)
+                .serve(app)
+                .await
+        }
+        None => {
+            tracing::info!("listening on http://{}", config.bind);
+            axum_server::bind(config.bind).handle(handle).serve(app).await
+        }
+    }
+    .context("http server failed")?;
+    Ok(())
 }
 
-#[derive(Clone, Debug, Error)]
-enum ChatError {}
-
-#[async_trait]
-impl MessageHandler<ChatInbound> for ChatMessageHandler {
-    type Outbound = ChatOutbound;
-    type Err = ChatError;
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install ctrl-c handler");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-    async fn handle_message(&self, room: &Room, from: Participant, msg: ChatInbound) -> Result<MessageResponse<Self::Outbound>, Self::Err> {
-        match msg {
-            ChatInbound::SendPrivateMessage { to, content } =>
-                Ok(MessageResponse::Unicast { to, msg: ChatOutbound::PrivateMessage { from, content } }),
-            ChatInbound::SendPublicMessage { content } =>
-                Ok(MessageResponse::Broadcast { msg: ChatOutbound::PublicMessage { from, content } }),
-            ChatInbound::ListParticipants => {
-                Ok(MessageResponse::Unicast { to: from, msg: ChatOutbound::ListOfParticipants { participants: room.participants.clone() } })
-            }
-        }
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
     }
-}
\ No newline at end of file
+}
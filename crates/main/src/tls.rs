@@ -0,0 +1,117 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::Duration;
+use anyhow::Context;
+use axum_server::tls_rustls::RustlsConfig;
+use crate::config::TlsConfig;
+
+pub async fn load(config: &TlsConfig) -> anyhow::Result<RustlsConfig> {
+    // ring is the only provider compiled in; ignore the error when it's already installed
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    // taken first, so that a change racing the load is still picked up
+    let loaded = fingerprint(config).await;
+    let rustls_config = RustlsConfig::from_pem_file(&config.cert, &config.key)
+        .await
+        .with_context(|| format!("failed to load TLS certificate {} and key {}", config.cert.display(), config.key.display()))?;
+    tokio::spawn(watch(rustls_config.clone(), config.clone(), loaded));
+    Ok(rustls_config)
+}
+
+async fn watch(rustls_config: RustlsConfig, config: TlsConfig, mut loaded: Option<u64>) {
+    let mut interval = tokio::time::interval(Duration::from_millis(config.reload_interval_ms));
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let current = fingerprint(&config).await;
+        if current == loaded {
+            continue;
+        }
+        // the files may be mid-rotation; on failure keep serving the old pair and retry next tick
+        match rustls_config.reload_from_pem_file(&config.cert, &config.key).await {
+            Ok(()) => {
+                tracing::info!("reloaded TLS certificate {}", config.cert.display());
+                loaded = current;
+            }
+            Err(e) => tracing::warn!("failed to reload TLS certificate, keeping the current one: {e}"),
+        }
+    }
+}
+
+/// Modification times may be too coarse to tell quick writes apart.
+async fn fingerprint(config: &TlsConfig) -> Option<u64> {
+    let mut hasher = DefaultHasher::new();
+    tokio::fs::read(&config.cert).await.ok()?.hash(&mut hasher);
+    tokio::fs::read(&config.key).await.ok()?.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use std::sync::Arc;
+    use axum::Router;
+    use axum::routing::get;
+    use rcgen::CertifiedKey;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio_rustls::TlsConnector;
+    use tokio_rustls::rustls::pki_types::ServerName;
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+
+    fn write_self_signed(dir: &Path) -> CertifiedKey {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        std::fs::write(dir.join("key.pem"), certified.key_pair.serialize_pem()).unwrap();
+        std::fs::write(dir.join("cert.pem"), certified.cert.pem()).unwrap();
+        certified
+    }
+
+    async fn get_trusting(addr: SocketAddr, trusted: &CertifiedKey) -> anyhow::Result<String> {
+        let mut roots = RootCertStore::empty();
+        roots.add(trusted.cert.der().clone())?;
+        let client_config = ClientConfig::builder().with_root_certificates(roots).with_no_client_auth();
+        let stream = TcpStream::connect(addr).await?;
+        let mut stream = TlsConnector::from(Arc::new(client_config))
+            .connect(ServerName::try_from("localhost")?, stream)
+            .await?;
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok(response)
+    }
+
+    #[tokio::test]
+    async fn certificates_are_reloaded_when_the_files_change() {
+        let dir = std::env::temp_dir().join(format!("sync-player-tls-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let first = write_self_signed(&dir);
+        let config = TlsConfig {
+            cert: dir.join("cert.pem"),
+            key: dir.join("key.pem"),
+            reload_interval_ms: 20,
+        };
+
+        let handle = axum_server::Handle::new();
+        let router = Router::new().route("/", get(|| async { "ok" }));
+        let server = axum_server::bind_rustls("127.0.0.1:0".parse().unwrap(), load(&config).await.unwrap())
+            .handle(handle.clone())
+            .serve(router.into_make_service());
+        tokio::spawn(server);
+        let addr = handle.listening().await.unwrap();
+
+        assert!(get_trusting(addr, &first).await.unwrap().ends_with("ok"));
+
+        let second = write_self_signed(&dir);
+        let mut reloaded = false;
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            if get_trusting(addr, &second).await.is_ok() {
+                reloaded = true;
+                break;
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(reloaded, "the new certificate was never served");
+        assert!(get_trusting(addr, &first).await.is_err());
+    }
+}
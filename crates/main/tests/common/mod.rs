@@ -0,0 +1,54 @@
+use std::sync::Arc;
+use std::time::Duration;
+use client::{Client, Frame, RoomSocket};
+use lobby::config::LobbyConfig;
+use lobby::domain::RoomId;
+use main::chat::{ChatInbound, ChatMessageHandler, ChatOutbound};
+use tokio::net::TcpListener;
+
+pub type ChatSocket = RoomSocket<ChatInbound, ChatOutbound>;
+
+/// Serves a chat lobby on an ephemeral port, returning its base URL.
+pub async fn serve() -> String {
+    let lobby = lobby::setup(Arc::new(ChatMessageHandler::default()), LobbyConfig::default()).await.unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, lobby.router).await });
+    format!("http://{addr}")
+}
+
+/// Returns once the lobby can deliver messages, skipping the replayed history.
+pub async fn join(client: &Client, room_id: RoomId) -> ChatSocket {
+    let mut socket = client.join(room_id).await.unwrap();
+    // the socket registers for delivery before the lobby reads from it, so any answer
+    // means it is ready
+    socket.send(&ChatInbound::ListParticipants).await.unwrap();
+    loop {
+        match recv(&mut socket).await {
+            ChatOutbound::ListOfParticipants { .. } => return socket,
+            ChatOutbound::History { .. } => {}
+            msg => panic!("expected the list of participants, got {msg:?}"),
+        }
+    }
+}
+
+pub async fn recv(socket: &mut ChatSocket) -> ChatOutbound {
+    match next_frame(socket).await {
+        Frame::Message(msg) => msg,
+        frame => panic!("expected a chat message, got {frame:?}"),
+    }
+}
+
+pub async fn next_frame(socket: &mut ChatSocket) -> Frame<ChatOutbound> {
+    tokio::time::timeout(Duration::from_secs(5), socket.recv())
+        .await
+        .expect("timed out waiting for a frame")
+        .unwrap()
+}
+
+/// Asserts nothing arrives for a little while.
+pub async fn assert_silent(socket: &mut ChatSocket) {
+    if let Ok(frame) = tokio::time::timeout(Duration::from_millis(200), socket.recv()).await {
+        panic!("expected silence, got {frame:?}");
+    }
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use std::time::Duration;
+use client::{Client, ClientError};
+use common::{assert_silent, join, recv, serve};
+use lobby::domain::{Participant, Profile, Room, RoomId};
+use main::chat::{ChatInbound, ChatOutbound};
+
+async fn room(client: &Client, room_id: RoomId) -> Option<Room> {
+    client.list_rooms().await.unwrap().into_iter().find(|room| room.id == room_id)
+}
+
+/// Waits for the lobby to notice the participant is gone.
+async fn wait_until_left(client: &Client, room_id: RoomId, participant: Participant) {
+    for _ in 0..50 {
+        if !room(client, room_id).await.unwrap().is_participant(participant) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("{participant} never left the room");
+}
+
+#[tokio::test]
+async fn participants_chat_leave_and_close_the_room() {
+    let base = serve().await;
+    let alice = Client::new(&base).unwrap();
+    let bob = Client::new(&base).unwrap();
+    alice.update_profile(&Profile { display_name: "alice".to_string(), ..Profile::default() }).await.unwrap();
+    let (alice_id, bob_id) = (alice.participant().await.unwrap(), bob.participant().await.unwrap());
+    let room = alice.create_room("movie night", 4).await.unwrap();
+    assert_eq!(room.created_by, alice_id);
+
+    let mut alice_socket = join(&alice, room.id).await;
+    let mut bob_socket = join(&bob, room.id).await;
+    alice_socket.send(&ChatInbound::SendPublicMessage { content: "hi all".to_string() }).await.unwrap();
+    let public = recv(&mut alice_socket).await;
+    assert!(
+        matches!(&public, ChatOutbound::PublicMessage { from, from_name, content, .. }
+            if *from == alice_id && from_name == "alice" && content == "hi all"),
+        "{public:?}"
+    );
+    assert_eq!(recv(&mut bob_socket).await, public);
+
+    bob_socket.send(&ChatInbound::SendPrivateMessage { to: alice_id, content: "psst".to_string() }).await.unwrap();
+    let private = recv(&mut alice_socket).await;
+    assert!(
+        matches!(&private, ChatOutbound::PrivateMessage { from, content, .. } if *from == bob_id && content == "psst"),
+        "{private:?}"
+    );
+    assert_silent(&mut bob_socket).await;
+
+    bob_socket.close().await.unwrap();
+    wait_until_left(&alice, room.id, bob_id).await;
+    assert_eq!(self::room(&alice, room.id).await.unwrap().participants, [alice_id]);
+
+    alice.close_room(room.id).await.unwrap();
+    assert!(self::room(&alice, room.id).await.is_none());
+}
+
+#[tokio::test]
+async fn full_rooms_turn_participants_away() {
+    let base = serve().await;
+    let alice = Client::new(&base).unwrap();
+    let bob = Client::new(&base).unwrap();
+    let room = alice.create_room("just me", 1).await.unwrap();
+    let _alice_socket = join(&alice, room.id).await;
+
+    let result = bob.join::<ChatInbound, ChatOutbound>(room.id).await;
+
+    assert!(matches!(result, Err(ClientError::Status { status: 400, .. })), "{:?}", result.err());
+    assert_eq!(self::room(&alice, room.id).await.unwrap().participants.len(), 1);
+}
+
+#[tokio::test]
+async fn only_the_owner_closes_a_room() {
+    let base = serve().await;
+    let alice = Client::new(&base).unwrap();
+    let mallory = Client::new(&base).unwrap();
+    let room = alice.create_room("alice's", 4).await.unwrap();
+
+    let result = mallory.close_room(room.id).await;
+
+    assert!(matches!(result, Err(ClientError::Status { status: 401, .. })), "{:?}", result.err());
+    assert!(self::room(&alice, room.id).await.is_some());
+}
+
+#[tokio::test]
+async fn rate_limited_room_creation_says_when_to_retry() {
+    let base = serve().await;
+    let alice = Client::new(&base).unwrap();
+    for i in 0..5 {
+        alice.create_room(&format!("room {i}"), 4).await.unwrap();
+    }
+
+    let result = alice.create_room("one too many", 4).await;
+
+    assert!(
+        matches!(result, Err(ClientError::Status { status: 429, retry_after_secs: Some(10), .. })),
+        "{:?}",
+        result.err()
+    );
+}
+
+#[tokio::test]
+async fn dropped_connections_free_their_seat() {
+    let base = serve().await;
+    let alice = Client::new(&base).unwrap();
+    let bob = Client::new(&base).unwrap();
+    let carol = Client::new(&base).unwrap();
+    let room = alice.create_room("two seats", 2).await.unwrap();
+    let mut alice_socket = join(&alice, room.id).await;
+    let bob_socket = join(&bob, room.id).await;
+
+    // gone without a close frame, like a crashed browser tab
+    drop(bob_socket);
+    wait_until_left(&alice, room.id, bob.participant().await.unwrap()).await;
+
+    let mut carol_socket = join(&carol, room.id).await;
+    carol_socket.send(&ChatInbound::SendPublicMessage { content: "hello?".to_string() }).await.unwrap();
+    assert!(matches!(recv(&mut alice_socket).await, ChatOutbound::PublicMessage { .. }));
+}
+
+#[tokio::test]
+async fn identities_carry_over_to_new_clients() {
+    let base = serve().await;
+    let alice = Client::new(&base).unwrap();
+    let room = alice.create_room("alice's", 4).await.unwrap();
+
+    let restored = Client::with_identity(&base, &alice.identity().unwrap()).unwrap();
+
+    assert_eq!(restored.participant().await.unwrap(), room.created_by);
+    restored.close_room(room.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn late_joiners_are_sent_what_was_said() {
+    let base = serve().await;
+    let alice = Client::new(&base).unwrap();
+    let bob = Client::new(&base).unwrap();
+    let room = alice.create_room("movie night", 4).await.unwrap();
+    let mut alice_socket = join(&alice, room.id).await;
+    let mut sent = Vec::new();
+    for content in ["first", "second"] {
+        alice_socket.send(&ChatInbound::SendPublicMessage { content: content.to_string() }).await.unwrap();
+        let ChatOutbound::PublicMessage { id, .. } = recv(&mut alice_socket).await else {
+            panic!("expected the message back");
+        };
+        sent.push((id, content));
+    }
+
+    let mut bob_socket = bob.join::<ChatInbound, ChatOutbound>(room.id).await.unwrap();
+
+    let ChatOutbound::History { messages, has_more } = recv(&mut bob_socket).await else {
+        panic!("expected the history first");
+    };
+    let contents: Vec<_> = messages.iter().map(|msg| (msg.id, msg.content.as_str())).collect();
+    assert_eq!(contents, sent);
+    assert!(!has_more);
+}
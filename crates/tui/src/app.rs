@@ -0,0 +1,365 @@
+use std::collections::HashSet;
+use std::time::Instant;
+use client::Frame;
+use lobby::{LobbyNotice, SignalKind};
+use lobby::domain::{Participant, Room, RoomId};
+use main::chat::{ChatInbound, ChatOutbound, ChatParticipant, MessageId, Playback};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+pub struct App {
+    pub me: Participant,
+    pub rooms: Vec<Room>,
+    pub selected: usize,
+    pub joined: Option<RoomId>,
+    pub participants: Vec<ChatParticipant>,
+    /// What participants are signalling: typing, listening or buffering.
+    pub signals: HashSet<(Participant, SignalKind)>,
+    /// What the room is playing, and when this client was told.
+    pub playback: Option<(Playback, Instant)>,
+    pub log: Vec<LogEntry>,
+    pub mode: Mode,
+    pub input: String,
+    pub status: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Picking a room from the list.
+    Browsing,
+    /// Typing the name of a room to open.
+    Naming,
+    /// Typing messages to the joined room.
+    Chatting,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LogEntry {
+    Public { id: MessageId, from_name: String, content: String, edited: bool },
+    Private { from_name: String, content: String },
+    /// From the lobby or this client rather than another participant.
+    Notice(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    None,
+    Quit,
+    Refresh,
+    Open(String),
+    Join(RoomId),
+    Leave,
+    Send(ChatInbound),
+    /// Started typing a message, or stopped without sending it.
+    Typing(bool),
+}
+
+impl App {
+    pub fn new(me: Participant) -> Self {
+        let mut app = Self {
+            me,
+            rooms: Vec::new(),
+            selected: 0,
+            joined: None,
+            participants: Vec::new(),
+            signals: HashSet::new(),
+            playback: None,
+            log: Vec::new(),
+            mode: Mode::Browsing,
+            input: String::new(),
+            status: String::new(),
+        };
+        app.browse();
+        app
+    }
+
+    pub fn on_key(&mut self, key: KeyEvent) -> Action {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Action::Quit;
+        }
+        match self.mode {
+            Mode::Browsing => match key.code {
+                KeyCode::Char('q') => Action::Quit,
+                KeyCode::Char('r') => Action::Refresh,
+                KeyCode::Char('n') => {
+                    self.mode = Mode::Naming;
+                    self.status = "name the room · enter open · esc cancel".to_string();
+                    Action::None
+                }
+                KeyCode::Up => {
+                    self.selected = self.selected.saturating_sub(1);
+                    Action::None
+                }
+                KeyCode::Down => {
+                    self.selected = (self.selected + 1).min(self.rooms.len().saturating_sub(1));
+                    Action::None
+                }
+                KeyCode::Enter => match self.rooms.get(self.selected) {
+                    Some(room) => Action::Join(room.id),
+                    None => Action::None,
+                },
+                _ => Action::None,
+            },
+            Mode::Naming | Mode::Chatting => match key.code {
+                KeyCode::Esc if self.mode == Mode::Naming => {
+                    self.input.clear();
+                    self.browse();
+                    Action::None
+                }
+                KeyCode::Esc => Action::Leave,
+                KeyCode::Char(c) => {
+                    let started = self.input.is_empty();
+                    self.input.push(c);
+                    match self.mode == Mode::Chatting && started {
+                        true => Action::Typing(true),
+                        false => Action::None,
+                    }
+                }
+                KeyCode::Backspace => {
+                    let erased = self.input.pop().is_some() && self.input.is_empty();
+                    match self.mode == Mode::Chatting && erased {
+                        true => Action::Typing(false),
+                        false => Action::None,
+                    }
+                }
+                KeyCode::Enter if self.input.trim().is_empty() => Action::None,
+                KeyCode::Enter => {
+                    let input = std::mem::take(&mut self.input);
+                    match self.mode {
+                        Mode::Naming => {
+                            self.browse();
+                            Action::Open(input.trim().to_string())
+                        }
+                        _ => self.message(&input),
+                    }
+                }
+                _ => Action::None,
+            },
+        }
+    }
+
+    /// A public message, or `/w name message` to whisper to the participant shown as `name`.
+    fn message(&mut self, input: &str) -> Action {
+        let Some(whisper) = input.strip_prefix("/w ") else {
+            return Action::Send(ChatInbound::SendPublicMessage { content: input.to_string() });
+        };
+        let (name, content) = whisper.split_once(' ').unwrap_or((whisper, ""));
+        match self.participants.iter().find(|participant| participant.display_name == name) {
+            Some(participant) if !content.is_empty() => {
+                self.log.push(LogEntry::Private { from_name: format!("me → {name}"), content: content.to_string() });
+                Action::Send(ChatInbound::SendPrivateMessage { to: participant.id, content: content.to_string() })
+            }
+            Some(_) => {
+                self.status = "usage: /w name message".to_string();
+                Action::None
+            }
+            None => {
+                self.status = format!("nobody called {name} is here");
+                Action::None
+            }
+        }
+    }
+
+    pub fn on_frame(&mut self, frame: Frame<ChatOutbound>) {
+        match frame {
+            Frame::Message(ChatOutbound::PublicMessage { id, from_name, content, .. }) => {
+                self.log.push(LogEntry::Public { id, from_name, content, edited: false })
+            }
+            Frame::Message(ChatOutbound::PrivateMessage { from_name, content, .. }) => {
+                self.log.push(LogEntry::Private { from_name, content })
+            }
+            Frame::Message(ChatOutbound::ListOfParticipants { participants }) => self.participants = participants,
+            // what was said before joining goes above anything said since
+            Frame::Message(ChatOutbound::History { messages, .. }) => {
+                let said = messages.into_iter().map(|msg| LogEntry::Public {
+                    id: msg.id,
+                    from_name: msg.from_name,
+                    content: msg.content,
+                    edited: msg.edited_at.is_some(),
+                });
+                self.log.splice(0..0, said);
+            }
+            Frame::Message(ChatOutbound::MessageEdited { id, content: new_content, .. }) => {
+                for entry in &mut self.log {
+                    if let LogEntry::Public { id: entry_id, content, edited, .. } = entry {
+                        if *entry_id == id {
+                            *content = new_content.clone();
+                            *edited = true;
+                        }
+                    }
+                }
+            }
+            Frame::Message(ChatOutbound::MessageDeleted { id }) => {
+                self.log.retain(|entry| !matches!(entry, LogEntry::Public { id: entry_id, .. } if *entry_id == id))
+            }
+            // reactions aren't shown
+            Frame::Message(ChatOutbound::ReactionsChanged { .. }) => {}
+            Frame::Message(ChatOutbound::Mentioned { from_name, .. }) => {
+                self.status = format!("{from_name} mentioned you");
+            }
+            Frame::Message(ChatOutbound::MessageRejected { reason, .. }) => {
+                self.status = format!("message not sent: {reason}");
+            }
+            Frame::Message(ChatOutbound::PlaybackChanged { playback }) => self.playback = Some((playback, Instant::now())),
+            Frame::Message(ChatOutbound::Refused { reason }) => self.status = format!("refused: {reason}"),
+            Frame::Notice(LobbyNotice::RateLimited { retry_after_ms }) => {
+                self.notice(format!("slow down, the last message was dropped; retry in {retry_after_ms}ms"))
+            }
+            Frame::Notice(LobbyNotice::Signal { from, kind, active }) => {
+                match active {
+                    true => self.signals.insert((from, kind)),
+                    false => self.signals.remove(&(from, kind)),
+                };
+            }
+            Frame::Notice(LobbyNotice::Kicked { reason }) => self.notice(format!("kicked: {reason}")),
+            Frame::Notice(LobbyNotice::Announcement { message }) => self.notice(format!("announcement: {message}")),
+            Frame::Notice(LobbyNotice::ServerGoingAway { reconnect_after_ms, .. }) => {
+                self.notice(format!("the server is going away; reconnect in {reconnect_after_ms}ms"))
+            }
+            Frame::Closed { reason, .. } if reason.is_empty() => self.left("the lobby closed the socket".to_string()),
+            Frame::Closed { reason, .. } => self.left(format!("the lobby closed the socket: {reason}")),
+        }
+    }
+
+    pub fn joined(&mut self, room_id: RoomId) {
+        self.joined = Some(room_id);
+        self.mode = Mode::Chatting;
+        self.log.clear();
+        self.status = "type to chat · /w name message to whisper · esc leave".to_string();
+    }
+
+    /// Back to browsing once the room was left, saying why.
+    pub fn left(&mut self, why: String) {
+        self.joined = None;
+        self.participants.clear();
+        self.signals.clear();
+        self.playback = None;
+        self.notice(why);
+        self.browse();
+    }
+
+    /// Display names of the participants signalling `kind`.
+    pub fn signalling(&self, kind: SignalKind) -> Vec<&str> {
+        self.participants
+            .iter()
+            .filter(|participant| self.signals.contains(&(participant.id, kind)))
+            .map(|participant| participant.display_name.as_str())
+            .collect()
+    }
+
+    /// Where playback is at `now`, counting on from the last position while playing.
+    pub fn position_ms_at(&self, now: Instant) -> Option<u64> {
+        let (playback, received) = self.playback.as_ref()?;
+        let elapsed = match playback.playing {
+            true => now.saturating_duration_since(*received).as_millis() as u64,
+            false => 0,
+        };
+        Some(playback.position_ms.saturating_add(elapsed).min(playback.duration_ms))
+    }
+
+    pub fn notice(&mut self, notice: String) {
+        self.log.push(LogEntry::Notice(notice));
+    }
+
+    pub fn set_rooms(&mut self, rooms: Vec<Room>) {
+        self.rooms = rooms;
+        self.rooms.sort_by_key(|room| room.created_at);
+        self.selected = self.selected.min(self.rooms.len().saturating_sub(1));
+    }
+
+    fn browse(&mut self) {
+        self.mode = Mode::Browsing;
+        self.status = "↑↓ pick a room · enter join · n new room · r refresh · q quit".to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    fn type_line(app: &mut App, line: &str) -> Action {
+        for c in line.chars() {
+            app.on_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+        app.on_key(KeyEvent::from(KeyCode::Enter))
+    }
+
+    fn chatting() -> App {
+        let mut app = App::new(Uuid::new_v4());
+        app.joined(Uuid::new_v4());
+        let bob = ChatParticipant { id: Uuid::new_v4(), display_name: "bob".to_string(), profile: None };
+        app.on_frame(Frame::Message(ChatOutbound::ListOfParticipants { participants: vec![bob] }));
+        app
+    }
+
+    #[test]
+    fn typed_lines_are_sent_publicly_or_whispered_by_name() {
+        let mut app = chatting();
+        let bob = app.participants[0].id;
+
+        assert_eq!(
+            type_line(&mut app, "hello"),
+            Action::Send(ChatInbound::SendPublicMessage { content: "hello".to_string() })
+        );
+        assert_eq!(
+            type_line(&mut app, "/w bob psst"),
+            Action::Send(ChatInbound::SendPrivateMessage { to: bob, content: "psst".to_string() })
+        );
+        assert_eq!(app.on_key(KeyEvent::from(KeyCode::Char('x'))), Action::Typing(true));
+        assert_eq!(app.on_key(KeyEvent::from(KeyCode::Backspace)), Action::Typing(false));
+        assert_eq!(type_line(&mut app, "/w carol hi"), Action::None);
+        assert_eq!(app.status, "nobody called carol is here");
+        assert!(app.input.is_empty());
+    }
+
+    #[test]
+    fn frames_fill_the_log_until_the_room_is_left() {
+        let mut app = chatting();
+        let from = app.participants[0].id;
+        let id = uuid::Uuid::new_v4();
+
+        app.on_frame(Frame::Message(ChatOutbound::PublicMessage {
+            id,
+            from,
+            from_name: "bob".to_string(),
+            content: "hi".to_string(),
+            mentions: Vec::new(),
+            sent_at: chrono::Utc::now(),
+        }));
+        app.on_frame(Frame::Message(ChatOutbound::MessageEdited {
+            id,
+            content: "hey".to_string(),
+            mentions: Vec::new(),
+            edited_at: chrono::Utc::now(),
+        }));
+        app.on_frame(Frame::Notice(LobbyNotice::Signal { from, kind: SignalKind::Buffering, active: true }));
+        assert_eq!(app.signalling(SignalKind::Buffering), ["bob"]);
+        assert!(app.signalling(SignalKind::Listening).is_empty());
+        app.on_frame(Frame::Message(ChatOutbound::PlaybackChanged {
+            playback: Playback {
+                track: "intro.mp3".to_string(),
+                position_ms: 89_000,
+                duration_ms: 90_000,
+                playing: true,
+                set_by: from,
+                set_at: chrono::Utc::now(),
+            },
+        }));
+        let received = app.playback.as_ref().unwrap().1;
+        assert_eq!(app.position_ms_at(received + Duration::from_millis(500)), Some(89_500));
+        assert_eq!(app.position_ms_at(received + Duration::from_secs(5)), Some(90_000));
+        app.on_frame(Frame::Notice(LobbyNotice::Kicked { reason: "flooding".to_string() }));
+        app.on_frame(Frame::Closed { code: Some(1008), reason: "flooding".to_string() });
+
+        assert_eq!(
+            app.log[0],
+            LogEntry::Public { id, from_name: "bob".to_string(), content: "hey".to_string(), edited: true }
+        );
+        assert_eq!(app.log[1], LogEntry::Notice("kicked: flooding".to_string()));
+        assert!(app.signals.is_empty());
+        assert!(app.playback.is_none());
+        assert_eq!((app.joined, app.mode), (None, Mode::Browsing));
+        assert!(app.participants.is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::Duration;
+use clap::Parser;
+use client::{Client, Frame, RoomSocket};
+use lobby::SignalKind;
+use lobby::domain::Profile;
+use main::chat::{ChatInbound, ChatOutbound};
+use ratatui::DefaultTerminal;
+use ratatui::crossterm::event::{self, Event, KeyEventKind};
+use tokio::sync::mpsc;
+use crate::app::{Action, App};
+
+mod app;
+mod ui;
+
+type ChatSocket = RoomSocket<ChatInbound, ChatOutbound>;
+
+#[derive(Debug, Parser)]
+#[command(about = "Chats in the rooms of a sync-player lobby from a terminal")]
+struct Cli {
+    /// Base URL of the chat lobby.
+    #[arg(long, env = "SYNC_PLAYER_URL", default_value = "http://127.0.0.1:8080/chat")]
+    url: String,
+    /// Name shown to the other participants instead of the start of the participant id.
+    #[arg(long, env = "SYNC_PLAYER_NAME")]
+    name: Option<String>,
+}
+
+/// The lobby doesn't announce joins and leaves.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(3);
+
+/// How often the position bar moves on while nothing else happens.
+const REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let client = Client::new(&cli.url)?;
+    if let Some(name) = cli.name {
+        client.update_profile(&Profile { display_name: name, ..Profile::default() }).await?;
+    }
+    let mut app = App::new(client.participant().await?);
+    app.set_rooms(client.list_rooms().await?);
+
+    let terminal = ratatui::init();
+    let result = run(terminal, &client, &mut app).await;
+    ratatui::restore();
+    result
+}
+
+async fn run(mut terminal: DefaultTerminal, client: &Client, app: &mut App) -> anyhow::Result<()> {
+    let mut events = terminal_events();
+    let mut socket: Option<ChatSocket> = None;
+    let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+    let mut redraw = tokio::time::interval(REDRAW_INTERVAL);
+    loop {
+        terminal.draw(|frame| ui::draw(frame, app))?;
+        let action = tokio::select! {
+            event = events.recv() => match event {
+                Some(Event::Key(key)) if key.kind == KeyEventKind::Press => app.on_key(key),
+                Some(_) => Action::None,
+                None => Action::Quit,
+            },
+            frame = recv(&mut socket) => {
+                match frame {
+                    Ok(frame) => {
+                        if matches!(frame, Frame::Closed { .. }) {
+                            socket = None;
+                        }
+                        app.on_frame(frame);
+                    }
+                    Err(e) => {
+                        socket = None;
+                        app.left(format!("lost the connection: {e}"));
+                    }
+                }
+                Action::None
+            }
+            _ = refresh.tick() => Action::Refresh,
+            _ = redraw.tick() => Action::None,
+        };
+        match action {
+            Action::None => {}
+            Action::Quit => break,
+            Action::Refresh => {
+                refresh_rooms(client, app).await;
+                if let Some(socket) = &mut socket {
+                    send(socket, app, &ChatInbound::ListParticipants).await;
+                    // a half-typed message keeps the typing signal from expiring
+                    if !app.input.is_empty() {
+                        signal_typing(socket, app, true).await;
+                    }
+                }
+            }
+            Action::Open(name) => match client.create_room(&name, 10).await {
+                Ok(room) => {
+                    refresh_rooms(client, app).await;
+                    app.selected = app.rooms.iter().position(|r| r.id == room.id).unwrap_or(app.selected);
+                }
+                Err(e) => app.status = format!("failed to open the room: {e}"),
+            },
+            Action::Join(room_id) => {
+                if let Some(socket) = socket.take() {
+                    let _ = socket.close().await;
+                }
+                match client.join(room_id).await {
+                    Ok(mut joined) => {
+                        app.joined(room_id);
+                        send(&mut joined, app, &ChatInbound::ListParticipants).await;
+                        socket = Some(joined);
+                        refresh_rooms(client, app).await;
+                    }
+                    Err(e) => app.status = format!("failed to join: {e}"),
+                }
+            }
+            Action::Leave => {
+                if let Some(socket) = socket.take() {
+                    let _ = socket.close().await;
+                }
+                app.left("left the room".to_string());
+                refresh_rooms(client, app).await;
+            }
+            Action::Send(msg) => {
+                if let Some(socket) = &mut socket {
+                    send(socket, app, &msg).await;
+                    signal_typing(socket, app, false).await;
+                }
+            }
+            Action::Typing(typing) => {
+                if let Some(socket) = &mut socket {
+                    signal_typing(socket, app, typing).await;
+                }
+            }
+        }
+    }
+    if let Some(socket) = socket {
+        let _ = socket.close().await;
+    }
+    Ok(())
+}
+
+async fn refresh_rooms(client: &Client, app: &mut App) {
+    match client.list_rooms().await {
+        Ok(rooms) => app.set_rooms(rooms),
+        Err(e) => app.status = format!("failed to list the rooms: {e}"),
+    }
+}
+
+async fn send(socket: &mut ChatSocket, app: &mut App, msg: &ChatInbound) {
+    if let Err(e) = socket.send(msg).await {
+        app.status = format!("failed to send: {e}");
+    }
+}
+
+async fn signal_typing(socket: &mut ChatSocket, app: &mut App, typing: bool) {
+    if let Err(e) = socket.signal(SignalKind::Typing, typing).await {
+        app.status = format!("failed to signal typing: {e}");
+    }
+}
+
+async fn recv(socket: &mut Option<ChatSocket>) -> Result<Frame<ChatOutbound>, client::ClientError> {
+    match socket {
+        Some(socket) => socket.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Reads terminal events on their own thread, as crossterm only offers a blocking read.
+fn terminal_events() -> mpsc::Receiver<Event> {
+    let (sender, receiver) = mpsc::channel(16);
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if sender.blocking_send(event).is_err() {
+                return;
+            }
+        }
+    });
+    receiver
+}
@@ -0,0 +1,131 @@
+use std::time::Instant;
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Gauge, List, ListItem, ListState, Paragraph, Wrap};
+use lobby::SignalKind;
+use crate::app::{App, LogEntry, Mode};
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [main, input, status] = Layout::vertical([Constraint::Min(5), Constraint::Length(3), Constraint::Length(1)])
+        .areas(frame.area());
+    let [side, room] = Layout::horizontal([Constraint::Percentage(30), Constraint::Percentage(70)]).areas(main);
+    let [rooms, participants] = Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(side);
+    let [now_playing, log] = Layout::vertical([Constraint::Length(6), Constraint::Min(3)]).areas(room);
+
+    draw_rooms(frame, app, rooms);
+    draw_participants(frame, app, participants);
+    draw_now_playing(frame, app, now_playing);
+    draw_log(frame, app, log);
+    let title = match app.mode {
+        Mode::Browsing => "",
+        Mode::Naming => "New room",
+        Mode::Chatting => "Message",
+    };
+    frame.render_widget(Paragraph::new(app.input.as_str()).block(Block::bordered().title(title)), input);
+    if app.mode != Mode::Browsing {
+        frame.set_cursor_position((input.x + 1 + app.input.chars().count() as u16, input.y + 1));
+    }
+    frame.render_widget(Paragraph::new(app.status.as_str()).dim(), status);
+}
+
+fn draw_rooms(frame: &mut Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .rooms
+        .iter()
+        .map(|room| {
+            let marker = if app.joined == Some(room.id) { "● " } else { "  " };
+            ListItem::new(Line::from(vec![
+                Span::raw(marker),
+                Span::raw(room.name.clone()),
+                Span::raw(format!(" {}/{}", room.participants.len(), room.capacity)).dim(),
+            ]))
+        })
+        .collect();
+    let mut state = ListState::default().with_selected((!app.rooms.is_empty()).then_some(app.selected));
+    let list = List::new(items)
+        .block(Block::bordered().title("Rooms"))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_participants(frame: &mut Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .participants
+        .iter()
+        .map(|participant| {
+            let name = Span::raw(participant.display_name.clone());
+            match participant.id == app.me {
+                true => ListItem::new(Line::from(vec![name.bold(), Span::raw(" (you)").dim()])),
+                false if app.signals.contains(&(participant.id, SignalKind::Typing)) => {
+                    ListItem::new(Line::from(vec![name, Span::raw(" typing…").italic().dim()]))
+                }
+                false => ListItem::new(Line::from(name)),
+            }
+        })
+        .collect();
+    frame.render_widget(List::new(items).block(Block::bordered().title("Participants")), area);
+}
+
+fn draw_now_playing(frame: &mut Frame, app: &App, area: Rect) {
+    let line = |label: &str, kind| match app.signalling(kind) {
+        names if names.is_empty() => Line::from(format!("{label}: nobody")).dim(),
+        names => Line::from(format!("{label}: {}", names.join(", "))),
+    };
+    let block = Block::bordered().title("Now playing");
+    let [track, bar, signals] =
+        Layout::vertical([Constraint::Length(1), Constraint::Length(1), Constraint::Min(2)]).areas(block.inner(area));
+    frame.render_widget(block, area);
+    let paragraph = Paragraph::new(vec![line("Listening", SignalKind::Listening), line("Buffering", SignalKind::Buffering)])
+        .wrap(Wrap { trim: true });
+    frame.render_widget(paragraph, signals);
+
+    let (Some((playback, _)), Some(position_ms)) = (&app.playback, app.position_ms_at(Instant::now())) else {
+        frame.render_widget(Paragraph::new("nothing playing").dim(), track);
+        return;
+    };
+    let state = if playback.playing { "▶" } else { "⏸" };
+    frame.render_widget(Paragraph::new(format!("{state} {}", playback.track)), track);
+    let ratio = match playback.duration_ms {
+        0 => 0.0,
+        duration_ms => position_ms as f64 / duration_ms as f64,
+    };
+    let gauge = Gauge::default()
+        .ratio(ratio)
+        .label(format!("{} / {}", clock(position_ms), clock(playback.duration_ms)))
+        .gauge_style(Style::new().fg(Color::Cyan));
+    frame.render_widget(gauge, bar);
+}
+
+/// `m:ss`, as players show positions.
+fn clock(ms: u64) -> String {
+    let secs = ms / 1000;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+fn draw_log(frame: &mut Frame, app: &App, area: Rect) {
+    let lines: Vec<Line> = app
+        .log
+        .iter()
+        .map(|entry| match entry {
+            LogEntry::Public { from_name, content, edited, .. } => Line::from(vec![
+                Span::raw(format!("{from_name}: ")).bold(),
+                Span::raw(content.clone()),
+                Span::raw(if *edited { " (edited)" } else { "" }).dim(),
+            ]),
+            LogEntry::Private { from_name, content } => Line::from(vec![
+                Span::raw(format!("{from_name} (private): ")).bold().fg(Color::Magenta),
+                Span::raw(content.clone()).fg(Color::Magenta),
+            ]),
+            LogEntry::Notice(notice) => Line::from(notice.clone()).italic().fg(Color::Yellow),
+        })
+        .collect();
+    // keep the latest messages in view, ignoring wrapping
+    let height = area.height.saturating_sub(2) as usize;
+    let skip = lines.len().saturating_sub(height);
+    let paragraph = Paragraph::new(lines.into_iter().skip(skip).collect::<Vec<_>>())
+        .wrap(Wrap { trim: false })
+        .block(Block::bordered().title("Chat"));
+    frame.render_widget(paragraph, area);
+}